@@ -4,15 +4,14 @@ use std::net::SocketAddr;
 use tracing::info;
 use tracing_subscriber::EnvFilter;
 
-async fn handle_event(Json(payload): Json<Value>) -> impl IntoResponse {
-    info!("=== Received Nostr Event ===");
-    info!(
-        "{}",
-        serde_json::to_string_pretty(&payload).unwrap()
-    );
+async fn handle_event(Json(batch): Json<Vec<Value>>) -> impl IntoResponse {
+    info!("=== Received batch of {} Nostr Events ===", batch.len());
+    for event in &batch {
+        info!("{}", serde_json::to_string_pretty(event).unwrap());
+    }
     info!("============================");
 
-    (StatusCode::OK, "Event received successfully")
+    (StatusCode::OK, "Events received successfully")
 }
 
 #[tokio::main]
@@ -1,4 +1,173 @@
-use prometheus::{register_gauge, register_histogram, register_int_counter, Gauge, Histogram, IntCounter};
+use crate::output::circuit_breaker::BreakerState;
+use dashmap::{DashMap, DashSet};
+use prometheus::{
+    Gauge, GaugeVec, Histogram, IntCounter, IntCounterVec, register_gauge, register_gauge_vec,
+    register_histogram, register_int_counter, register_int_counter_vec,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Bucket upper bounds (seconds) for relay-to-downstream latency, sub-second to multi-second
+const END_TO_END_LATENCY_BUCKETS: [f64; 14] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+];
+
+/// Bucket upper bounds (events) for downstream batch sizes
+const BATCH_SIZE_BUCKETS: [f64; 10] = [
+    1.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0,
+];
+
+/// Event kinds reported under their own `kind` label; everything else is counted as `other`
+const TRACKED_KINDS: [u16; 16] = [
+    0, 1, 3, 4, 5, 6, 7, 16, 1059, 1063, 1984, 9734, 9735, 10002, 30023, 31990,
+];
+
+/// Label for event kinds outside `TRACKED_KINDS`
+const OTHER_KIND_LABEL: &str = "other";
+
+/// `layer` label values for duplicates caught by each exact-match dedup layer
+pub const DEDUP_LAYER_HOTSET: &str = "hotset";
+pub const DEDUP_LAYER_LRU: &str = "lru";
+pub const DEDUP_LAYER_ROCKSDB: &str = "rocksdb";
+
+/// Default span of the rolling duplicate ratio
+const DEFAULT_DUPLICATE_RATIO_WINDOW: Duration = Duration::from_secs(60);
+
+/// Per-second dedup outcome counts over a sliding window, with running totals
+struct DuplicateWindow {
+    window_secs: u64,
+    started: Instant,
+    inner: Mutex<DuplicateWindowInner>,
+}
+
+#[derive(Default)]
+struct DuplicateWindowInner {
+    /// `(second since start, events checked, duplicates)`, oldest first
+    buckets: VecDeque<(u64, u64, u64)>,
+    total: u64,
+    duplicates: u64,
+}
+
+impl DuplicateWindow {
+    fn new(window: Duration) -> Self {
+        Self {
+            window_secs: window.as_secs().max(1),
+            started: Instant::now(),
+            inner: Mutex::new(DuplicateWindowInner::default()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, DuplicateWindowInner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Drop buckets that fell out of the window ending at `now`
+    fn evict(&self, inner: &mut DuplicateWindowInner, now: u64) {
+        while let Some(&(second, total, duplicates)) = inner.buckets.front() {
+            if second + self.window_secs > now {
+                break;
+            }
+            inner.total -= total;
+            inner.duplicates -= duplicates;
+            inner.buckets.pop_front();
+        }
+    }
+
+    fn record(&self, duplicate: bool) -> f64 {
+        let now = self.started.elapsed().as_secs();
+        let mut inner = self.lock();
+        self.evict(&mut inner, now);
+        match inner.buckets.back_mut() {
+            Some(bucket) if bucket.0 == now => {
+                bucket.1 += 1;
+                bucket.2 += u64::from(duplicate);
+            }
+            _ => inner.buckets.push_back((now, 1, u64::from(duplicate))),
+        }
+        inner.total += 1;
+        inner.duplicates += u64::from(duplicate);
+        Self::ratio_of(&inner)
+    }
+
+    fn clear(&self) {
+        *self.lock() = DuplicateWindowInner::default();
+    }
+
+    fn ratio(&self) -> f64 {
+        let now = self.started.elapsed().as_secs();
+        let mut inner = self.lock();
+        self.evict(&mut inner, now);
+        Self::ratio_of(&inner)
+    }
+
+    fn ratio_of(inner: &DuplicateWindowInner) -> f64 {
+        if inner.total == 0 {
+            0.0
+        } else {
+            inner.duplicates as f64 / inner.total as f64
+        }
+    }
+}
+
+/// Response of `/api/metrics/summary`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricsSummary {
+    /// Events accepted as new and forwarded
+    pub events_processed_total: u64,
+    /// Events dropped as duplicates
+    pub duplicates_filtered_total: u64,
+    /// Duplicates / events checked over the rolling `duplicate_ratio_window_secs`
+    pub duplicate_ratio: f64,
+    /// Events waiting to be routed downstream
+    pub events_in_queue: f64,
+    /// Connected relays
+    pub active_connections: f64,
+    pub memory_usage_mb: f64,
+    /// Counters of every relay currently in the pool, by relay URL
+    pub relays: BTreeMap<String, RelaySummary>,
+    /// Delivery counters of every downstream sink, by endpoint
+    pub downstream: BTreeMap<String, DeliverySummary>,
+    /// Mean events per downstream batch, `None` before the first batch
+    pub average_batch_size: Option<f64>,
+    pub end_to_end_latency_seconds: LatencyPercentiles,
+}
+
+/// Per-relay counters in the metrics summary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelaySummary {
+    /// Events received, including ones later rejected or filtered
+    pub events_received_total: u64,
+    /// Serialized JSON bytes of the received events
+    pub bytes_received_total: u64,
+    /// Receive rate over the last health check interval
+    pub bytes_per_second: f64,
+    pub duplicates_total: u64,
+    /// Unix time in seconds of the last event received (0 before the first)
+    pub last_event_timestamp: i64,
+    pub seconds_since_last_event: f64,
+}
+
+/// Per-sink delivery counters in the metrics summary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeliverySummary {
+    /// Batches delivered
+    pub success_total: u64,
+    /// Failed delivery attempts
+    pub failures_total: u64,
+    /// Circuit breaker state: 0 closed, 1 open, 2 half-open
+    pub circuit_state: f64,
+}
+
+/// Relay-to-downstream latency percentiles in seconds, `None` before the first observation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatencyPercentiles {
+    pub p50: Option<f64>,
+    pub p95: Option<f64>,
+    pub p99: Option<f64>,
+}
 
 /// Metrics for monitoring the relay system
 pub struct Metrics {
@@ -8,6 +177,45 @@ pub struct Metrics {
     pub memory_usage: Gauge,
     pub active_connections: Gauge,
     pub events_in_queue: Gauge,
+    pub events_dropped: IntCounter,
+    pub relays_failed: IntCounter,
+    pub relays_evicted: IntCounter,
+    pub ws_slow_client_disconnects: IntCounter,
+    pub ws_serialize_errors: IntCounter,
+    pub relay_events_received: IntCounterVec,
+    pub relay_bytes_received: IntCounterVec,
+    pub relay_duplicates: IntCounterVec,
+    pub relay_attributions: IntCounterVec,
+    pub relay_last_event_timestamp: GaugeVec,
+    pub relay_event_lag: GaugeVec,
+    pub delivery_success: IntCounterVec,
+    pub delivery_failures: IntCounterVec,
+    pub sink_circuit_state: GaugeVec,
+    pub rest_connections_opened: IntCounterVec,
+    pub rest_connections_reused: IntCounterVec,
+    pub rest_connections_active: GaugeVec,
+    pub end_to_end_latency: Histogram,
+    pub downstream_batch_size: Histogram,
+    pub rocksdb_properties: GaugeVec,
+    pub events_by_kind: IntCounterVec,
+    pub events_rejected: IntCounterVec,
+    pub dedup_layer_duplicates: IntCounterVec,
+    pub dedup_bloom_checks: IntCounterVec,
+    pub dedup_lru_lookups: IntCounterVec,
+    pub dedup_lru_evictions: IntCounter,
+    pub dedup_degraded: Gauge,
+    pub duplicate_ratio: Gauge,
+    /// Recent dedup outcomes backing `duplicate_ratio`
+    duplicate_window: DuplicateWindow,
+    /// Last `(sample time, bytes received, bytes per second)` per relay, for the summary rate
+    relay_byte_rates: DashMap<String, (Instant, u64, f64)>,
+    /// Downstream endpoints with delivery series, used for the summary breakdown
+    delivery_endpoints: DashSet<String>,
+    /// Unix time in milliseconds of the last successful delivery per downstream endpoint
+    delivery_last_success: DashMap<String, i64>,
+    /// Per-bucket counts mirroring `end_to_end_latency`, used to estimate percentiles
+    /// (the last slot counts observations above the largest bucket)
+    end_to_end_buckets: [AtomicU64; END_TO_END_LATENCY_BUCKETS.len() + 1],
 }
 
 impl Metrics {
@@ -38,8 +246,479 @@ impl Metrics {
                 "events_in_queue",
                 "Number of events waiting in queue"
             )?,
+            events_dropped: register_int_counter!(
+                "events_dropped_total",
+                "Total events dropped because the output queue was full"
+            )?,
+            relays_failed: register_int_counter!(
+                "relays_failed_total",
+                "Total relays marked failed after exhausting reconnect attempts"
+            )?,
+            relays_evicted: register_int_counter!(
+                "relays_evicted_total",
+                "Total relays evicted for a persistently low health score"
+            )?,
+            ws_slow_client_disconnects: register_int_counter!(
+                "ws_slow_client_disconnects_total",
+                "Total WebSocket clients disconnected for falling too far behind"
+            )?,
+            ws_serialize_errors: register_int_counter!(
+                "ws_serialize_errors_total",
+                "Total events skipped for a WebSocket client because they failed to serialize"
+            )?,
+            relay_events_received: register_int_counter_vec!(
+                "relay_events_received_total",
+                "Total events received per relay",
+                &["relay"]
+            )?,
+            relay_bytes_received: register_int_counter_vec!(
+                "relay_bytes_received_total",
+                "Total bytes of the frames received per relay",
+                &["relay"]
+            )?,
+            relay_duplicates: register_int_counter_vec!(
+                "relay_duplicates_total",
+                "Total duplicate events received per relay",
+                &["relay"]
+            )?,
+            relay_attributions: register_int_counter_vec!(
+                "relay_attributions_total",
+                "Total events credited to each relay as their first deliverer",
+                &["relay"]
+            )?,
+            relay_last_event_timestamp: register_gauge_vec!(
+                "relay_last_event_timestamp_seconds",
+                "Unix timestamp of the last event received per relay",
+                &["relay"]
+            )?,
+            relay_event_lag: register_gauge_vec!(
+                "relay_seconds_since_last_event",
+                "Seconds since the last event was received per relay, updated on each health check",
+                &["relay"]
+            )?,
+            delivery_success: register_int_counter_vec!(
+                "downstream_delivery_success_total",
+                "Total batches successfully delivered per downstream endpoint",
+                &["endpoint"]
+            )?,
+            delivery_failures: register_int_counter_vec!(
+                "downstream_delivery_failures_total",
+                "Total failed batch delivery attempts per downstream endpoint",
+                &["endpoint"]
+            )?,
+            sink_circuit_state: register_gauge_vec!(
+                "downstream_circuit_state",
+                "Circuit breaker state per downstream endpoint (0 closed, 1 open, 2 half-open)",
+                &["endpoint"]
+            )?,
+            rest_connections_opened: register_int_counter_vec!(
+                "rest_connections_opened_total",
+                "Total REST sink requests sent on a newly opened connection per endpoint",
+                &["endpoint"]
+            )?,
+            rest_connections_reused: register_int_counter_vec!(
+                "rest_connections_reused_total",
+                "Total REST sink requests sent on a pooled keep-alive connection per endpoint",
+                &["endpoint"]
+            )?,
+            rest_connections_active: register_gauge_vec!(
+                "rest_connections_active",
+                "Connections used by a REST sink within the pool idle timeout per endpoint",
+                &["endpoint"]
+            )?,
+            end_to_end_latency: register_histogram!(
+                "end_to_end_latency_seconds",
+                "Time from relay receipt until an output sent the event in seconds",
+                END_TO_END_LATENCY_BUCKETS.to_vec()
+            )?,
+            downstream_batch_size: register_histogram!(
+                "downstream_batch_size",
+                "Number of events in each batch sent to the downstream sinks",
+                BATCH_SIZE_BUCKETS.to_vec()
+            )?,
+            rocksdb_properties: register_gauge_vec!(
+                "rocksdb_property",
+                "RocksDB internal property values (file counts, pending compaction, cache usage)",
+                &["property"]
+            )?,
+            events_by_kind: register_int_counter_vec!(
+                "events_by_kind_total",
+                "Total events received per Nostr event kind (uncommon kinds grouped as \"other\")",
+                &["kind"]
+            )?,
+            events_rejected: register_int_counter_vec!(
+                "events_rejected_total",
+                "Total events from relays rejected before processing, by reason",
+                &["reason"]
+            )?,
+            dedup_layer_duplicates: register_int_counter_vec!(
+                "dedup_layer_duplicates_total",
+                "Total duplicates caught per deduplication layer",
+                &["layer"]
+            )?,
+            dedup_bloom_checks: register_int_counter_vec!(
+                "dedup_bloom_checks_total",
+                "Total bloom filter lookups by result (negative = new without further lookups)",
+                &["result"]
+            )?,
+            dedup_lru_lookups: register_int_counter_vec!(
+                "dedup_lru_lookups_total",
+                "Total dedup LRU cache lookups by result (hit or miss)",
+                &["result"]
+            )?,
+            dedup_lru_evictions: register_int_counter!(
+                "dedup_lru_evictions_total",
+                "Total event ids evicted from the dedup LRU cache to make room"
+            )?,
+            dedup_degraded: register_gauge!(
+                "dedup_degraded",
+                "1 while RocksDB is bypassed and deduplication runs on the in-memory layers only"
+            )?,
+            duplicate_ratio: register_gauge!(
+                "duplicate_ratio",
+                "Share of deduplicated events that were duplicates over the rolling window"
+            )?,
+            duplicate_window: DuplicateWindow::new(DEFAULT_DUPLICATE_RATIO_WINDOW),
+            relay_byte_rates: DashMap::new(),
+            delivery_endpoints: DashSet::new(),
+            delivery_last_success: DashMap::new(),
+            end_to_end_buckets: std::array::from_fn(|_| AtomicU64::new(0)),
         })
     }
+
+    /// Compute `duplicate_ratio` over `window` instead of the default minute
+    pub fn with_duplicate_ratio_window(mut self, window: Duration) -> Self {
+        self.duplicate_window = DuplicateWindow::new(window);
+        self
+    }
+
+    /// Record whether an event checked by deduplication was a duplicate
+    pub fn record_dedup_outcome(&self, duplicate: bool) {
+        self.duplicate_ratio
+            .set(self.duplicate_window.record(duplicate));
+    }
+
+    /// Duplicate ratio over the rolling window, also refreshing the gauge so it decays
+    /// to zero when no events arrive
+    pub fn current_duplicate_ratio(&self) -> f64 {
+        let ratio = self.duplicate_window.ratio();
+        self.duplicate_ratio.set(ratio);
+        ratio
+    }
+
+    /// Duplicate counts per dedup layer and bloom filter lookup outcomes
+    pub fn dedup_summary(&self) -> serde_json::Value {
+        let layer = |l: &str| self.dedup_layer_duplicates.with_label_values(&[l]).get();
+        let bloom = |r: &str| self.dedup_bloom_checks.with_label_values(&[r]).get();
+        let lru = |r: &str| self.dedup_lru_lookups.with_label_values(&[r]).get();
+        serde_json::json!({
+            "duplicates_by_layer": {
+                DEDUP_LAYER_HOTSET: layer(DEDUP_LAYER_HOTSET),
+                DEDUP_LAYER_LRU: layer(DEDUP_LAYER_LRU),
+                DEDUP_LAYER_ROCKSDB: layer(DEDUP_LAYER_ROCKSDB),
+            },
+            "bloom": {
+                "negative": bloom("negative"),
+                "positive": bloom("positive"),
+            },
+            "lru": {
+                "hits": lru("hit"),
+                "misses": lru("miss"),
+                "evictions": self.dedup_lru_evictions.get(),
+            },
+        })
+    }
+
+    /// Label value for an event kind, bounding cardinality to `TRACKED_KINDS` plus `other`
+    fn kind_label(kind: u16) -> String {
+        if TRACKED_KINDS.contains(&kind) {
+            kind.to_string()
+        } else {
+            OTHER_KIND_LABEL.to_string()
+        }
+    }
+
+    /// Record the kind of an event received from a relay
+    pub fn record_event_kind(&self, kind: u16) {
+        self.events_by_kind
+            .with_label_values(&[Self::kind_label(kind)])
+            .inc();
+    }
+
+    /// Record an event dropped by validation
+    pub fn record_event_rejected(&self, reason: &str) {
+        self.events_rejected.with_label_values(&[reason]).inc();
+    }
+
+    /// Event counts keyed by kind label, omitting kinds never seen
+    pub fn kind_summary(&self) -> serde_json::Map<String, serde_json::Value> {
+        TRACKED_KINDS
+            .iter()
+            .map(|kind| kind.to_string())
+            .chain(std::iter::once(OTHER_KIND_LABEL.to_string()))
+            .filter_map(|label| {
+                let count = self
+                    .events_by_kind
+                    .get_metric_with_label_values(&[&label])
+                    .ok()?
+                    .get();
+                (count > 0).then(|| (label, count.into()))
+            })
+            .collect()
+    }
+
+    /// Record the time an event spent between relay receipt and being sent by an output
+    pub fn observe_end_to_end_latency(&self, seconds: f64) {
+        self.end_to_end_latency.observe(seconds);
+        let bucket = END_TO_END_LATENCY_BUCKETS
+            .iter()
+            .position(|upper| seconds <= *upper)
+            .unwrap_or(END_TO_END_LATENCY_BUCKETS.len());
+        self.end_to_end_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record the size of a batch handed to the downstream sinks
+    pub fn observe_batch_size(&self, size: usize) {
+        self.downstream_batch_size.observe(size as f64);
+    }
+
+    /// Mean downstream batch size so far, `None` before the first batch
+    pub fn average_batch_size(&self) -> Option<f64> {
+        let count = self.downstream_batch_size.get_sample_count();
+        (count > 0).then(|| self.downstream_batch_size.get_sample_sum() / count as f64)
+    }
+
+    /// Estimate a latency quantile (0.0..=1.0) as the upper bound of the bucket containing it.
+    /// Returns `None` before any observation.
+    pub fn end_to_end_latency_quantile(&self, quantile: f64) -> Option<f64> {
+        let counts: Vec<u64> = self
+            .end_to_end_buckets
+            .iter()
+            .map(|c| c.load(Ordering::Relaxed))
+            .collect();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+
+        let target = (quantile.clamp(0.0, 1.0) * total as f64).ceil().max(1.0) as u64;
+        let mut cumulative = 0;
+        for (i, count) in counts.iter().enumerate() {
+            cumulative += count;
+            if cumulative >= target {
+                // Observations above the largest bucket report that bound
+                let idx = i.min(END_TO_END_LATENCY_BUCKETS.len() - 1);
+                return Some(END_TO_END_LATENCY_BUCKETS[idx]);
+            }
+        }
+        END_TO_END_LATENCY_BUCKETS.last().copied()
+    }
+
+    /// Record an event received from a relay
+    pub fn record_relay_event(&self, relay_url: &str) {
+        self.relay_events_received
+            .with_label_values(&[relay_url])
+            .inc();
+        self.relay_last_event_timestamp
+            .with_label_values(&[relay_url])
+            .set(chrono::Utc::now().timestamp() as f64);
+    }
+
+    /// Record the size of a frame received from a relay
+    pub fn record_relay_bytes(&self, relay_url: &str, bytes: usize) {
+        self.relay_bytes_received
+            .with_label_values(&[relay_url])
+            .inc_by(bytes as u64);
+    }
+
+    /// Recompute a relay's bytes-per-second rate from the byte counter since the last sample
+    pub fn sample_relay_bandwidth(&self, relay_url: &str) {
+        let now = Instant::now();
+        let total = self
+            .relay_bytes_received
+            .with_label_values(&[relay_url])
+            .get();
+        let mut sample = self
+            .relay_byte_rates
+            .entry(relay_url.to_string())
+            .or_insert((now, total, 0.0));
+        let (at, bytes, _) = *sample;
+        let elapsed = now.duration_since(at).as_secs_f64();
+        if elapsed > 0.0 {
+            *sample = (now, total, total.saturating_sub(bytes) as f64 / elapsed);
+        }
+    }
+
+    /// Record a duplicate event received from a relay
+    pub fn record_relay_duplicate(&self, relay_url: &str) {
+        self.relay_duplicates.with_label_values(&[relay_url]).inc();
+    }
+
+    /// Record an event credited to the relay that delivered it first
+    pub fn record_relay_attribution(&self, relay_url: &str) {
+        self.relay_attributions
+            .with_label_values(&[relay_url])
+            .inc();
+    }
+
+    /// Record how long a relay has gone without delivering an event
+    pub fn set_relay_event_lag(&self, relay_url: &str, lag: std::time::Duration) {
+        self.relay_event_lag
+            .with_label_values(&[relay_url])
+            .set(lag.as_secs_f64());
+    }
+
+    /// Zero the event counters between test runs. Labelled counters lose their series until
+    /// the next increment. Histograms cannot be reset and keep their totals; gauges that mirror
+    /// live state are left for their next sample.
+    pub fn reset_counters(&self) {
+        for counter in [
+            &self.events_processed,
+            &self.duplicates_filtered,
+            &self.events_dropped,
+            &self.relays_failed,
+            &self.relays_evicted,
+            &self.ws_slow_client_disconnects,
+            &self.ws_serialize_errors,
+            &self.dedup_lru_evictions,
+        ] {
+            counter.reset();
+        }
+        for counter in [
+            &self.relay_events_received,
+            &self.relay_bytes_received,
+            &self.relay_duplicates,
+            &self.relay_attributions,
+            &self.delivery_success,
+            &self.delivery_failures,
+            &self.rest_connections_opened,
+            &self.rest_connections_reused,
+            &self.events_by_kind,
+            &self.events_rejected,
+            &self.dedup_layer_duplicates,
+            &self.dedup_bloom_checks,
+            &self.dedup_lru_lookups,
+        ] {
+            counter.reset();
+        }
+        self.relay_byte_rates.clear();
+        self.duplicate_window.clear();
+        self.duplicate_ratio.set(0.0);
+        for bucket in &self.end_to_end_buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Drop all per-relay series for a relay that left the pool, keeping label cardinality bounded
+    pub fn remove_relay(&self, relay_url: &str) {
+        let _ = self.relay_events_received.remove_label_values(&[relay_url]);
+        let _ = self.relay_bytes_received.remove_label_values(&[relay_url]);
+        self.relay_byte_rates.remove(relay_url);
+        let _ = self.relay_duplicates.remove_label_values(&[relay_url]);
+        let _ = self.relay_attributions.remove_label_values(&[relay_url]);
+        let _ = self
+            .relay_last_event_timestamp
+            .remove_label_values(&[relay_url]);
+        let _ = self.relay_event_lag.remove_label_values(&[relay_url]);
+    }
+
+    /// Start tracking delivery counters for a downstream endpoint
+    pub fn register_delivery_endpoint(&self, endpoint: &str) {
+        self.delivery_success.with_label_values(&[endpoint]);
+        self.delivery_failures.with_label_values(&[endpoint]);
+        self.sink_circuit_state.with_label_values(&[endpoint]);
+        self.delivery_endpoints.insert(endpoint.to_string());
+    }
+
+    /// Record a successful batch delivery to a downstream endpoint
+    pub fn record_delivery_success(&self, endpoint: &str) {
+        self.delivery_success.with_label_values(&[endpoint]).inc();
+        self.delivery_last_success
+            .insert(endpoint.to_string(), chrono::Utc::now().timestamp_millis());
+    }
+
+    /// Record a failed batch delivery attempt to a downstream endpoint
+    pub fn record_delivery_failure(&self, endpoint: &str) {
+        self.delivery_failures.with_label_values(&[endpoint]).inc();
+    }
+
+    /// Record which connection a REST sink request went out on and how many it currently uses
+    pub fn record_rest_connection(&self, endpoint: &str, reused: bool, active: usize) {
+        if reused {
+            self.rest_connections_reused
+                .with_label_values(&[endpoint])
+                .inc();
+        } else {
+            self.rest_connections_opened
+                .with_label_values(&[endpoint])
+                .inc();
+        }
+        self.rest_connections_active
+            .with_label_values(&[endpoint])
+            .set(active as f64);
+    }
+
+    /// Record the circuit breaker state of a downstream endpoint
+    pub fn set_sink_circuit_state(&self, endpoint: &str, state: BreakerState) {
+        self.sink_circuit_state
+            .with_label_values(&[endpoint])
+            .set(state.as_gauge());
+    }
+
+    /// Delivery success/failure counts keyed by downstream endpoint
+    pub fn delivery_summary(&self) -> BTreeMap<String, DeliverySummary> {
+        self.delivery_endpoints
+            .iter()
+            .map(|endpoint| {
+                let labels = [endpoint.as_str()];
+                let summary = DeliverySummary {
+                    success_total: self.delivery_success.with_label_values(&labels).get(),
+                    failures_total: self.delivery_failures.with_label_values(&labels).get(),
+                    circuit_state: self.sink_circuit_state.with_label_values(&labels).get(),
+                };
+                (endpoint.clone(), summary)
+            })
+            .collect()
+    }
+
+    /// Health of every downstream endpoint, sorted by endpoint, for `/status`
+    pub fn downstream_status(&self) -> Vec<serde_json::Value> {
+        let mut endpoints: Vec<String> =
+            self.delivery_endpoints.iter().map(|e| e.clone()).collect();
+        endpoints.sort();
+        endpoints
+            .iter()
+            .map(|endpoint| {
+                let labels = [endpoint.as_str()];
+                let state = BreakerState::from_gauge(
+                    self.sink_circuit_state.with_label_values(&labels).get(),
+                );
+                serde_json::json!({
+                    "endpoint": endpoint,
+                    "state": state.as_str(),
+                    "healthy": state == BreakerState::Closed,
+                    "last_success": self.delivery_last_success.get(endpoint).map(|t| *t),
+                    "failures_total": self.delivery_failures.with_label_values(&labels).get(),
+                })
+            })
+            .collect()
+    }
+
+    /// Per-relay counters for a relay currently in the pool
+    pub fn relay_summary(&self, relay_url: &str) -> RelaySummary {
+        let labels = [relay_url];
+        RelaySummary {
+            events_received_total: self.relay_events_received.with_label_values(&labels).get(),
+            bytes_received_total: self.relay_bytes_received.with_label_values(&labels).get(),
+            bytes_per_second: self.relay_byte_rates.get(relay_url).map_or(0.0, |s| s.2),
+            duplicates_total: self.relay_duplicates.with_label_values(&labels).get(),
+            last_event_timestamp: self
+                .relay_last_event_timestamp
+                .with_label_values(&labels)
+                .get() as i64,
+            seconds_since_last_event: self.relay_event_lag.with_label_values(&labels).get(),
+        }
+    }
 }
 
 impl Default for Metrics {
@@ -1,4 +1,5 @@
 pub mod metrics;
 pub mod rest_api;
+pub mod subscription;
 pub mod websocket;
 
@@ -1,42 +1,178 @@
 use axum::{
     Router,
-    extract::State,
-    http::StatusCode,
-    response::Json,
+    extract::{Path, Query, Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
     routing::{delete, get, post},
 };
 use prometheus::{Encoder, TextEncoder};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::path::PathBuf;
 use std::sync::Arc;
+use subtle::ConstantTimeEq;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 
-use crate::api::metrics::Metrics;
-use crate::core::relay_pool::RelayPool;
+use crate::api::metrics::{LatencyPercentiles, Metrics, MetricsSummary};
+use crate::config::AppConfig;
 use crate::core::dedupe_engine::DeduplicationEngine;
+use crate::core::relay_pool::{
+    ConnectionLimitReached, RelayAlreadyConnected, RelayConnectTimeout, RelayNotFound, RelayPool,
+    RelayStatus,
+};
+use crate::storage::rocksdb_store::{EventCursor, INDEXED_TAGS, RocksDBStore};
+
+/// Default number of events returned by `/api/events`
+const DEFAULT_EVENTS_LIMIT: usize = 100;
+/// Upper bound on events returned by a single `/api/events` request
+const MAX_EVENTS_LIMIT: usize = 1000;
+/// Upper bound on ids checked by a single `/api/events/exists` request
+const MAX_EXISTS_IDS: usize = 10_000;
+/// `Content-Type` of the OpenMetrics exposition served to clients that accept it
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
 
 #[derive(Clone)]
 pub struct AppState {
     pub pool: Arc<RelayPool>,
     pub dedupe: Arc<DeduplicationEngine>,
     pub metrics: Arc<Metrics>,
+    pub rocksdb: Arc<RocksDBStore>,
+    /// Bearer token required on mutating routes; `None` leaves them open
+    pub api_key: Option<Arc<str>>,
+    /// Configuration the process was started with, if a config file was given
+    pub config: Option<Arc<AppConfig>>,
 }
 
 /// Create the REST API router
-pub fn create_router(pool: Arc<RelayPool>, dedupe: Arc<DeduplicationEngine>, metrics: Arc<Metrics>) -> Router {
-    let state = AppState { pool, dedupe, metrics };
-    Router::new()
+pub fn create_router(
+    pool: Arc<RelayPool>,
+    dedupe: Arc<DeduplicationEngine>,
+    metrics: Arc<Metrics>,
+    rocksdb: Arc<RocksDBStore>,
+    api_key: Option<String>,
+    cors_origins: &[String],
+    config: Option<Arc<AppConfig>>,
+) -> Router {
+    let state = AppState {
+        pool,
+        dedupe,
+        metrics,
+        rocksdb,
+        api_key: api_key.map(Arc::from),
+        config: config.clone(),
+    };
+
+    // Mutating routes sit behind the API key check
+    let protected = Router::new()
+        .route("/api/relays/add", post(add_relay))
+        .route("/api/relays/batch", post(add_relays_batch))
+        .route("/api/relays/remove", delete(remove_relay))
+        .route("/api/relays/pause", post(pause_relay))
+        .route("/api/relays/resume", post(resume_relay))
+        .route("/api/admin/compact", post(compact_db))
+        .route("/api/events/{id}", delete(delete_event));
+    // Backup, export and import read and write server paths, so they only exist behind a key
+    let protected = if state.api_key.is_some() {
+        protected
+            .route("/api/admin/backup", post(create_backup))
+            .route("/api/admin/export", post(export_events))
+            .route("/api/admin/import", post(import_events))
+    } else {
+        protected
+    };
+    // Resetting counters breaks Prometheus rate() across the reset, so it is opt-in
+    let protected = if config
+        .as_ref()
+        .is_some_and(|c| c.monitoring.allow_metrics_reset)
+    {
+        protected.route("/api/admin/metrics/reset", post(reset_metrics))
+    } else {
+        protected
+    };
+    let protected = protected.route_layer(middleware::from_fn_with_state(
+        state.clone(),
+        require_api_key,
+    ));
+
+    let router = Router::new()
         .route("/health", get(health))
+        .route("/health/live", get(health))
+        .route("/health/ready", get(readiness))
         .route("/metrics", get(prometheus_metrics))
         .route("/status", get(status))
+        .route("/api/config", get(running_config))
         .route("/api/metrics/summary", get(metrics_summary))
         .route("/api/metrics/memory", get(memory))
+        .route("/api/metrics/kinds", get(metrics_kinds))
+        .route("/api/metrics/dedup", get(metrics_dedup))
+        .route("/api/events", get(list_events))
+        .route("/api/events/exists", post(events_exist))
+        .route("/api/events/by-tag", get(events_by_tag))
+        .route("/api/events/{id}", get(get_event))
         .route("/api/relays", get(list_relays))
-        .route("/api/relays/add", post(add_relay))
-        .route("/api/relays/remove", delete(remove_relay))
-        .with_state(state)
+        .merge(protected)
+        .with_state(state);
+
+    match cors_layer(cors_origins) {
+        Some(cors) => router.layer(cors),
+        None => router,
+    }
+}
+
+/// Build a CORS layer for the configured origins; `None` keeps CORS disabled
+fn cors_layer(origins: &[String]) -> Option<CorsLayer> {
+    if origins.is_empty() {
+        return None;
+    }
+
+    let allow_origin = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins: Vec<HeaderValue> = origins
+            .iter()
+            .filter_map(|origin| match origin.parse() {
+                Ok(value) => Some(value),
+                Err(_) => {
+                    tracing::warn!("Ignoring invalid CORS origin: {}", origin);
+                    None
+                }
+            })
+            .collect();
+        AllowOrigin::list(origins)
+    };
+
+    Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods(Any)
+            .allow_headers(Any),
+    )
 }
 
-/// Health check endpoint
+/// Reject requests without a matching `Authorization: Bearer <key>` header
+async fn require_api_key(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if let Some(expected) = &state.api_key {
+        let provided = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        // Constant-time, so response timing does not reveal how much of a guess matched
+        let authorized = provided
+            .is_some_and(|provided| bool::from(provided.as_bytes().ct_eq(expected.as_bytes())));
+        if !authorized {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "error": "missing or invalid API key" })),
+            )
+                .into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Liveness endpoint: the process is up and serving requests
 async fn health() -> Json<serde_json::Value> {
     Json(json!({
         "status": "healthy",
@@ -44,8 +180,57 @@ async fn health() -> Json<serde_json::Value> {
     }))
 }
 
-/// Metrics endpoint for Prometheus
-async fn prometheus_metrics() -> Result<String, StatusCode> {
+/// Readiness endpoint: at least one relay connected and RocksDB usable, `503` otherwise.
+/// A primary must accept writes; a read-only secondary only has to answer reads.
+async fn readiness(State(state): State<AppState>) -> (StatusCode, Json<serde_json::Value>) {
+    let connected_relays = state
+        .pool
+        .get_connection_statuses()
+        .await
+        .iter()
+        .filter(|(_, conn)| conn.status == RelayStatus::Connected)
+        .count();
+    let read_error = state.rocksdb.check_readable().await.err();
+    if let Some(e) = &read_error {
+        tracing::warn!("Readiness check: RocksDB not readable: {:#}", e);
+    }
+    let secondary = state.rocksdb.is_secondary();
+    let write_error = if secondary {
+        None
+    } else {
+        state.rocksdb.check_writable().await.err()
+    };
+    if let Some(e) = &write_error {
+        tracing::warn!("Readiness check: RocksDB not writable: {:#}", e);
+    }
+
+    let db_ready = read_error.is_none() && write_error.is_none();
+    let ready = connected_relays > 0 && db_ready;
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(json!({
+            "status": if ready { "ready" } else { "not_ready" },
+            "connected_relays": connected_relays,
+            "db_readable": read_error.is_none(),
+            "db_writable": !secondary && write_error.is_none(),
+            "db_secondary": secondary,
+        })),
+    )
+}
+
+/// Metrics endpoint for Prometheus.
+/// Serves OpenMetrics when the `Accept` header prefers `application/openmetrics-text`, and the
+/// Prometheus text format otherwise.
+async fn prometheus_metrics(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    state.metrics.current_duplicate_ratio();
     let encoder = TextEncoder::new();
     let metric_families = prometheus::gather();
     let mut buffer = Vec::new();
@@ -53,8 +238,100 @@ async fn prometheus_metrics() -> Result<String, StatusCode> {
     encoder
         .encode(&metric_families, &mut buffer)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let text = String::from_utf8_lossy(&buffer);
+
+    let (content_type, body) = if prefers_openmetrics(&headers) {
+        (OPENMETRICS_CONTENT_TYPE, to_openmetrics(&text))
+    } else {
+        (encoder.format_type(), text.into_owned())
+    };
+    Ok(([(header::CONTENT_TYPE, content_type.to_string())], body).into_response())
+}
+
+/// Whether the `Accept` header prefers OpenMetrics: it must be listed with a non-zero quality
+/// that is no lower than the quality given to `text/plain`.
+fn prefers_openmetrics(headers: &HeaderMap) -> bool {
+    let mut openmetrics = 0.0_f32;
+    let mut text = 0.0_f32;
+    let ranges = headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+    for range in ranges {
+        let mut params = range.split(';').map(str::trim);
+        let media_type = params.next().unwrap_or_default().to_ascii_lowercase();
+        let quality = params
+            .filter_map(|param| param.split_once('='))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case("q"))
+            .map(|(_, value)| value.trim().parse::<f32>().unwrap_or(0.0))
+            .unwrap_or(1.0);
+        match media_type.as_str() {
+            "application/openmetrics-text" => openmetrics = openmetrics.max(quality),
+            "text/plain" => text = text.max(quality),
+            _ => {}
+        }
+    }
+    openmetrics > 0.0 && openmetrics >= text
+}
+
+/// Rewrite Prometheus text exposition as OpenMetrics: counter families are named without their
+/// `_total` suffix (samples keep it), `untyped` becomes `unknown` and the body ends in `# EOF`.
+/// The prometheus crate records no exemplars, so none are emitted.
+fn to_openmetrics(text: &str) -> String {
+    let counters: std::collections::HashSet<&str> = text
+        .lines()
+        .filter_map(|line| line.strip_prefix("# TYPE "))
+        .filter_map(|rest| rest.strip_suffix(" counter"))
+        .collect();
+    let family = |name: &str| name.strip_suffix("_total").unwrap_or(name).to_string();
+
+    let mut out = String::with_capacity(text.len() + 8);
+    for line in text.lines() {
+        if let Some(rest) = line.strip_prefix("# HELP ") {
+            let (name, help) = rest.split_once(' ').unwrap_or((rest, ""));
+            let name = if counters.contains(name) {
+                family(name)
+            } else {
+                name.to_string()
+            };
+            out.push_str(&format!("# HELP {} {}", name, help));
+        } else if let Some(rest) = line.strip_prefix("# TYPE ") {
+            let (name, kind) = rest.split_once(' ').unwrap_or((rest, "unknown"));
+            match kind {
+                "counter" => out.push_str(&format!("# TYPE {} counter", family(name))),
+                "untyped" => out.push_str(&format!("# TYPE {} unknown", name)),
+                _ => out.push_str(line),
+            }
+        } else if line.is_empty() {
+            continue;
+        } else {
+            // Counter samples must be named `<family>_total`
+            let end = line.find(['{', ' ']).unwrap_or(line.len());
+            let name = &line[..end];
+            if counters.contains(name) && !name.ends_with("_total") {
+                out.push_str(&format!("{}_total{}", name, &line[end..]));
+            } else {
+                out.push_str(line);
+            }
+        }
+        out.push('\n');
+    }
+    out.push_str("# EOF\n");
+    out
+}
 
-    Ok(String::from_utf8_lossy(&buffer).to_string())
+/// Effective configuration (after environment overrides) with secrets masked
+async fn running_config(
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    match &state.config {
+        Some(config) => Ok(Json(config.redacted())),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "running without a configuration file" })),
+        )),
+    }
 }
 
 /// Get connection status
@@ -65,10 +342,15 @@ async fn status(State(state): State<AppState>) -> Json<serde_json::Value> {
 
     Json(json!({
         "active_connections": active,
-        "connections": statuses.iter().map(|(url, status)| {
+        "max_connections": state.pool.max_connections(),
+        "connection_headroom": state.pool.connection_headroom(),
+        "warmup": deque_status.warming_up,
+        "connections": statuses.iter().map(|(url, conn)| {
             json!({
                 "url": url,
-                "status": format!("{:?}", status)
+                "status": conn.status,
+                "status_since": conn.since,
+                "error": conn.error,
             })
         }).collect::<Vec<_>>(),
         "deduplication_engine": {
@@ -76,7 +358,9 @@ async fn status(State(state): State<AppState>) -> Json<serde_json::Value> {
             "lru_cache_size": deque_status.lru_cache_size,
             "rocksdb_entry_count": deque_status.rocksdb_approximate_count,
             "hot_set_size": deque_status.hot_set_size,
-        }
+            "degraded": deque_status.degraded,
+        },
+        "downstreams": state.metrics.downstream_status(),
     }))
 }
 
@@ -84,12 +368,33 @@ async fn status(State(state): State<AppState>) -> Json<serde_json::Value> {
 #[derive(Debug, Deserialize)]
 struct AddRelayRequest {
     url: String,
+    /// Wait for the connection to be established (default); when false the relay is
+    /// connected in the background and `202 Accepted` is returned immediately
+    #[serde(default = "default_wait")]
+    wait: bool,
+}
+
+fn default_wait() -> bool {
+    true
+}
+
+/// Request body for adding several relays at once
+#[derive(Debug, Deserialize)]
+struct BatchAddRelayRequest {
+    urls: Vec<String>,
 }
 
 /// Request body for removing a relay
 #[derive(Debug, Deserialize)]
 struct RemoveRelayRequest {
     url: String,
+    /// How long to wait for in-flight events from the relay before closing it
+    #[serde(default = "default_drain_timeout_ms")]
+    drain_timeout_ms: u64,
+}
+
+fn default_drain_timeout_ms() -> u64 {
+    5000
 }
 
 /// Response for relay operations
@@ -103,67 +408,405 @@ struct RelayResponse {
 async fn add_relay(
     State(state): State<AppState>,
     Json(payload): Json<AddRelayRequest>,
-) -> Result<Json<RelayResponse>, StatusCode> {
+) -> Result<(StatusCode, Json<RelayResponse>), (StatusCode, Json<RelayResponse>)> {
+    if !payload.wait {
+        // Reject what can be decided up front; the relay is listed as `connecting` until the
+        // background connect succeeds, or as `failed` with the error
+        if let Err(e) = state.pool.connect_in_background(&payload.url) {
+            tracing::error!("Failed to add relay {}: {}", payload.url, e);
+            return Err(add_relay_error(e));
+        }
+        return Ok((
+            StatusCode::ACCEPTED,
+            Json(RelayResponse {
+                success: true,
+                message: format!("Connecting to relay in the background: {}", payload.url),
+            }),
+        ));
+    }
+
     match state.pool.connect_and_subscribe(payload.url.clone()).await {
-        Ok(_) => Ok(Json(RelayResponse {
+        Ok(_) => Ok((
+            StatusCode::OK,
+            Json(RelayResponse {
+                success: true,
+                message: format!("Successfully connected to relay: {}", payload.url),
+            }),
+        )),
+        Err(e) => {
+            tracing::error!("Failed to add relay {}: {}", payload.url, e);
+            Err(add_relay_error(e))
+        }
+    }
+}
+
+/// Map a failed relay add onto its HTTP status
+fn add_relay_error(e: anyhow::Error) -> (StatusCode, Json<RelayResponse>) {
+    let status = if e.is::<ConnectionLimitReached>() || e.is::<RelayAlreadyConnected>() {
+        StatusCode::CONFLICT
+    } else if e.is::<RelayConnectTimeout>() {
+        StatusCode::GATEWAY_TIMEOUT
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    (
+        status,
+        Json(RelayResponse {
+            success: false,
+            message: e.to_string(),
+        }),
+    )
+}
+
+/// Add several relays concurrently, reporting success or failure per URL
+async fn add_relays_batch(
+    State(state): State<AppState>,
+    Json(payload): Json<BatchAddRelayRequest>,
+) -> Json<serde_json::Value> {
+    let results = state.pool.connect_batch(payload.urls).await;
+
+    let mut succeeded = 0;
+    let mut per_url = serde_json::Map::new();
+    for (url, result) in results {
+        let response = match result {
+            Ok(_) => {
+                succeeded += 1;
+                RelayResponse {
+                    success: true,
+                    message: format!("Successfully connected to relay: {}", url),
+                }
+            }
+            Err(e) => {
+                tracing::error!("Failed to add relay {}: {}", url, e);
+                RelayResponse {
+                    success: false,
+                    message: e.to_string(),
+                }
+            }
+        };
+        per_url.insert(url, json!(response));
+    }
+
+    Json(json!({
+        "succeeded": succeeded,
+        "failed": per_url.len() - succeeded,
+        "results": per_url,
+    }))
+}
+
+/// Request body for creating a database backup, export or import
+#[derive(Debug, Deserialize)]
+struct BackupRequest {
+    path: String,
+}
+
+/// Resolve a backup, export or import path. With `security.admin_dir` set, relative paths are
+/// taken from that directory and paths leaving it are rejected with `400`.
+fn admin_path(
+    state: &AppState,
+    requested: &str,
+) -> Result<PathBuf, (StatusCode, Json<serde_json::Value>)> {
+    let requested = PathBuf::from(requested);
+    let Some(dir) = state
+        .config
+        .as_ref()
+        .and_then(|c| c.security.admin_dir.as_deref())
+    else {
+        return Ok(requested);
+    };
+    let dir = std::path::Path::new(dir);
+    let escapes = requested
+        .components()
+        .any(|c| c == std::path::Component::ParentDir);
+    if escapes || (requested.is_absolute() && !requested.starts_with(dir)) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": format!("path must be inside security.admin_dir ({})", dir.display())
+            })),
+        ));
+    }
+    Ok(dir.join(requested))
+}
+
+/// Create a RocksDB checkpoint at the requested path
+async fn create_backup(
+    State(state): State<AppState>,
+    Json(payload): Json<BackupRequest>,
+) -> Result<Json<RelayResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let path = admin_path(&state, &payload.path)?;
+    match state.rocksdb.create_checkpoint(&path).await {
+        Ok(()) => Ok(Json(RelayResponse {
             success: true,
-            message: format!("Successfully connected to relay: {}", payload.url),
+            message: format!("Backup created at {}", path.display()),
         })),
         Err(e) => {
-            tracing::error!("Failed to add relay {}: {}", payload.url, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            tracing::error!("Failed to create backup at {}: {:#}", path.display(), e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": format!("{:#}", e) })),
+            ))
+        }
+    }
+}
+
+/// Stream every stored event to a JSONL file on the server
+async fn export_events(
+    State(state): State<AppState>,
+    Json(payload): Json<BackupRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let path = admin_path(&state, &payload.path)?;
+    let started = std::time::Instant::now();
+    match state.rocksdb.export_jsonl(&path).await {
+        Ok(exported) => {
+            tracing::info!(
+                "Exported {} events to {} in {:?}",
+                exported,
+                path.display(),
+                started.elapsed()
+            );
+            Ok(Json(json!({
+                "path": path,
+                "events_exported": exported,
+                "duration_ms": started.elapsed().as_millis() as u64,
+            })))
+        }
+        Err(e) => {
+            tracing::error!("Failed to export events to {}: {:#}", path.display(), e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": format!("{:#}", e) })),
+            ))
+        }
+    }
+}
+
+/// Store the events of a JSONL file on the server and mark them as seen for deduplication
+async fn import_events(
+    State(state): State<AppState>,
+    Json(payload): Json<BackupRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let path = admin_path(&state, &payload.path)?;
+    let started = std::time::Instant::now();
+    let dedupe = state.dedupe.clone();
+    let result = state
+        .rocksdb
+        .import_jsonl(&path, async |events: &[nostr_sdk::Event]| {
+            dedupe.remember(events).await
+        })
+        .await;
+    match result {
+        Ok(summary) => {
+            tracing::info!(
+                "Imported {} events from {} in {:?} ({} malformed lines skipped)",
+                summary.imported,
+                path.display(),
+                started.elapsed(),
+                summary.skipped
+            );
+            Ok(Json(json!({
+                "path": path,
+                "events_imported": summary.imported,
+                "lines_skipped": summary.skipped,
+                "duration_ms": started.elapsed().as_millis() as u64,
+            })))
+        }
+        Err(e) => {
+            tracing::error!("Failed to import events from {}: {:#}", path.display(), e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": format!("{:#}", e) })),
+            ))
         }
     }
 }
 
-/// Remove a relay
+/// Run a full RocksDB compaction, reporting estimated key counts before and after
+async fn compact_db(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let keys_before = state.rocksdb.approximate_count().await;
+    let started = std::time::Instant::now();
+    state.rocksdb.compact_range().await;
+    let elapsed = started.elapsed();
+    let keys_after = state.rocksdb.approximate_count().await;
+    tracing::info!(
+        "RocksDB compaction finished in {:?} ({} -> {} estimated keys)",
+        elapsed,
+        keys_before,
+        keys_after
+    );
+    Json(json!({
+        "estimated_keys_before": keys_before,
+        "estimated_keys_after": keys_after,
+        "duration_ms": elapsed.as_millis() as u64,
+    }))
+}
+
+/// Zero the event counters, for load tests that should not need a restart between runs
+async fn reset_metrics(State(state): State<AppState>) -> Json<serde_json::Value> {
+    state.metrics.reset_counters();
+    tracing::warn!("Metrics counters reset through the admin API");
+    Json(json!({ "reset": true }))
+}
+
+/// Remove a relay after draining its in-flight events
 async fn remove_relay(
     State(state): State<AppState>,
     Json(payload): Json<RemoveRelayRequest>,
-) -> Result<Json<RelayResponse>, StatusCode> {
-    match state.pool.disconnect_relay(&payload.url).await {
+) -> Result<Json<RelayResponse>, (StatusCode, Json<RelayResponse>)> {
+    let drain_timeout = std::time::Duration::from_millis(payload.drain_timeout_ms);
+    match state.pool.drain_relay(&payload.url, drain_timeout).await {
         Ok(_) => Ok(Json(RelayResponse {
             success: true,
             message: format!("Successfully disconnected relay: {}", payload.url),
         })),
         Err(e) => {
             tracing::error!("Failed to remove relay {}: {}", payload.url, e);
-            Err(StatusCode::NOT_FOUND)
+            Err(relay_action_error(e))
         }
     }
 }
 
-/// List all relays
-async fn list_relays(State(state): State<AppState>) -> Json<serde_json::Value> {
-    let _relay_urls = state.pool.list_relays();
-    let statuses = state.pool.get_connection_statuses().await;
+/// Request body naming a single relay
+#[derive(Debug, Deserialize)]
+struct RelayUrlRequest {
+    url: String,
+}
+
+/// Map a remove/pause/resume failure to `404` for unknown relays and `500` otherwise
+fn relay_action_error(e: anyhow::Error) -> (StatusCode, Json<RelayResponse>) {
+    let status = if e.is::<RelayNotFound>() {
+        StatusCode::NOT_FOUND
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    (
+        status,
+        Json(RelayResponse {
+            success: false,
+            message: e.to_string(),
+        }),
+    )
+}
+
+/// Stop ingesting from a relay without disconnecting it
+async fn pause_relay(
+    State(state): State<AppState>,
+    Json(payload): Json<RelayUrlRequest>,
+) -> Result<Json<RelayResponse>, (StatusCode, Json<RelayResponse>)> {
+    match state.pool.pause_relay(&payload.url).await {
+        Ok(()) => Ok(Json(RelayResponse {
+            success: true,
+            message: format!("Paused relay: {}", payload.url),
+        })),
+        Err(e) => {
+            tracing::error!("Failed to pause relay {}: {}", payload.url, e);
+            Err(relay_action_error(e))
+        }
+    }
+}
+
+/// Resume ingesting from a paused relay
+async fn resume_relay(
+    State(state): State<AppState>,
+    Json(payload): Json<RelayUrlRequest>,
+) -> Result<Json<RelayResponse>, (StatusCode, Json<RelayResponse>)> {
+    match state.pool.resume_relay(&payload.url).await {
+        Ok(()) => Ok(Json(RelayResponse {
+            success: true,
+            message: format!("Resumed relay: {}", payload.url),
+        })),
+        Err(e) => {
+            tracing::error!("Failed to resume relay {}: {}", payload.url, e);
+            Err(relay_action_error(e))
+        }
+    }
+}
+
+/// Query parameters for listing relays
+#[derive(Debug, Deserialize)]
+struct ListRelaysQuery {
+    status: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+/// List relays, optionally filtered by status and paginated
+async fn list_relays(
+    State(state): State<AppState>,
+    Query(query): Query<ListRelaysQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let status_filter = match query.status.as_deref() {
+        Some(raw) => Some(raw.parse::<RelayStatus>().map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": e.to_string() })),
+            )
+        })?),
+        None => None,
+    };
 
+    let mut statuses = state.pool.get_connection_statuses().await;
+    if let Some(filter) = &status_filter {
+        statuses.retain(|(_, conn)| conn.status.same_state(filter));
+    }
+    // Stable ordering so pages do not shift between calls
+    statuses.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let total = statuses.len();
+    let offset = query.offset.unwrap_or(0);
+    let limit = query.limit.unwrap_or(total);
     let mut relay_info = Vec::new();
-    for (url, status) in statuses {
+    for (url, conn) in statuses.into_iter().skip(offset).take(limit) {
+        let health_score = state.pool.health_score(&url).await;
+        let info = state.pool.relay_info(&url);
         relay_info.push(json!({
             "url": url,
-            "status": format!("{:?}", status)
+            "status": conn.status,
+            "status_since": conn.since,
+            "error": conn.error,
+            "health_score": health_score,
+            "info": info,
         }));
     }
 
-    Json(json!({
+    Ok(Json(json!({
         "relays": relay_info,
-        "count": relay_info.len()
-    }))
+        "count": relay_info.len(),
+        "total": total,
+        "offset": offset,
+    })))
 }
 
 /// Summary metrics endpoint (JSON)
-async fn metrics_summary(State(state): State<AppState>) -> Json<serde_json::Value> {
+async fn metrics_summary(State(state): State<AppState>) -> Json<MetricsSummary> {
     let m = &state.metrics;
     // Convert the kb to MB（1 MB = 1024 * 1024 bytes）
     let memory_usage_mb = m.memory_usage.get() as f64 / 1024.0;
-    Json(serde_json::json!({
-        "events_processed_total": m.events_processed.get(),
-        "duplicates_filtered_total": m.duplicates_filtered.get(),
-        "events_in_queue": m.events_in_queue.get(),
-        "active_connections": m.active_connections.get(),
-        "memory_usage_mb": memory_usage_mb,
-    }))
+    let relays = state
+        .pool
+        .list_relays()
+        .into_iter()
+        .map(|url| {
+            let summary = m.relay_summary(&url);
+            (url, summary)
+        })
+        .collect();
+    Json(MetricsSummary {
+        events_processed_total: m.events_processed.get(),
+        duplicates_filtered_total: m.duplicates_filtered.get(),
+        duplicate_ratio: m.current_duplicate_ratio(),
+        events_in_queue: m.events_in_queue.get(),
+        active_connections: m.active_connections.get(),
+        memory_usage_mb,
+        relays,
+        downstream: m.delivery_summary(),
+        average_batch_size: m.average_batch_size(),
+        end_to_end_latency_seconds: LatencyPercentiles {
+            p50: m.end_to_end_latency_quantile(0.50),
+            p95: m.end_to_end_latency_quantile(0.95),
+            p99: m.end_to_end_latency_quantile(0.99),
+        },
+    })
 }
 
 /// Memory-only endpoint
@@ -174,3 +817,614 @@ async fn memory(State(state): State<AppState>) -> Json<serde_json::Value> {
         "memory_usage_mb": memory_usage_mb,
     }))
 }
+
+/// Event counts by Nostr kind (JSON)
+async fn metrics_kinds(State(state): State<AppState>) -> Json<serde_json::Value> {
+    Json(serde_json::Value::Object(state.metrics.kind_summary()))
+}
+
+/// Which dedup layer is catching duplicates (JSON)
+async fn metrics_dedup(State(state): State<AppState>) -> Json<serde_json::Value> {
+    Json(state.metrics.dedup_summary())
+}
+
+/// Query parameters for the time-range event lookup
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    since: Option<u64>,
+    until: Option<u64>,
+    limit: Option<usize>,
+    /// `next_cursor` from the previous page
+    cursor: Option<String>,
+}
+
+/// List stored events created within `[since, until]`, oldest first, a page at a time
+async fn list_events(
+    State(state): State<AppState>,
+    Query(query): Query<EventsQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let cursor = match query.cursor.as_deref().map(str::parse::<EventCursor>) {
+        Some(Ok(cursor)) => Some(cursor),
+        Some(Err(e)) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": format!("{:#}", e) })),
+            ));
+        }
+        None => None,
+    };
+    let since = query.since.unwrap_or(0);
+    let until = query.until.unwrap_or(u64::MAX);
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_EVENTS_LIMIT)
+        .min(MAX_EVENTS_LIMIT);
+
+    match state
+        .rocksdb
+        .get_events_page(since, until, cursor.as_ref(), limit)
+        .await
+    {
+        Ok((events, next_cursor)) => Ok(Json(json!({
+            "events": events,
+            "count": events.len(),
+            "next_cursor": next_cursor.map(|c| c.to_string()),
+        }))),
+        Err(e) => {
+            tracing::error!(
+                "Failed to query events in range [{}, {}]: {}",
+                since,
+                until,
+                e
+            );
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Failed to look up events" })),
+            ))
+        }
+    }
+}
+
+/// Query parameters for the tag index lookup
+#[derive(Debug, Deserialize)]
+struct TagQuery {
+    tag: char,
+    value: String,
+    limit: Option<usize>,
+}
+
+/// List stored events referencing a value through an indexed tag (`#p` or `#e`), oldest first
+async fn events_by_tag(
+    State(state): State<AppState>,
+    Query(query): Query<TagQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    if !INDEXED_TAGS.contains(&query.tag) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("tag must be one of {:?}", INDEXED_TAGS) })),
+        ));
+    }
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_EVENTS_LIMIT)
+        .min(MAX_EVENTS_LIMIT);
+
+    match state
+        .rocksdb
+        .get_events_by_tag(query.tag, &query.value, limit)
+        .await
+    {
+        Ok(events) => Ok(Json(json!({
+            "events": events,
+            "count": events.len(),
+        }))),
+        Err(e) => {
+            tracing::error!(
+                "Failed to query events by #{}={}: {}",
+                query.tag,
+                query.value,
+                e
+            );
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Failed to look up events" })),
+            ))
+        }
+    }
+}
+
+/// Request body for the bulk existence check
+#[derive(Debug, Deserialize)]
+struct ExistsRequest {
+    ids: Vec<String>,
+}
+
+/// Report which of the given event ids are stored, as a boolean array parallel to `ids`
+async fn events_exist(
+    State(state): State<AppState>,
+    Json(payload): Json<ExistsRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    if payload.ids.len() > MAX_EXISTS_IDS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("at most {} ids per request", MAX_EXISTS_IDS) })),
+        ));
+    }
+
+    let ids: Vec<&str> = payload.ids.iter().map(String::as_str).collect();
+    match state.rocksdb.exists_many(&ids).await {
+        Ok(exists) => Ok(Json(json!({ "exists": exists }))),
+        Err(e) => {
+            tracing::error!("Failed to check {} event ids: {}", ids.len(), e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Failed to look up events" })),
+            ))
+        }
+    }
+}
+
+/// Fetch a single stored event by id
+async fn get_event(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<nostr_sdk::Event>, (StatusCode, Json<serde_json::Value>)> {
+    match state.rocksdb.get_event(&id).await {
+        Ok(Some(event)) => Ok(Json(event)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Event {} not found", id) })),
+        )),
+        Err(e) => {
+            tracing::error!("Failed to look up event {}: {}", id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Failed to look up event" })),
+            ))
+        }
+    }
+}
+
+/// Permanently delete a stored event and drop it from the dedup caches
+async fn delete_event(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let internal_error = |e: anyhow::Error| {
+        tracing::error!("Failed to delete event {}: {}", id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": "Failed to delete event" })),
+        )
+    };
+
+    let event = match state.rocksdb.get_event(&id).await {
+        Ok(Some(event)) => event,
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(json!({ "error": format!("Event {} not found", id) })),
+            ));
+        }
+        Err(e) => return Err(internal_error(e)),
+    };
+    state
+        .rocksdb
+        .delete_event(&id)
+        .await
+        .map_err(internal_error)?;
+    state.dedupe.forget(&event).await.map_err(internal_error)?;
+    tracing::info!(event_id = %id, "Deleted event {}", id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{metrics, mock_relay, serve, temp_store, unreachable_relay};
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// The REST API served on a local port over a scratch RocksDB
+    struct TestApi {
+        url: String,
+        client: reqwest::Client,
+        pool: Arc<RelayPool>,
+        dir: TempDir,
+    }
+
+    impl TestApi {
+        async fn start(api_key: Option<&str>, max_connections: usize) -> Self {
+            Self::start_with_config(api_key, max_connections, None).await
+        }
+
+        async fn start_with_config(
+            api_key: Option<&str>,
+            max_connections: usize,
+            config: Option<AppConfig>,
+        ) -> Self {
+            let (dir, rocksdb) = temp_store();
+            Self::start_over(dir, rocksdb, api_key, max_connections, config).await
+        }
+
+        /// Serve the API over `rocksdb`, keeping `dir` alive as long as the server
+        async fn start_over(
+            dir: TempDir,
+            rocksdb: Arc<RocksDBStore>,
+            api_key: Option<&str>,
+            max_connections: usize,
+            config: Option<AppConfig>,
+        ) -> Self {
+            let (pool, _events) = RelayPool::new(Duration::from_secs(60), max_connections);
+            let pool = Arc::new(pool.with_connect_timeout(Duration::from_secs(2)));
+            let dedupe = Arc::new(DeduplicationEngine::new(rocksdb.clone()));
+            let router = create_router(
+                pool.clone(),
+                dedupe,
+                metrics(),
+                rocksdb,
+                api_key.map(str::to_string),
+                &[],
+                config.map(Arc::new),
+            );
+            Self {
+                url: serve(router).await,
+                client: reqwest::Client::new(),
+                pool,
+                dir,
+            }
+        }
+
+        async fn get(&self, path: &str) -> reqwest::Response {
+            self.send(self.client.get(format!("{}{}", self.url, path)))
+                .await
+        }
+
+        async fn post(&self, path: &str, body: serde_json::Value) -> reqwest::Response {
+            self.send(
+                self.client
+                    .post(format!("{}{}", self.url, path))
+                    .json(&body),
+            )
+            .await
+        }
+
+        async fn delete(&self, path: &str, body: serde_json::Value) -> reqwest::Response {
+            self.send(
+                self.client
+                    .delete(format!("{}{}", self.url, path))
+                    .json(&body),
+            )
+            .await
+        }
+
+        async fn post_with_key(
+            &self,
+            path: &str,
+            body: serde_json::Value,
+            key: &str,
+        ) -> reqwest::Response {
+            let request = self.client.post(format!("{}{}", self.url, path));
+            self.send(request.bearer_auth(key).json(&body)).await
+        }
+
+        async fn send(&self, request: reqwest::RequestBuilder) -> reqwest::Response {
+            request.send().await.unwrap()
+        }
+    }
+
+    /// The template configuration, as a base for tests that need a config
+    fn template_config() -> AppConfig {
+        toml::from_str(include_str!("../../config.template.toml")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn batch_add_reports_each_url() {
+        let api = TestApi::start(None, 10).await;
+        let reachable = mock_relay().await;
+        let unreachable = unreachable_relay().await;
+
+        let response = api
+            .post(
+                "/api/relays/batch",
+                json!({ "urls": [reachable, unreachable, "not a url"] }),
+            )
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["succeeded"], 1);
+        assert_eq!(body["failed"], 2);
+        assert_eq!(body["results"][&reachable]["success"], true);
+        assert_eq!(body["results"][&unreachable]["success"], false);
+        assert_eq!(body["results"]["not a url"]["success"], false);
+        assert!(body["results"]["not a url"]["message"].is_string());
+    }
+
+    #[tokio::test]
+    async fn readiness_needs_a_connected_relay_and_a_writable_primary() {
+        let api = TestApi::start(None, 10).await;
+        let response = api.get("/health/ready").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        api.pool
+            .connect_and_subscribe(mock_relay().await)
+            .await
+            .unwrap();
+        let response = api.get("/health/ready").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["db_readable"], true);
+        assert_eq!(body["db_writable"], true);
+        assert_eq!(body["db_secondary"], false);
+    }
+
+    #[tokio::test]
+    async fn a_secondary_is_ready_without_accepting_writes() {
+        let (dir, _primary) = temp_store();
+        let secondary =
+            RocksDBStore::open_secondary(dir.path().join("db"), dir.path().join("secondary"))
+                .unwrap();
+        let api = TestApi::start_over(dir, Arc::new(secondary), None, 10, None).await;
+        api.pool
+            .connect_and_subscribe(mock_relay().await)
+            .await
+            .unwrap();
+
+        let response = api.get("/health/ready").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["status"], "ready");
+        assert_eq!(body["db_readable"], true);
+        assert_eq!(body["db_writable"], false);
+        assert_eq!(body["db_secondary"], true);
+    }
+
+    #[tokio::test]
+    async fn file_admin_routes_are_not_served_without_an_api_key() {
+        let api = TestApi::start(None, 10).await;
+        for route in [
+            "/api/admin/backup",
+            "/api/admin/export",
+            "/api/admin/import",
+        ] {
+            let path = api.dir.path().join("target");
+            let response = api.post(route, json!({ "path": path })).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", route);
+        }
+        assert!(!api.dir.path().join("target").exists());
+    }
+
+    #[tokio::test]
+    async fn backup_requires_the_api_key_and_a_path_inside_admin_dir() {
+        let backups = TempDir::new().unwrap();
+        let mut config = template_config();
+        config.security.admin_dir = Some(backups.path().to_string_lossy().into_owned());
+        let api = TestApi::start_with_config(Some("secret"), 10, Some(config)).await;
+
+        let response = api
+            .post("/api/admin/backup", json!({ "path": "snap" }))
+            .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        for outside in ["../snap", "/tmp/snap"] {
+            let response = api
+                .post_with_key("/api/admin/backup", json!({ "path": outside }), "secret")
+                .await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", outside);
+        }
+
+        let response = api
+            .post_with_key("/api/admin/backup", json!({ "path": "snap" }), "secret")
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(backups.path().join("snap").join("CURRENT").exists());
+    }
+
+    #[tokio::test]
+    async fn protected_routes_require_the_configured_api_key() {
+        let api = TestApi::start(Some("secret"), 10).await;
+
+        let response = api.post("/api/admin/compact", json!({})).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        for wrong in ["", "secre", "secret2", "SECRET"] {
+            let response = api
+                .post_with_key("/api/admin/compact", json!({}), wrong)
+                .await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{:?}", wrong);
+        }
+        let response = api
+            .post_with_key("/api/admin/compact", json!({}), "secret")
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        // Read-only routes stay open
+        let response = api.get("/api/relays").await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn protected_routes_are_open_without_an_api_key() {
+        let api = TestApi::start(None, 10).await;
+
+        let response = api.post("/api/admin/compact", json!({})).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn an_added_relay_is_listed_as_soon_as_the_add_returns() {
+        let api = TestApi::start(None, 10).await;
+        let relay = mock_relay().await;
+
+        let response = api.post("/api/relays/add", json!({ "url": relay })).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body: serde_json::Value = api.get("/api/relays").await.json().await.unwrap();
+        assert_eq!(body["total"], 1);
+        assert_eq!(body["relays"][0]["url"], relay);
+        assert_eq!(body["relays"][0]["status"], "connected");
+    }
+
+    #[tokio::test]
+    async fn add_with_wait_returns_once_the_relay_is_connected() {
+        let api = TestApi::start(None, 10).await;
+        let relay = mock_relay().await;
+
+        let response = api
+            .post("/api/relays/add", json!({ "url": relay, "wait": true }))
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = api.get("/api/relays").await.json().await.unwrap();
+        assert_eq!(body["relays"][0]["status"], "connected");
+    }
+
+    #[tokio::test]
+    async fn add_without_wait_accepts_and_connects_in_the_background() {
+        let api = TestApi::start(None, 10).await;
+        let relay = mock_relay().await;
+
+        let response = api
+            .post("/api/relays/add", json!({ "url": relay, "wait": false }))
+            .await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let listed = async {
+            loop {
+                let body: serde_json::Value = api.get("/api/relays").await.json().await.unwrap();
+                if body["relays"][0]["status"] == "connected" {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), listed)
+            .await
+            .expect("relay connected in the background");
+
+        // What can be decided up front is still refused before anything is spawned
+        let response = api
+            .post("/api/relays/add", json!({ "url": relay, "wait": false }))
+            .await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn a_background_add_is_listed_while_connecting_and_then_as_failed() {
+        let api = TestApi::start(None, 10).await;
+        let relay = unreachable_relay().await;
+
+        let response = api
+            .post("/api/relays/add", json!({ "url": relay, "wait": false }))
+            .await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let body: serde_json::Value = api.get("/api/relays").await.json().await.unwrap();
+        assert_eq!(body["relays"][0]["url"], relay);
+        assert_eq!(body["relays"][0]["status"], "connecting");
+        let response = api
+            .post("/api/relays/add", json!({ "url": relay, "wait": false }))
+            .await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let failed = async {
+            loop {
+                let body: serde_json::Value = api.get("/api/relays").await.json().await.unwrap();
+                if body["relays"][0]["status"] == "failed" {
+                    return body;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        };
+        let body = tokio::time::timeout(Duration::from_secs(5), failed)
+            .await
+            .expect("background add was not marked failed");
+        assert!(body["relays"][0]["error"].is_string());
+        assert_eq!(api.pool.active_connections(), 0);
+
+        // Removing the failed relay clears its listing
+        let response = api
+            .delete("/api/relays/remove", json!({ "url": relay }))
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = api.get("/api/relays").await.json().await.unwrap();
+        assert_eq!(body["total"], 0);
+    }
+
+    fn accept(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn openmetrics_is_chosen_only_when_preferred() {
+        assert!(prefers_openmetrics(&accept("application/openmetrics-text")));
+        assert!(prefers_openmetrics(&accept(
+            "application/openmetrics-text;version=1.0.0,text/plain;version=0.0.4;q=0.5,*/*;q=0.1"
+        )));
+        assert!(!prefers_openmetrics(&HeaderMap::new()));
+        assert!(!prefers_openmetrics(&accept("text/plain")));
+        assert!(!prefers_openmetrics(&accept(
+            "application/openmetrics-text;q=0"
+        )));
+        assert!(!prefers_openmetrics(&accept(
+            "application/openmetrics-text; q=0.2, text/plain; q=0.9"
+        )));
+    }
+
+    #[test]
+    fn openmetrics_renames_counter_families_and_ends_in_eof() {
+        let text = "\
+# HELP events_total Events seen
+# TYPE events_total counter
+events_total{relay=\"a\"} 3
+# HELP drops Dropped frames
+# TYPE drops counter
+drops 1
+# HELP legacy Untyped metric
+# TYPE legacy untyped
+legacy 2
+# HELP depth Queue depth
+# TYPE depth gauge
+depth 5
+";
+        let expected = "\
+# HELP events Events seen
+# TYPE events counter
+events_total{relay=\"a\"} 3
+# HELP drops Dropped frames
+# TYPE drops counter
+drops_total 1
+# HELP legacy Untyped metric
+# TYPE legacy unknown
+legacy 2
+# HELP depth Queue depth
+# TYPE depth gauge
+depth 5
+# EOF
+";
+        assert_eq!(to_openmetrics(text), expected);
+    }
+
+    #[tokio::test]
+    async fn metrics_are_served_in_the_format_the_client_prefers() {
+        let api = TestApi::start(None, 10).await;
+        let scrape = |accept: &str| {
+            let request = api.client.get(format!("{}/metrics", api.url));
+            api.send(request.header(header::ACCEPT, accept))
+        };
+
+        let response = scrape("application/openmetrics-text;version=1.0.0").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            OPENMETRICS_CONTENT_TYPE
+        );
+        assert!(response.text().await.unwrap().ends_with("# EOF\n"));
+
+        let response = scrape("application/openmetrics-text;q=0, text/plain").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let content_type = response.headers()[header::CONTENT_TYPE].to_str().unwrap();
+        assert!(content_type.starts_with("text/plain"));
+        assert!(!response.text().await.unwrap().contains("# EOF"));
+    }
+}
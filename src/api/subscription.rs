@@ -0,0 +1,168 @@
+use nostr_sdk::Event;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::storage::rocksdb_store::INDEXED_TAGS;
+
+/// NIP-01 subscription filter as sent by clients in a `REQ` message
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SubscriptionFilter {
+    #[serde(default)]
+    pub ids: Option<Vec<String>>,
+    #[serde(default)]
+    pub authors: Option<Vec<String>>,
+    #[serde(default)]
+    pub kinds: Option<Vec<u16>>,
+    #[serde(default)]
+    pub since: Option<u64>,
+    #[serde(default)]
+    pub until: Option<u64>,
+    /// Tag filters keyed by `#<letter>` (e.g. `#e`, `#p`)
+    #[serde(flatten)]
+    pub tags: HashMap<String, serde_json::Value>,
+}
+
+impl SubscriptionFilter {
+    /// Check whether an event satisfies every condition of this filter
+    pub fn matches(&self, event: &Event) -> bool {
+        if let Some(ids) = &self.ids {
+            let id = event.id.to_hex();
+            if !ids.iter().any(|i| id.starts_with(i.as_str())) {
+                return false;
+            }
+        }
+
+        if let Some(authors) = &self.authors {
+            let author = event.pubkey.to_hex();
+            if !authors.iter().any(|a| author.starts_with(a.as_str())) {
+                return false;
+            }
+        }
+
+        if let Some(kinds) = &self.kinds {
+            if !kinds.contains(&event.kind.as_u16()) {
+                return false;
+            }
+        }
+
+        let created_at = event.created_at.as_secs();
+        if self.since.is_some_and(|since| created_at < since) {
+            return false;
+        }
+        if self.until.is_some_and(|until| created_at > until) {
+            return false;
+        }
+
+        self.tag_filters().all(|(name, values)| {
+            event.tags.iter().any(|tag| {
+                let tag = tag.as_slice();
+                tag.len() >= 2 && tag[0] == name && values.iter().any(|v| v == &tag[1])
+            })
+        })
+    }
+
+    /// Whether the filter has no conditions and so matches every event
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_none()
+            && self.authors.is_none()
+            && self.kinds.is_none()
+            && self.since.is_none()
+            && self.until.is_none()
+            && self.tag_filters().next().is_none()
+    }
+
+    /// The first tag filter that can be answered from the RocksDB tag index
+    pub fn indexed_tag(&self) -> Option<(char, Vec<&str>)> {
+        self.tag_filters().find_map(|(name, values)| {
+            let tag = name.chars().next()?;
+            INDEXED_TAGS.contains(&tag).then_some((tag, values))
+        })
+    }
+
+    /// Iterate over the `#<letter>` tag filters, yielding the tag name and accepted values
+    fn tag_filters(&self) -> impl Iterator<Item = (&str, Vec<&str>)> {
+        self.tags.iter().filter_map(|(key, value)| {
+            let name = key.strip_prefix('#')?;
+            if name.chars().count() != 1 {
+                return None;
+            }
+            let values = value
+                .as_array()?
+                .iter()
+                .filter_map(|v| v.as_str())
+                .collect();
+            Some((name, values))
+        })
+    }
+}
+
+/// Parsed client-to-relay message
+#[derive(Debug)]
+pub enum ClientRequest {
+    Req {
+        subscription_id: String,
+        filters: Vec<SubscriptionFilter>,
+    },
+    Close {
+        subscription_id: String,
+    },
+    /// NIP-45 request for the number of stored events matching the filters
+    Count {
+        subscription_id: String,
+        filters: Vec<SubscriptionFilter>,
+    },
+    /// NIP-42 signed answer to the server's `AUTH` challenge
+    Auth {
+        event: Box<Event>,
+    },
+}
+
+impl ClientRequest {
+    /// Parse a raw text frame into a client request
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let value: serde_json::Value =
+            serde_json::from_str(text).map_err(|e| format!("invalid: malformed JSON: {}", e))?;
+        let items = value
+            .as_array()
+            .ok_or_else(|| "invalid: message must be a JSON array".to_string())?;
+        let kind = items
+            .first()
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "invalid: missing message type".to_string())?;
+        if kind == "AUTH" {
+            let event = items
+                .get(1)
+                .ok_or_else(|| "invalid: missing AUTH event".to_string())?;
+            let event = serde_json::from_value(event.clone())
+                .map_err(|e| format!("invalid: malformed AUTH event: {}", e))?;
+            return Ok(Self::Auth {
+                event: Box::new(event),
+            });
+        }
+        let subscription_id = items
+            .get(1)
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "invalid: missing subscription id".to_string())?
+            .to_string();
+
+        let filters = || {
+            items[2..]
+                .iter()
+                .map(|f| serde_json::from_value(f.clone()))
+                .collect::<Result<Vec<SubscriptionFilter>, _>>()
+                .map_err(|e| format!("invalid: malformed filter: {}", e))
+        };
+        match kind {
+            "REQ" => Ok(Self::Req {
+                subscription_id,
+                filters: filters()?,
+            }),
+            "CLOSE" => Ok(Self::Close { subscription_id }),
+            "COUNT" => Ok(Self::Count {
+                subscription_id,
+                filters: filters()?,
+            }),
+            other => Err(format!("unsupported: message type {}", other)),
+        }
+    }
+}
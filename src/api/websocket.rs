@@ -1,58 +1,511 @@
 use axum::{
     Router,
     extract::{
-        State,
-        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade, close_code},
     },
     response::Response,
     routing::get,
 };
 use flume::Receiver;
 use futures_util::{SinkExt, StreamExt};
-use nostr_sdk::Event;
-use serde_json;
-use std::sync::Arc;
-use tracing::{error, info};
+use nostr_sdk::{Event, EventId, Kind, PublicKey, Timestamp};
+use serde::Deserialize;
+use serde_json::{self, json};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::{Notify, RwLock, watch};
+use tracing::{debug, error, info, warn};
 
-// use crate::core::relay_pool::RelayPool;
+use crate::api::metrics::Metrics;
+use crate::api::subscription::{ClientRequest, SubscriptionFilter};
+use crate::core::event_router::RoutedEvent;
+use crate::core::relay_pool::{RelayPool, RelayStatusChange};
+use crate::storage::rocksdb_store::{EventCursor, RocksDBStore};
+
+/// Capacity of the fan-out channel shared by all WebSocket clients
+const BROADCAST_CAPACITY: usize = 4096;
+/// Events read from RocksDB per page while replaying history
+const REPLAY_PAGE_SIZE: usize = 500;
+/// Upper bound on events replayed to a single connection
+const MAX_REPLAY_EVENTS: usize = 10_000;
+/// Upper bound on stored events examined for a single `COUNT`; larger counts are approximate
+const MAX_COUNT_SCAN: usize = 50_000;
+/// Largest difference in seconds between an `AUTH` event's `created_at` and the server clock
+const AUTH_MAX_CLOCK_SKEW_SECS: u64 = 600;
+
+/// Shared state for WebSocket connections
+#[derive(Clone)]
+struct WsState {
+    event_tx: broadcast::Sender<RoutedEvent>,
+    heartbeat: Heartbeat,
+    limits: ClientLimits,
+    /// Pubkeys allowed to authenticate; clients must pass NIP-42 AUTH when set
+    allowed_pubkeys: Option<Arc<HashSet<PublicKey>>>,
+    rocksdb: Arc<RocksDBStore>,
+    metrics: Arc<Metrics>,
+}
+
+/// Shared state for the relay status control channel
+#[derive(Clone)]
+struct ControlState {
+    pool: Arc<RelayPool>,
+    heartbeat: Heartbeat,
+}
+
+/// Query parameters accepted on the `/ws` upgrade
+#[derive(Debug, Deserialize)]
+struct WsParams {
+    /// Replay stored events created at or after this unix timestamp before streaming live
+    replay_since: Option<u64>,
+    /// How events are framed for this connection
+    #[serde(default)]
+    protocol: WsProtocol,
+}
+
+/// Event framing negotiated with `?protocol=` on the `/ws` upgrade
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum WsProtocol {
+    /// Bare event JSON per frame until the client opens a subscription
+    #[default]
+    Raw,
+    /// Standard relay protocol: events are only sent as `["EVENT", subid, event]` for open
+    /// subscriptions
+    Nostr,
+}
+
+/// Server-initiated keepalive settings for WebSocket clients
+#[derive(Debug, Clone, Copy)]
+pub struct Heartbeat {
+    /// Interval between server `Ping` frames
+    pub ping_interval: Duration,
+    /// Close the connection if nothing (including `Pong`) is received for this long
+    pub idle_timeout: Duration,
+}
+
+/// Per-client queueing and subscription limits
+#[derive(Debug, Clone, Copy)]
+pub struct ClientLimits {
+    /// Events queued per client before the oldest are dropped
+    pub buffer: usize,
+    /// Dropped-event count after which a lagging client is disconnected
+    pub slow_disconnect_after: Option<u64>,
+    /// Open `REQ` subscriptions allowed per client
+    pub max_subscriptions: usize,
+}
+
+/// The `REQ` subscriptions a client has open
+#[derive(Default)]
+struct Subscriptions {
+    open: HashMap<String, Vec<SubscriptionFilter>>,
+    /// Set by the client's first `REQ`; closing every subscription does not bring back the
+    /// raw stream
+    requested: bool,
+}
+
+/// NIP-42 authentication state of one WebSocket client
+struct ClientAuth {
+    challenge: String,
+    allowed: Arc<HashSet<PublicKey>>,
+    /// Set by the receive task as soon as a valid `AUTH` arrives
+    accepted: AtomicBool,
+    /// Wakes the send task once the `OK` for the accepted `AUTH` has been queued
+    authenticated: watch::Sender<bool>,
+}
+
+impl ClientAuth {
+    fn new(allowed: Arc<HashSet<PublicKey>>) -> Self {
+        Self {
+            challenge: rand::random::<[u8; 16]>()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+            allowed,
+            accepted: AtomicBool::new(false),
+            authenticated: watch::channel(false).0,
+        }
+    }
+
+    fn is_authenticated(&self) -> bool {
+        self.accepted.load(Ordering::Acquire)
+    }
+
+    /// Check a kind 22242 event answering this client's challenge, accepting the client if valid
+    fn verify(&self, event: &Event) -> Result<(), &'static str> {
+        if event.kind != Kind::Authentication {
+            return Err("invalid: AUTH requires a kind 22242 event");
+        }
+        if event.verify().is_err() {
+            return Err("invalid: bad event id or signature");
+        }
+        let answers_challenge = event.tags.iter().any(|tag| {
+            let tag = tag.as_slice();
+            tag.len() >= 2 && tag[0] == "challenge" && tag[1] == self.challenge
+        });
+        if !answers_challenge {
+            return Err("invalid: challenge does not match");
+        }
+        let skew = Timestamp::now()
+            .as_secs()
+            .abs_diff(event.created_at.as_secs());
+        if skew > AUTH_MAX_CLOCK_SKEW_SECS {
+            return Err("invalid: created_at is too far from the current time");
+        }
+        if !self.allowed.contains(&event.pubkey) {
+            return Err("restricted: pubkey is not allowed to stream events");
+        }
+        self.accepted.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Start streaming once the client has been accepted
+    fn publish(&self) {
+        if self.is_authenticated() && !*self.authenticated.borrow() {
+            self.authenticated.send_replace(true);
+        }
+    }
+}
+
+/// Bounded per-client queue between the shared broadcast channel and the socket writer
+///
+/// Pushing never blocks: when the client falls behind the oldest queued events are
+/// discarded and counted, so one slow reader cannot stall delivery to the others.
+struct ClientBuffer {
+    events: Mutex<VecDeque<RoutedEvent>>,
+    capacity: usize,
+    dropped: AtomicU64,
+    closed: AtomicBool,
+    notify: Notify,
+}
+
+impl ClientBuffer {
+    fn new(capacity: usize) -> Self {
+        Self {
+            events: Mutex::new(VecDeque::with_capacity(capacity.min(BROADCAST_CAPACITY))),
+            capacity: capacity.max(1),
+            dropped: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+            notify: Notify::new(),
+        }
+    }
+
+    /// Queue an event, evicting the oldest one if the buffer is full
+    fn push(&self, event: RoutedEvent) {
+        {
+            let mut events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+            if events.len() >= self.capacity {
+                events.pop_front();
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            events.push_back(event);
+        }
+        self.notify.notify_one();
+    }
+
+    /// Account for events that never reached the buffer
+    fn record_dropped(&self, count: u64) {
+        self.dropped.fetch_add(count, Ordering::Relaxed);
+        self.notify.notify_one();
+    }
+
+    /// Mark the upstream channel as closed and wake the writer
+    fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.notify.notify_one();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Take every queued event together with the number dropped since the last call
+    fn drain(&self) -> (Vec<RoutedEvent>, u64) {
+        let events = self
+            .events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .drain(..)
+            .collect();
+        (events, self.take_dropped())
+    }
+
+    /// Take the number of events dropped since the last call, leaving queued events in place
+    fn take_dropped(&self) -> u64 {
+        self.dropped.swap(0, Ordering::Relaxed)
+    }
+}
 
 /// WebSocket handler for streaming events to downstream systems
 async fn websocket_handler(
     ws: WebSocketUpgrade,
-    State(event_rx): State<Arc<Receiver<Event>>>,
+    Query(params): Query<WsParams>,
+    State(state): State<WsState>,
 ) -> Response {
-    ws.on_upgrade(|socket| handle_socket(socket, event_rx))
+    // Subscribe before upgrading so the client does not miss events sent during the handshake
+    // (or while history is being replayed)
+    let event_rx = state.event_tx.subscribe();
+    ws.on_upgrade(move |socket| handle_socket(socket, event_rx, state, params))
 }
 
 /// Handle individual WebSocket connection
-async fn handle_socket(socket: WebSocket, event_rx: Arc<Receiver<Event>>) {
+///
+/// Clients that never send a `REQ` receive the raw event stream, unless they connected with
+/// `?protocol=nostr`, in which case nothing is streamed before a `REQ`. Once a `REQ` has been
+/// sent, events are only delivered as NIP-01 `EVENT` messages for the subscriptions whose
+/// filters they match, even after every subscription has been closed again.
+///
+/// With `replay_since`, stored events created since the given timestamp are sent first; live
+/// events that arrive meanwhile are queued and any already replayed are skipped.
+///
+/// A client that has had `slow_client_disconnect_after` events dropped in total, not counting
+/// those dropped during the replay, is closed with a policy-violation close frame. A `REQ`
+/// beyond `max_subscriptions` open subscriptions is refused with `CLOSED` and leaves the
+/// existing ones untouched.
+///
+/// With an allowlist of pubkeys the server opens with a NIP-42 `AUTH` challenge, and nothing
+/// (replay included) is streamed until the client answers it with an accepted event.
+async fn handle_socket(
+    socket: WebSocket,
+    mut event_rx: broadcast::Receiver<RoutedEvent>,
+    state: WsState,
+    params: WsParams,
+) {
+    let WsParams {
+        replay_since,
+        protocol,
+    } = params;
     info!("New WebSocket connection established");
+    let WsState {
+        heartbeat,
+        limits,
+        allowed_pubkeys,
+        rocksdb,
+        metrics,
+        ..
+    } = state;
+    let client_auth = allowed_pubkeys.map(ClientAuth::new);
+    let auth_challenge = client_auth.as_ref().map(|auth| {
+        let authenticated = auth.authenticated.subscribe();
+        (auth.challenge.clone(), authenticated)
+    });
+
+    // Move events off the shared channel immediately; backpressure is applied per client
+    let buffer = Arc::new(ClientBuffer::new(limits.buffer));
+    let pump_buffer = buffer.clone();
+    let pump_task = tokio::spawn(async move {
+        loop {
+            match event_rx.recv().await {
+                Ok(event) => pump_buffer.push(event),
+                Err(RecvError::Lagged(skipped)) => pump_buffer.record_dropped(skipped),
+                Err(RecvError::Closed) => break,
+            }
+        }
+        pump_buffer.close();
+    });
 
     let (mut sender, mut receiver) = socket.split();
+    let subscriptions: Arc<RwLock<Subscriptions>> = Arc::default();
+    // Relay-to-client control messages (EOSE, CLOSED, NOTICE) produced by the receive task
+    let (reply_tx, reply_rx) = flume::unbounded::<String>();
 
     // Spawn task to send events to client
-    let send_task = tokio::spawn(async move {
-        let event_rx = event_rx.clone();
-        while let Ok(event) = event_rx.recv_async().await {
-            let json = match serde_json::to_string(&event) {
-                Ok(j) => j,
-                Err(e) => {
-                    error!("Failed to serialize event: {}", e);
-                    continue;
+    let send_subscriptions = subscriptions.clone();
+    let replay_store = rocksdb.clone();
+    let mut send_task = tokio::spawn(async move {
+        if let Some((challenge, mut authenticated)) = auth_challenge {
+            let frame = json!(["AUTH", challenge]).to_string();
+            if let Err(e) = sender.send(Message::Text(frame.into())).await {
+                error!("Failed to send AUTH challenge: {}", e);
+                return;
+            }
+            let mut auth_ping = tokio::time::interval(heartbeat.ping_interval);
+            auth_ping.tick().await;
+            while !*authenticated.borrow_and_update() {
+                let frame = tokio::select! {
+                    _ = auth_ping.tick() => Message::Ping(Default::default()),
+                    reply = reply_rx.recv_async() => match reply {
+                        Ok(reply) => Message::Text(reply.into()),
+                        Err(_) => return,
+                    },
+                    changed = authenticated.changed() => match changed {
+                        Ok(()) => continue,
+                        Err(_) => return,
+                    },
+                };
+                if let Err(e) = sender.send(frame).await {
+                    error!("Failed to send WebSocket message: {}", e);
+                    return;
                 }
-            };
+            }
+            // The OK for the accepted AUTH is queued before streaming is unlocked
+            while let Ok(reply) = reply_rx.try_recv() {
+                if let Err(e) = sender.send(Message::Text(reply.into())).await {
+                    error!("Failed to send WebSocket message: {}", e);
+                    return;
+                }
+            }
+            // Events that arrived before authentication are not delivered
+            let _ = buffer.drain();
+        }
 
-            if let Err(e) = sender.send(Message::Text(json.into())).await {
+        // Ids sent during replay, so the same events arriving on the live feed are not repeated
+        let mut replayed: HashSet<EventId> = HashSet::new();
+        if let Some(since) = replay_since {
+            let history = load_replay(&replay_store, since).await;
+            info!("Replaying {} stored events since {}", history.len(), since);
+            for event in history {
+                let frames = event_frames(
+                    &event,
+                    &*send_subscriptions.read().await,
+                    protocol,
+                    &metrics,
+                );
+                replayed.insert(event.id);
+                for frame in frames {
+                    if let Err(e) = sender.send(Message::Text(frame.into())).await {
+                        error!("Failed to send replayed event: {}", e);
+                        return;
+                    }
+                }
+            }
+        }
+
+        // Live events dropped while history was being sent are reported, but the client had no
+        // chance to keep up with them, so they do not count towards `slow_disconnect_after`
+        let replay_dropped = buffer.take_dropped();
+        if replay_dropped > 0 {
+            warn!(
+                "WebSocket client dropped {} live events during replay",
+                replay_dropped
+            );
+            let notice = format!("dropped: {} events during replay", replay_dropped);
+            let frame = json!(["NOTICE", notice]).to_string();
+            if let Err(e) = sender.send(Message::Text(frame.into())).await {
                 error!("Failed to send WebSocket message: {}", e);
+                return;
+            }
+        }
+
+        let mut ping_timer = tokio::time::interval(heartbeat.ping_interval);
+        // The first tick completes immediately; skip it so pings start one interval in
+        ping_timer.tick().await;
+        let mut finished = false;
+        let mut total_dropped: u64 = 0;
+        loop {
+            // Relay receipt times of the live events in this round, for end-to-end latency
+            let mut delivered: Vec<Instant> = Vec::new();
+            let frames = tokio::select! {
+                _ = ping_timer.tick() => {
+                    if let Err(e) = sender.send(Message::Ping(Default::default())).await {
+                        error!("Failed to send WebSocket ping: {}", e);
+                        return;
+                    }
+                    continue;
+                },
+                reply = reply_rx.recv_async() => match reply {
+                    Ok(reply) => vec![reply],
+                    Err(_) => break,
+                },
+                _ = buffer.notify.notified() => {
+                    // Check before draining so events queued ahead of the close are still sent
+                    finished = buffer.is_closed();
+                    let (mut events, dropped) = buffer.drain();
+                    if !replayed.is_empty() {
+                        events.retain(|routed| !replayed.remove(&routed.event.id));
+                    }
+                    let mut frames = Vec::new();
+                    total_dropped += dropped;
+                    if limits
+                        .slow_disconnect_after
+                        .is_some_and(|limit| total_dropped >= limit)
+                    {
+                        warn!(
+                            "Disconnecting slow WebSocket client after {} dropped events",
+                            total_dropped
+                        );
+                        metrics.ws_slow_client_disconnects.inc();
+                        let reason = format!("slow consumer: dropped {} events", total_dropped);
+                        let close = CloseFrame {
+                            code: close_code::POLICY,
+                            reason: reason.into(),
+                        };
+                        let _ = sender.send(Message::Close(Some(close))).await;
+                        return;
+                    }
+                    if dropped > 0 {
+                        warn!("WebSocket client lagging behind, dropped {} events", dropped);
+                        let notice =
+                            format!("dropped: {} events, client is not keeping up", dropped);
+                        frames.push(json!(["NOTICE", notice]).to_string());
+                    }
+                    let subscriptions = send_subscriptions.read().await;
+                    for routed in &events {
+                        let sent =
+                            event_frames(&routed.event, &subscriptions, protocol, &metrics);
+                        if !sent.is_empty() {
+                            delivered.push(routed.received_at);
+                        }
+                        frames.extend(sent);
+                    }
+                    frames
+                },
+            };
+
+            for frame in frames {
+                if let Err(e) = sender.send(Message::Text(frame.into())).await {
+                    error!("Failed to send WebSocket message: {}", e);
+                    return;
+                }
+            }
+            for received_at in delivered {
+                metrics.observe_end_to_end_latency(received_at.elapsed().as_secs_f64());
+            }
+            if finished {
                 break;
             }
         }
     });
 
-    // Spawn task to receive messages from client (for ping/pong, etc.)
-    let recv_task = tokio::spawn(async move {
-        while let Some(Ok(msg)) = receiver.next().await {
+    // Spawn task to receive messages from client (subscriptions, ping/pong, etc.)
+    let mut recv_task = tokio::spawn(async move {
+        loop {
+            // Any frame from the client, including Pong, counts as liveness
+            let msg = match tokio::time::timeout(heartbeat.idle_timeout, receiver.next()).await {
+                Ok(Some(Ok(msg))) => msg,
+                Ok(_) => break,
+                Err(_) => {
+                    info!(
+                        "WebSocket client idle for {:?}, closing connection",
+                        heartbeat.idle_timeout
+                    );
+                    break;
+                }
+            };
             match msg {
+                Message::Text(text) => {
+                    let reply = handle_client_message(
+                        text.as_str(),
+                        &subscriptions,
+                        limits.max_subscriptions,
+                        &rocksdb,
+                        client_auth.as_ref(),
+                    )
+                    .await;
+                    // A CLOSE has no reply and never changes the authentication state
+                    let Some(reply) = reply else {
+                        continue;
+                    };
+                    if reply_tx.send_async(reply).await.is_err() {
+                        break;
+                    }
+                    if let Some(auth) = &client_auth {
+                        auth.publish();
+                    }
+                }
                 Message::Close(_) => {
                     info!("WebSocket connection closed by client");
                     break;
@@ -65,18 +518,926 @@ async fn handle_socket(socket: WebSocket, event_rx: Arc<Receiver<Event>>) {
         }
     });
 
-    // Wait for either task to complete
+    // Wait for either task to complete, then stop the other so the connection is fully released
     tokio::select! {
-        _ = send_task => {}
-        _ = recv_task => {}
+        _ = &mut send_task => recv_task.abort(),
+        _ = &mut recv_task => send_task.abort(),
     }
+    pump_task.abort();
 
     info!("WebSocket connection closed");
 }
 
+/// WebSocket handler streaming relay status changes as JSON objects
+async fn control_handler(ws: WebSocketUpgrade, State(state): State<ControlState>) -> Response {
+    // Subscribe before upgrading so changes during the handshake are not missed
+    let changes = state.pool.subscribe_status_changes();
+    ws.on_upgrade(move |socket| handle_control_socket(socket, changes, state.heartbeat))
+}
+
+/// Forward every relay status change to a control client until either side goes away.
+/// Incoming frames are ignored apart from keeping the connection alive.
+async fn handle_control_socket(
+    socket: WebSocket,
+    mut changes: broadcast::Receiver<RelayStatusChange>,
+    heartbeat: Heartbeat,
+) {
+    info!("New control WebSocket connection established");
+    let (mut sender, mut receiver) = socket.split();
+
+    let mut send_task = tokio::spawn(async move {
+        let mut ping_timer = tokio::time::interval(heartbeat.ping_interval);
+        ping_timer.tick().await;
+        loop {
+            let frame = tokio::select! {
+                _ = ping_timer.tick() => Message::Ping(Default::default()),
+                change = changes.recv() => match change {
+                    Ok(change) => match serde_json::to_string(&change) {
+                        Ok(json) => Message::Text(json.into()),
+                        Err(e) => {
+                            error!("Failed to serialize relay status change: {}", e);
+                            continue;
+                        }
+                    },
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Control WebSocket client lagging, dropped {} changes", skipped);
+                        let notice = format!("dropped: {} status changes", skipped);
+                        Message::Text(json!(["NOTICE", notice]).to_string().into())
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            };
+            if let Err(e) = sender.send(frame).await {
+                error!("Failed to send control WebSocket message: {}", e);
+                return;
+            }
+        }
+    });
+
+    let mut recv_task = tokio::spawn(async move {
+        loop {
+            match tokio::time::timeout(heartbeat.idle_timeout, receiver.next()).await {
+                Ok(Some(Ok(Message::Close(_)))) | Ok(None) | Ok(Some(Err(_))) => break,
+                Ok(Some(Ok(_))) => {}
+                Err(_) => {
+                    info!(
+                        "Control WebSocket client idle for {:?}, closing connection",
+                        heartbeat.idle_timeout
+                    );
+                    break;
+                }
+            }
+        }
+    });
+
+    tokio::select! {
+        _ = &mut send_task => recv_task.abort(),
+        _ = &mut recv_task => send_task.abort(),
+    }
+
+    info!("Control WebSocket connection closed");
+}
+
+/// Apply a client message to the subscription table and build the reply frame, if any.
+/// A `CLOSE` is not answered: `CLOSED` is only sent for subscriptions the server ends.
+async fn handle_client_message(
+    text: &str,
+    subscriptions: &RwLock<Subscriptions>,
+    max_subscriptions: usize,
+    rocksdb: &RocksDBStore,
+    auth: Option<&ClientAuth>,
+) -> Option<String> {
+    let reply = match ClientRequest::parse(text) {
+        Ok(ClientRequest::Auth { event }) => auth_reply(&event, auth),
+        Ok(
+            ClientRequest::Req {
+                subscription_id, ..
+            }
+            | ClientRequest::Count {
+                subscription_id, ..
+            },
+        ) if auth.is_some_and(|auth| !auth.is_authenticated()) => json!([
+            "CLOSED",
+            subscription_id,
+            "auth-required: authenticate before subscribing"
+        ])
+        .to_string(),
+        Ok(ClientRequest::Req {
+            subscription_id,
+            filters,
+        }) => {
+            let mut subscriptions = subscriptions.write().await;
+            subscriptions.requested = true;
+            if filters.is_empty() {
+                debug!(
+                    "WebSocket subscription {} refused: no filters",
+                    subscription_id
+                );
+                let reason = "invalid: a REQ needs at least one filter";
+                return Some(json!(["CLOSED", subscription_id, reason]).to_string());
+            }
+            // Re-sending an open subscription id replaces its filters and needs no new slot
+            if subscriptions.open.len() >= max_subscriptions
+                && !subscriptions.open.contains_key(&subscription_id)
+            {
+                debug!(
+                    "WebSocket subscription {} refused: limit reached",
+                    subscription_id
+                );
+                let reason = format!(
+                    "blocked: too many subscriptions, at most {} may be open",
+                    max_subscriptions
+                );
+                return Some(json!(["CLOSED", subscription_id, reason]).to_string());
+            }
+            debug!("WebSocket subscription {} opened", subscription_id);
+            subscriptions.open.insert(subscription_id.clone(), filters);
+            // No stored backlog is replayed, so the stored-events phase ends immediately
+            json!(["EOSE", subscription_id]).to_string()
+        }
+        Ok(ClientRequest::Close { subscription_id }) => {
+            debug!("WebSocket subscription {} closed", subscription_id);
+            subscriptions.write().await.open.remove(&subscription_id);
+            return None;
+        }
+        Ok(ClientRequest::Count {
+            subscription_id,
+            filters,
+        }) => match count_stored(rocksdb, &filters).await {
+            Ok((count, false)) => json!(["COUNT", subscription_id, { "count": count }]).to_string(),
+            Ok((count, true)) => json!([
+                "COUNT",
+                subscription_id,
+                { "count": count, "approximate": true }
+            ])
+            .to_string(),
+            Err(e) => {
+                error!("Failed to count stored events: {:#}", e);
+                json!(["CLOSED", subscription_id, "error: failed to count events"]).to_string()
+            }
+        },
+        Err(reason) => json!(["NOTICE", reason]).to_string(),
+    };
+    Some(reply)
+}
+
+/// Answer a NIP-42 `AUTH` with `OK`, accepting the client when the event checks out
+fn auth_reply(event: &Event, auth: Option<&ClientAuth>) -> String {
+    let outcome = match auth {
+        Some(auth) => auth.verify(event),
+        None => Err("error: authentication is not enabled"),
+    };
+    match outcome {
+        Ok(()) => {
+            info!(pubkey = %event.pubkey, "WebSocket client authenticated");
+            json!(["OK", event.id, true, ""]).to_string()
+        }
+        Err(reason) => {
+            debug!(pubkey = %event.pubkey, "WebSocket AUTH rejected: {}", reason);
+            json!(["OK", event.id, false, reason]).to_string()
+        }
+    }
+}
+
+/// Count stored events matching any of the filters, returning the count and whether it is
+/// approximate. A filter without conditions answers with RocksDB's key estimate; otherwise
+/// candidates come from the tag index when the filter has a `#p`/`#e` condition, or from the
+/// `created_at` index, and the scan stops after `MAX_COUNT_SCAN` events.
+async fn count_stored(
+    rocksdb: &RocksDBStore,
+    filters: &[SubscriptionFilter],
+) -> anyhow::Result<(u64, bool)> {
+    if filters.is_empty() || filters.iter().any(SubscriptionFilter::is_empty) {
+        return Ok((rocksdb.approximate_count().await, true));
+    }
+
+    let mut matched: HashSet<EventId> = HashSet::new();
+    let mut scanned = 0;
+    for filter in filters {
+        if let Some((tag, values)) = filter.indexed_tag() {
+            for value in values {
+                let candidates = rocksdb
+                    .get_events_by_tag(tag, value, MAX_COUNT_SCAN - scanned)
+                    .await?;
+                scanned += candidates.len();
+                matched.extend(
+                    candidates
+                        .iter()
+                        .filter(|e| filter.matches(e))
+                        .map(|e| e.id),
+                );
+                if scanned >= MAX_COUNT_SCAN {
+                    return Ok((matched.len() as u64, true));
+                }
+            }
+            continue;
+        }
+
+        let since = filter.since.unwrap_or(0);
+        let until = filter.until.unwrap_or(u64::MAX);
+        let mut cursor: Option<EventCursor> = None;
+        loop {
+            let (page, next) = rocksdb
+                .get_events_page(since, until, cursor.as_ref(), REPLAY_PAGE_SIZE)
+                .await?;
+            scanned += page.len();
+            matched.extend(page.iter().filter(|e| filter.matches(e)).map(|e| e.id));
+            if scanned >= MAX_COUNT_SCAN {
+                return Ok((matched.len() as u64, true));
+            }
+            match next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+    }
+    Ok((matched.len() as u64, false))
+}
+
+/// Load stored events created at or after `since`, oldest first, up to `MAX_REPLAY_EVENTS`
+async fn load_replay(rocksdb: &RocksDBStore, since: u64) -> Vec<Event> {
+    let mut history: Vec<Event> = Vec::new();
+    let mut cursor: Option<EventCursor> = None;
+    while history.len() < MAX_REPLAY_EVENTS {
+        let page_size = REPLAY_PAGE_SIZE.min(MAX_REPLAY_EVENTS - history.len());
+        let (page, next) = match rocksdb
+            .get_events_page(since, u64::MAX, cursor.as_ref(), page_size)
+            .await
+        {
+            Ok(page) => page,
+            Err(e) => {
+                error!("Failed to load replay events since {}: {}", since, e);
+                break;
+            }
+        };
+        history.extend(page);
+        match next {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    history
+}
+
+/// Build the outgoing frames for an event given the client's active subscriptions.
+/// An event that fails to serialize is replaced by a `NOTICE` telling the client it was skipped
+/// and counted in `ws_serialize_errors_total`.
+fn event_frames(
+    event: &Event,
+    subscriptions: &Subscriptions,
+    protocol: WsProtocol,
+    metrics: &Metrics,
+) -> Vec<String> {
+    let raw = !subscriptions.requested && protocol == WsProtocol::Raw;
+    let matching: Vec<&String> = subscriptions
+        .open
+        .iter()
+        .filter(|(_, filters)| filters.iter().any(|f| f.matches(event)))
+        .map(|(subscription_id, _)| subscription_id)
+        .collect();
+    if !raw && matching.is_empty() {
+        return Vec::new();
+    }
+
+    let json = match serde_json::to_string(event) {
+        Ok(json) => json,
+        Err(e) => return vec![serialize_error_notice(event.id, &e, metrics)],
+    };
+    if raw {
+        return vec![json];
+    }
+    matching
+        .into_iter()
+        .map(|subscription_id| format!("[\"EVENT\",{},{}]", json!(subscription_id), json))
+        .collect()
+}
+
+/// Record an event that could not be serialized and build the `NOTICE` sent in its place
+fn serialize_error_notice(
+    event_id: EventId,
+    error: &serde_json::Error,
+    metrics: &Metrics,
+) -> String {
+    error!("Failed to serialize event {}: {}", event_id, error);
+    metrics.ws_serialize_errors.inc();
+    let notice = format!(
+        "error: skipped event {}, it could not be serialized",
+        event_id
+    );
+    json!(["NOTICE", notice]).to_string()
+}
+
+/// Fan out the downstream event stream to every connected WebSocket client
+fn spawn_broadcaster(event_rx: Receiver<RoutedEvent>) -> broadcast::Sender<RoutedEvent> {
+    let (event_tx, _) = broadcast::channel(BROADCAST_CAPACITY);
+    let broadcaster = event_tx.clone();
+    tokio::spawn(async move {
+        while let Ok(event) = event_rx.recv_async().await {
+            // An error only means no client is currently subscribed
+            let _ = broadcaster.send(event);
+        }
+        info!("WebSocket broadcaster: event channel closed");
+    });
+    event_tx
+}
+
 /// Create WebSocket router
-pub fn create_websocket_router(event_rx: Arc<Receiver<Event>>) -> Router {
+pub fn create_websocket_router(
+    event_rx: Receiver<RoutedEvent>,
+    heartbeat: Heartbeat,
+    limits: ClientLimits,
+    allowed_pubkeys: Option<Arc<HashSet<PublicKey>>>,
+    rocksdb: Arc<RocksDBStore>,
+    metrics: Arc<Metrics>,
+) -> Router {
+    let state = WsState {
+        event_tx: spawn_broadcaster(event_rx),
+        heartbeat,
+        limits,
+        allowed_pubkeys,
+        rocksdb,
+        metrics,
+    };
     Router::new()
         .route("/ws", get(websocket_handler))
-        .with_state(event_rx)
+        .with_state(state)
+}
+
+/// Create the `/ws/events/control` router that pushes relay status changes
+pub fn create_control_router(pool: Arc<RelayPool>, heartbeat: Heartbeat) -> Router {
+    Router::new()
+        .route("/ws/events/control", get(control_handler))
+        .with_state(ControlState { pool, heartbeat })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, WsClient, signed_event, temp_store};
+    use nostr_sdk::{EventBuilder, Keys, RelayUrl, Tag};
+    use tempfile::TempDir;
+
+    const HEARTBEAT: Heartbeat = Heartbeat {
+        ping_interval: Duration::from_secs(30),
+        idle_timeout: Duration::from_secs(60),
+    };
+    const LIMITS: ClientLimits = ClientLimits {
+        buffer: 1024,
+        slow_disconnect_after: None,
+        max_subscriptions: 20,
+    };
+    /// How long to wait before concluding that nothing more will be sent
+    const QUIET: Duration = Duration::from_millis(300);
+
+    /// A `/ws` server fed with the events passed to `publish`
+    struct TestServer {
+        events: flume::Sender<RoutedEvent>,
+        url: String,
+        store: Arc<RocksDBStore>,
+        _dir: TempDir,
+    }
+
+    impl TestServer {
+        async fn start() -> Self {
+            Self::with(HEARTBEAT, LIMITS, None).await
+        }
+
+        async fn with(
+            heartbeat: Heartbeat,
+            limits: ClientLimits,
+            allowed_pubkeys: Option<HashSet<PublicKey>>,
+        ) -> Self {
+            let (dir, store) = temp_store();
+            let (events, event_rx) = flume::unbounded();
+            let router = create_websocket_router(
+                event_rx,
+                heartbeat,
+                limits,
+                allowed_pubkeys.map(Arc::new),
+                store.clone(),
+                test_support::metrics(),
+            );
+            Self {
+                events,
+                url: test_support::serve(router).await,
+                store,
+                _dir: dir,
+            }
+        }
+
+        /// Open `/ws` with the given query string (e.g. `?protocol=nostr`)
+        async fn connect(&self, query: &str) -> WsClient {
+            WsClient::connect(&format!("{}/ws{}", self.url, query)).await
+        }
+
+        fn publish(&self, event: &Event) {
+            self.publish_received_at(event, Instant::now());
+        }
+
+        /// Publish as if a relay had delivered `event` at `received_at`
+        fn publish_received_at(&self, event: &Event, received_at: Instant) {
+            let routed = RoutedEvent {
+                event: event.clone(),
+                received_at,
+            };
+            self.events.send(routed).unwrap();
+        }
+    }
+
+    fn note(content: &str) -> Event {
+        signed_event(content, Timestamp::now())
+    }
+
+    fn raw(event: &Event) -> serde_json::Value {
+        serde_json::to_value(event).unwrap()
+    }
+
+    /// Notes large enough that a few hundred fill the socket buffers of a client not reading
+    fn large_notes(count: usize) -> Vec<Event> {
+        let padding = "x".repeat(64 * 1024);
+        (0..count)
+            .map(|i| note(&format!("{} {}", i, padding)))
+            .collect()
+    }
+
+    /// Every text frame sent until the server goes quiet or closes the connection
+    async fn read_all(client: &mut WsClient) -> Vec<serde_json::Value> {
+        let mut frames = Vec::new();
+        while let Some(frame) = client.try_recv(Duration::from_secs(1)).await {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    /// Count of events reported in a `dropped: N events` NOTICE
+    fn dropped_count(frame: &serde_json::Value) -> Option<u64> {
+        if frame[0] != "NOTICE" {
+            return None;
+        }
+        let notice = frame[1].as_str()?.strip_prefix("dropped: ")?;
+        notice.split(' ').next()?.parse().ok()
+    }
+
+    #[tokio::test]
+    async fn matching_events_are_framed_for_each_open_subscription() {
+        let server = TestServer::start().await;
+        let mut client = server.connect("").await;
+        let event = note("hello");
+
+        client.send(json!(["REQ", "all", {"kinds": [1]}])).await;
+        assert_eq!(client.recv().await, json!(["EOSE", "all"]));
+        let author = event.pubkey.to_hex();
+        client
+            .send(json!(["REQ", "mine", {"authors": [author]}]))
+            .await;
+        assert_eq!(client.recv().await, json!(["EOSE", "mine"]));
+        client.send(json!(["REQ", "other", {"kinds": [0]}])).await;
+        assert_eq!(client.recv().await, json!(["EOSE", "other"]));
+
+        server.publish(&event);
+        let mut frames = vec![client.recv().await, client.recv().await];
+        frames.sort_by_key(|frame| frame[1].to_string());
+        assert_eq!(
+            frames,
+            [
+                json!(["EVENT", "all", raw(&event)]),
+                json!(["EVENT", "mine", raw(&event)])
+            ]
+        );
+        assert_eq!(client.try_recv(QUIET).await, None);
+    }
+
+    #[tokio::test]
+    async fn latency_is_recorded_when_an_event_is_sent_to_a_client() {
+        let server = TestServer::start().await;
+        let mut client = server.connect("").await;
+        let latency = &test_support::metrics().end_to_end_latency;
+        let sum = latency.get_sample_sum();
+
+        let event = note("delivered late");
+        server.publish_received_at(&event, Instant::now() - Duration::from_secs(2));
+        assert_eq!(client.recv().await, raw(&event));
+
+        // Recorded right after the send; other tests share the histogram, so check a lower bound
+        let recorded = async {
+            while latency.get_sample_sum() - sum < 2.0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), recorded)
+            .await
+            .expect("latency was not recorded");
+    }
+
+    #[tokio::test]
+    async fn raw_streaming_stops_once_a_req_has_been_sent() {
+        let server = TestServer::start().await;
+        let mut client = server.connect("").await;
+
+        let before = note("before any REQ");
+        server.publish(&before);
+        assert_eq!(client.recv().await, raw(&before));
+
+        client.send(json!(["REQ", "sub1", {"kinds": [1]}])).await;
+        assert_eq!(client.recv().await, json!(["EOSE", "sub1"]));
+        client.send(json!(["CLOSE", "sub1"])).await;
+        // The CLOSE is not answered, so the next frame is the reply to this malformed message
+        client.send(json!({})).await;
+        assert_eq!(client.recv().await[0], "NOTICE");
+
+        server.publish(&note("after CLOSE"));
+        assert_eq!(client.try_recv(QUIET).await, None);
+    }
+
+    #[tokio::test]
+    async fn a_req_without_filters_is_closed() {
+        let server = TestServer::start().await;
+        let mut client = server.connect("").await;
+
+        client.send(json!(["REQ", "sub1"])).await;
+        assert_eq!(
+            client.recv().await,
+            json!(["CLOSED", "sub1", "invalid: a REQ needs at least one filter"])
+        );
+
+        server.publish(&note("not streamed"));
+        assert_eq!(client.try_recv(QUIET).await, None);
+    }
+
+    #[tokio::test]
+    async fn every_client_receives_every_event() {
+        let server = TestServer::start().await;
+        let mut clients = Vec::new();
+        for _ in 0..3 {
+            clients.push(server.connect("").await);
+        }
+
+        let events: Vec<Event> = test_support::signed_events(5);
+        for event in &events {
+            server.publish(event);
+        }
+        for client in &mut clients {
+            for event in &events {
+                assert_eq!(client.recv().await, raw(event));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn an_idle_client_is_disconnected() {
+        let heartbeat = Heartbeat {
+            ping_interval: Duration::from_secs(30),
+            idle_timeout: Duration::from_millis(200),
+        };
+        let server = TestServer::with(heartbeat, LIMITS, None).await;
+        let mut client = server.connect("").await;
+
+        let started = tokio::time::Instant::now();
+        client.closed().await;
+        assert!(started.elapsed() >= heartbeat.idle_timeout);
+    }
+
+    #[tokio::test]
+    async fn answering_pings_keeps_a_client_connected() {
+        let heartbeat = Heartbeat {
+            ping_interval: Duration::from_millis(50),
+            idle_timeout: Duration::from_millis(200),
+        };
+        let server = TestServer::with(heartbeat, LIMITS, None).await;
+        let mut client = server.connect("").await;
+
+        // Reading answers the server's pings; nothing else is sent for several idle timeouts
+        assert_eq!(client.try_recv(Duration::from_secs(1)).await, None);
+        client.send(json!(["REQ", "sub1", {"kinds": [1]}])).await;
+        assert_eq!(client.recv().await, json!(["EOSE", "sub1"]));
+    }
+
+    #[test]
+    fn a_full_buffer_drops_its_oldest_events() {
+        let buffer = ClientBuffer::new(2);
+        let events = test_support::signed_events(5);
+        for event in &events {
+            buffer.push(RoutedEvent {
+                event: event.clone(),
+                received_at: Instant::now(),
+            });
+        }
+        buffer.record_dropped(4);
+
+        let (queued, dropped) = buffer.drain();
+        let queued: Vec<Event> = queued.into_iter().map(|routed| routed.event).collect();
+        assert_eq!(queued, events[3..]);
+        assert_eq!(dropped, 7);
+        let (queued, dropped) = buffer.drain();
+        assert!(queued.is_empty());
+        assert_eq!(dropped, 0);
+    }
+
+    #[tokio::test]
+    async fn a_slow_reader_gets_the_newest_events_after_a_dropped_notice() {
+        let limits = ClientLimits {
+            buffer: 4,
+            ..LIMITS
+        };
+        let server = TestServer::with(HEARTBEAT, limits, None).await;
+        let mut client = server.connect("").await;
+
+        // The client reads nothing until every event has been published
+        let events = large_notes(400);
+        for event in &events {
+            server.publish(event);
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let frames = read_all(&mut client).await;
+
+        let dropped: u64 = frames.iter().filter_map(dropped_count).sum();
+        let received = frames.iter().filter(|frame| frame.is_object()).count();
+        assert!(dropped > 0);
+        assert_eq!(received as u64 + dropped, events.len() as u64);
+        // Only the newest events survive the stall, in order, after the last notice
+        let last_notice = frames
+            .iter()
+            .rposition(|frame| dropped_count(frame).is_some())
+            .unwrap();
+        let newest: Vec<serde_json::Value> = events[events.len() - 4..].iter().map(raw).collect();
+        assert_eq!(frames[last_notice + 1..], newest);
+    }
+
+    #[tokio::test]
+    async fn replay_sends_stored_history_then_live_events_once() {
+        let server = TestServer::start().await;
+        let stored: Vec<Event> = [1_000, 2_000, 3_000]
+            .into_iter()
+            .map(|at| signed_event("stored", Timestamp::from(at)))
+            .collect();
+        server.store.store_events(&stored).await.unwrap();
+        let mut client = server.connect("?replay_since=1500").await;
+
+        assert_eq!(client.recv().await, raw(&stored[1]));
+        assert_eq!(client.recv().await, raw(&stored[2]));
+        // A replayed event arriving on the live feed is not sent again
+        let live = note("live");
+        server.publish(&stored[2]);
+        server.publish(&live);
+        assert_eq!(client.recv().await, raw(&live));
+        assert_eq!(client.try_recv(QUIET).await, None);
+    }
+
+    #[tokio::test]
+    async fn count_is_exact_for_filters_and_approximate_without_conditions() {
+        let server = TestServer::start().await;
+        let mentioned = Keys::generate().public_key();
+        let mention = EventBuilder::text_note("mention")
+            .tag(Tag::public_key(mentioned))
+            .sign_with_keys(&Keys::generate())
+            .unwrap();
+        let metadata = EventBuilder::metadata(&Default::default())
+            .sign_with_keys(&Keys::generate())
+            .unwrap();
+        let mut stored = test_support::signed_events(3);
+        stored.extend([mention, metadata]);
+        server.store.store_events(&stored).await.unwrap();
+        let mut client = server.connect("?protocol=nostr").await;
+
+        client.send(json!(["COUNT", "notes", {"kinds": [1]}])).await;
+        assert_eq!(client.recv().await, json!(["COUNT", "notes", {"count": 4}]));
+        client
+            .send(json!(["COUNT", "tagged", {"#p": [mentioned.to_hex()]}]))
+            .await;
+        assert_eq!(
+            client.recv().await,
+            json!(["COUNT", "tagged", {"count": 1}])
+        );
+        client
+            .send(json!(["COUNT", "either", {"kinds": [0]}, {"#p": [mentioned.to_hex()]}]))
+            .await;
+        assert_eq!(
+            client.recv().await,
+            json!(["COUNT", "either", {"count": 2}])
+        );
+
+        client.send(json!(["COUNT", "all", {}])).await;
+        let reply = client.recv().await;
+        assert_eq!(
+            reply.as_array().unwrap()[..2],
+            [json!("COUNT"), json!("all")]
+        );
+        assert_eq!(reply[2]["approximate"], true);
+        assert!(reply[2]["count"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn a_stalled_reader_is_closed_once_too_many_events_are_dropped() {
+        let limits = ClientLimits {
+            buffer: 4,
+            slow_disconnect_after: Some(10),
+            ..LIMITS
+        };
+        let server = TestServer::with(HEARTBEAT, limits, None).await;
+        let metrics = test_support::metrics();
+        let disconnects = metrics.ws_slow_client_disconnects.get();
+        let mut client = server.connect("").await;
+
+        for event in &large_notes(400) {
+            server.publish(event);
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        let close = client.closed().await.expect("no close frame");
+        assert_eq!(u16::from(close.code), close_code::POLICY);
+        assert!(close.reason.starts_with("slow consumer: dropped "));
+        assert!(metrics.ws_slow_client_disconnects.get() > disconnects);
+    }
+
+    #[tokio::test]
+    async fn events_dropped_during_replay_do_not_disconnect_the_client() {
+        let limits = ClientLimits {
+            buffer: 4,
+            slow_disconnect_after: Some(10),
+            ..LIMITS
+        };
+        let server = TestServer::with(HEARTBEAT, limits, None).await;
+        let mut history = large_notes(400);
+        server.store.store_events(&history).await.unwrap();
+        // Replay order: oldest first, ties broken by id
+        history.sort_by_key(|event| (event.created_at, event.id));
+        let mut client = server.connect("?replay_since=0").await;
+
+        // The replay cannot finish while the client is not reading, so all of these arrive
+        // during it and all but the last four are dropped
+        let live = test_support::signed_events(20);
+        for event in &live {
+            server.publish(event);
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let frames = read_all(&mut client).await;
+
+        let mut expected: Vec<serde_json::Value> = history.iter().map(raw).collect();
+        expected.push(json!(["NOTICE", "dropped: 16 events during replay"]));
+        expected.extend(live[16..].iter().map(raw));
+        assert_eq!(frames.len(), expected.len());
+        assert!(frames == expected, "unexpected frames after the replay");
+        client.send(json!(["REQ", "sub1", {"kinds": [1]}])).await;
+        assert_eq!(client.recv().await, json!(["EOSE", "sub1"]));
+    }
+
+    #[tokio::test]
+    async fn a_req_beyond_the_subscription_limit_is_closed() {
+        let limits = ClientLimits {
+            max_subscriptions: 2,
+            ..LIMITS
+        };
+        let server = TestServer::with(HEARTBEAT, limits, None).await;
+        let mut client = server.connect("").await;
+
+        for id in ["a", "b"] {
+            client.send(json!(["REQ", id, {"kinds": [1]}])).await;
+            assert_eq!(client.recv().await, json!(["EOSE", id]));
+        }
+        client.send(json!(["REQ", "c", {"kinds": [1]}])).await;
+        assert_eq!(
+            client.recv().await,
+            json!([
+                "CLOSED",
+                "c",
+                "blocked: too many subscriptions, at most 2 may be open"
+            ])
+        );
+        // Replacing the filters of an open subscription needs no new slot
+        client.send(json!(["REQ", "a", {"kinds": [1]}])).await;
+        assert_eq!(client.recv().await, json!(["EOSE", "a"]));
+
+        // The refusal left the open subscriptions in place
+        let event = note("still delivered");
+        server.publish(&event);
+        let mut ids = vec![
+            client.recv().await[1].clone(),
+            client.recv().await[1].clone(),
+        ];
+        ids.sort_by_key(|id| id.to_string());
+        assert_eq!(ids, [json!("a"), json!("b")]);
+
+        // Closing one frees its slot
+        client.send(json!(["CLOSE", "b"])).await;
+        client.send(json!(["REQ", "c", {"kinds": [1]}])).await;
+        assert_eq!(client.recv().await, json!(["EOSE", "c"]));
+    }
+
+    /// A NIP-42 answer to `challenge` signed by `keys`
+    fn auth_event(keys: &Keys, challenge: &str, created_at: Timestamp) -> Event {
+        let relay = RelayUrl::parse("ws://localhost").unwrap();
+        EventBuilder::auth(challenge, relay)
+            .custom_created_at(created_at)
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn auth_rejects_bad_answers_and_streams_once_accepted() {
+        let keys = Keys::generate();
+        let allowed = HashSet::from([keys.public_key()]);
+        let server = TestServer::with(HEARTBEAT, LIMITS, Some(allowed)).await;
+        let mut client = server.connect("").await;
+        let challenge = client.recv().await;
+        assert_eq!(challenge[0], "AUTH");
+        let challenge = challenge[1].as_str().unwrap().to_string();
+
+        client.send(json!(["REQ", "sub1", {"kinds": [1]}])).await;
+        assert_eq!(
+            client.recv().await,
+            json!([
+                "CLOSED",
+                "sub1",
+                "auth-required: authenticate before subscribing"
+            ])
+        );
+        server.publish(&note("before auth"));
+
+        let an_hour_ago = Timestamp::from(Timestamp::now().as_secs() - 3600);
+        let rejected = [
+            (
+                note("not an auth event"),
+                "invalid: AUTH requires a kind 22242 event",
+            ),
+            (
+                auth_event(&keys, "another challenge", Timestamp::now()),
+                "invalid: challenge does not match",
+            ),
+            (
+                auth_event(&keys, &challenge, an_hour_ago),
+                "invalid: created_at is too far from the current time",
+            ),
+            (
+                auth_event(&Keys::generate(), &challenge, Timestamp::now()),
+                "restricted: pubkey is not allowed to stream events",
+            ),
+        ];
+        for (event, reason) in rejected {
+            client.send(json!(["AUTH", &event])).await;
+            assert_eq!(client.recv().await, json!(["OK", event.id, false, reason]));
+        }
+
+        let accepted = auth_event(&keys, &challenge, Timestamp::now());
+        client.send(json!(["AUTH", &accepted])).await;
+        assert_eq!(client.recv().await, json!(["OK", accepted.id, true, ""]));
+        // Events published before authentication are not delivered, later ones are
+        let event = note("after auth");
+        server.publish(&event);
+        assert_eq!(client.recv().await, raw(&event));
+    }
+
+    #[tokio::test]
+    async fn nostr_framing_streams_nothing_outside_subscriptions() {
+        let server = TestServer::start().await;
+        server.store.store_event(&note("stored")).await.unwrap();
+        let mut raw_client = server.connect("?replay_since=0&protocol=raw").await;
+        let mut nostr_client = server.connect("?replay_since=0&protocol=nostr").await;
+        assert_eq!(raw_client.recv().await["content"], "stored");
+
+        let unsubscribed = note("before REQ");
+        server.publish(&unsubscribed);
+        assert_eq!(raw_client.recv().await, raw(&unsubscribed));
+        assert_eq!(nostr_client.try_recv(QUIET).await, None);
+
+        nostr_client
+            .send(json!(["REQ", "sub1", {"kinds": [1]}]))
+            .await;
+        assert_eq!(nostr_client.recv().await, json!(["EOSE", "sub1"]));
+        let event = note("after REQ");
+        server.publish(&event);
+        assert_eq!(
+            nostr_client.recv().await,
+            json!(["EVENT", "sub1", raw(&event)])
+        );
+        assert_eq!(raw_client.recv().await, raw(&event));
+    }
+
+    #[tokio::test]
+    async fn an_unknown_protocol_is_refused() {
+        let server = TestServer::start().await;
+        let url = format!("{}/ws?protocol=ndjson", server.url).replacen("http://", "ws://", 1);
+        match tokio_tungstenite::connect_async(url).await {
+            Err(tokio_tungstenite::tungstenite::Error::Http(response)) => {
+                assert_eq!(response.status(), 400);
+            }
+            other => panic!("expected a 400 response, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn an_unserializable_event_is_replaced_by_a_notice() {
+        let metrics = test_support::metrics();
+        let errors = metrics.ws_serialize_errors.get();
+        let event = note("unserializable");
+        let error = serde_json::from_str::<Event>("{").unwrap_err();
+
+        let notice: serde_json::Value =
+            serde_json::from_str(&serialize_error_notice(event.id, &error, &metrics)).unwrap();
+        let expected = format!(
+            "error: skipped event {}, it could not be serialized",
+            event.id
+        );
+        assert_eq!(notice, json!(["NOTICE", expected]));
+        assert!(metrics.ws_serialize_errors.get() > errors);
+    }
 }
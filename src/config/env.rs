@@ -0,0 +1,226 @@
+//! Environment variable overrides of config values
+
+use anyhow::{Context, Result};
+use serde::Deserializer;
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{DeserializeOwned, IntoDeserializer, Visitor};
+
+/// Prefix for environment variables overriding config values.
+/// `ISO_RELAYER__RELAY__MAX_CONNECTIONS=500` sets `relay.max_connections`.
+const ENV_PREFIX: &str = "ISO_RELAYER__";
+
+/// Overlay `ISO_RELAYER__SECTION__KEY` environment variables onto the parsed TOML table.
+/// Values are inserted as strings; [`deserialize`] converts them to the type of the setting.
+pub(super) fn apply_env_overrides(
+    table: &mut toml::Table,
+    vars: impl Iterator<Item = (String, String)>,
+) -> Result<()> {
+    for (name, raw) in vars {
+        let Some(path) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let keys: Vec<String> = path.split("__").map(|k| k.to_ascii_lowercase()).collect();
+        if keys.iter().any(|k| k.is_empty()) {
+            anyhow::bail!("Malformed config override variable {}", name);
+        }
+
+        let (last, parents) = keys.split_last().expect("split yields at least one key");
+        let mut current = &mut *table;
+        for key in parents {
+            let entry = current
+                .entry(key.clone())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            current = entry.as_table_mut().with_context(|| {
+                format!("Config override {} targets non-table key {}", name, key)
+            })?;
+        }
+        current.insert(last.clone(), toml::Value::String(raw));
+    }
+    Ok(())
+}
+
+/// Deserialize a config table, reading string values as TOML literals (numbers, booleans,
+/// arrays) wherever the target field is not a string
+pub(super) fn deserialize<T: DeserializeOwned>(table: toml::Table) -> Result<T, toml::de::Error> {
+    T::deserialize(Coerce(toml::Value::Table(table)))
+}
+
+/// Interpret a value as a TOML literal, if it is one
+fn parse_literal(raw: &str) -> Option<toml::Value> {
+    toml::from_str::<toml::Table>(&format!("value = {}", raw))
+        .ok()?
+        .remove("value")
+}
+
+/// A TOML value whose strings are converted to the type the deserialized field asks for, so
+/// an override of a string setting is kept as written even when it looks like a number
+struct Coerce(toml::Value);
+
+impl Coerce {
+    /// Read a string as a TOML literal, leaving it a string when it is not one
+    fn literal(self) -> Self {
+        match self.0 {
+            toml::Value::String(raw) => {
+                Coerce(parse_literal(&raw).unwrap_or(toml::Value::String(raw)))
+            }
+            other => Coerce(other),
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, toml::de::Error> for Coerce {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Forward the listed methods to `deserialize_any` after reading a string as a TOML literal
+macro_rules! deserialize_literal {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                self.literal().deserialize_any(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Coerce {
+    type Error = toml::de::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            toml::Value::Table(table) => {
+                let entries = table.into_iter().map(|(key, value)| (key, Coerce(value)));
+                let mut map = MapDeserializer::<_, toml::de::Error>::new(entries);
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            toml::Value::Array(items) => {
+                let items = items.into_iter().map(Coerce);
+                let mut seq = SeqDeserializer::<_, toml::de::Error>::new(items);
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        // TOML has no null: a present value is always `Some`
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.literal().deserialize_any(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0.deserialize_enum(name, variants, visitor)
+    }
+
+    deserialize_literal! {
+        deserialize_bool
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+        deserialize_f32 deserialize_f64
+        deserialize_seq deserialize_map
+    }
+
+    serde::forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct tuple tuple_struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+
+    /// The config template with the given `ISO_RELAYER__` overrides applied
+    fn overridden(vars: &[(&str, &str)]) -> Result<AppConfig> {
+        let mut table: toml::Table = toml::from_str(include_str!("../../config.template.toml"))?;
+        let vars = vars
+            .iter()
+            .map(|(name, value)| (format!("{}{}", ENV_PREFIX, name), value.to_string()));
+        apply_env_overrides(&mut table, vars)?;
+        Ok(deserialize(table)?)
+    }
+
+    #[test]
+    fn overrides_take_the_type_of_the_setting() {
+        let cfg = overridden(&[
+            ("RELAY__MAX_CONNECTIONS", "500"),
+            ("relay__persist_relays", "true"),
+            ("RELAY__RECONNECT_POLICY__MAX_RETRIES", "3"),
+            (
+                "RELAY__BOOTSTRAP_RELAYS",
+                r#"["wss://a.example", "wss://b.example"]"#,
+            ),
+            ("MONITORING__LOG_LEVEL", "debug"),
+        ])
+        .unwrap();
+
+        assert_eq!(cfg.relay.max_connections, 500);
+        assert!(cfg.relay.persist_relays);
+        assert_eq!(cfg.relay.reconnect_policy.max_retries, 3);
+        assert_eq!(
+            cfg.relay.bootstrap_relays,
+            ["wss://a.example", "wss://b.example"]
+        );
+        assert_eq!(cfg.monitoring.log_level.as_deref(), Some("debug"));
+    }
+
+    #[test]
+    fn numeric_looking_strings_are_kept_as_written() {
+        for api_key in ["12345", "0012", "1e5", "0x1F", "true", "2024-01-01"] {
+            let cfg = overridden(&[("SECURITY__API_KEY", api_key)]).unwrap();
+            assert_eq!(cfg.security.api_key.as_deref(), Some(api_key));
+        }
+    }
+
+    #[test]
+    fn overrides_that_do_not_fit_the_setting_are_rejected() {
+        assert!(overridden(&[("RELAY__MAX_CONNECTIONS", "lots")]).is_err());
+        assert!(overridden(&[("RELAY__BOOTSTRAP_RELAYS", "wss://a.example")]).is_err());
+
+        let malformed = overridden(&[("RELAY____MAX_CONNECTIONS", "1")]).unwrap_err();
+        assert!(malformed.to_string().contains("Malformed config override"));
+        let nested = overridden(&[("RELAY__MAX_CONNECTIONS__LIMIT", "1")]).unwrap_err();
+        assert!(
+            nested
+                .to_string()
+                .contains("targets non-table key max_connections")
+        );
+    }
+
+    #[test]
+    fn variables_without_the_prefix_are_ignored() {
+        let mut table = toml::Table::new();
+        let vars = [("RELAY__MAX_CONNECTIONS".to_string(), "1".to_string())];
+        apply_env_overrides(&mut table, vars.into_iter()).unwrap();
+        assert!(table.is_empty());
+    }
+}
@@ -1,58 +1,1271 @@
-use anyhow::{Context, Result};
-use serde::Deserialize;
+use anyhow::{Context, Result, ensure};
+use nostr_sdk::{Filter, Keys, Kind, PublicKey, Timestamp};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-#[derive(Debug, Clone, Deserialize)]
+use crate::core::relay_pool::lookup_key;
+
+mod env;
+
+/// Placeholder shown instead of secrets in `AppConfig::redacted`
+const REDACTED: &str = "***";
+
+/// Largest accepted `ingest.max_age_secs` (10 years); anything larger is likely a typo
+const MAX_INGEST_AGE_SECS: u64 = 10 * 365 * 24 * 60 * 60;
+
+/// Largest accepted `ingest.max_future_skew_secs` (1 year)
+const MAX_INGEST_FUTURE_SKEW_SECS: u64 = 365 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RelayConfig {
     pub bootstrap_relays: Vec<String>,
     pub max_connections: usize,
     pub health_check_interval: u64,
+    /// Give up on a relay that has not connected and subscribed within this many milliseconds
+    #[serde(default = "default_connect_timeout_ms")]
+    pub connect_timeout_ms: u64,
+    #[serde(default)]
+    pub reconnect_policy: ReconnectPolicy,
+    #[serde(default)]
+    pub health: HealthPolicy,
+    /// NIP-42 secret keys (hex or nsec) keyed by relay URL
+    #[serde(default)]
+    pub auth: HashMap<String, String>,
+    /// Filter sent in the REQ to every relay; empty subscribes to everything
+    #[serde(default)]
+    pub subscription: SubscriptionConfig,
+    /// Remember relays added at runtime and reconnect them on startup
+    #[serde(default)]
+    pub persist_relays: bool,
+    /// How long a relay's NIP-11 information document is cached (0 disables fetching)
+    #[serde(default = "default_nip11_ttl_secs")]
+    pub nip11_ttl_secs: u64,
+    /// Also request the last this-many seconds of stored events when a relay connects
+    #[serde(default)]
+    pub initial_backfill_secs: Option<u64>,
+    /// Events per second accepted from any single relay; excess events are dropped
+    /// (unlimited when unset)
+    #[serde(default)]
+    pub max_events_per_sec: Option<u32>,
+    /// Per-relay overrides of `max_events_per_sec`, keyed by relay URL
+    #[serde(default)]
+    pub relay_max_events_per_sec: HashMap<String, u32>,
+    /// Priority of relays, keyed by relay URL, when crediting an event to the relay that
+    /// delivered it first; unlisted relays have priority 0
+    #[serde(default)]
+    pub relay_priority: HashMap<String, i32>,
+}
+
+impl RelayConfig {
+    /// NIP-42 signing keys by relay URL, parsed from `auth`
+    pub fn auth_keys(&self) -> Result<HashMap<String, Keys>> {
+        self.auth
+            .iter()
+            .map(|(url, secret)| {
+                Keys::parse(secret)
+                    .map(|keys| (url.clone(), keys))
+                    .with_context(|| format!("Invalid NIP-42 auth key for relay {}", url))
+            })
+            .collect()
+    }
+}
+
+fn default_connect_timeout_ms() -> u64 {
+    10_000
+}
+
+fn default_nip11_ttl_secs() -> u64 {
+    3600
+}
+
+/// Relay-side subscription filter so relays only send matching events
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SubscriptionConfig {
+    #[serde(default)]
+    pub kinds: Vec<u16>,
+    /// Author public keys (hex or npub)
+    #[serde(default)]
+    pub authors: Vec<String>,
+    /// Only request events created at or after this unix timestamp
+    #[serde(default)]
+    pub since: Option<u64>,
+}
+
+impl SubscriptionConfig {
+    /// The REQ filter sent to every relay
+    pub fn filter(&self) -> Result<Filter> {
+        let mut filter = Filter::new();
+        if !self.kinds.is_empty() {
+            filter = filter.kinds(self.kinds.iter().copied().map(Kind::from));
+        }
+        if !self.authors.is_empty() {
+            let authors: Vec<_> = parse_pubkeys(&self.authors, "relay.subscription.authors")?;
+            filter = filter.authors(authors);
+        }
+        if let Some(since) = self.since {
+            filter = filter.since(Timestamp::from(since));
+        }
+        Ok(filter)
+    }
+}
+
+/// Relay health scoring and automatic eviction settings
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HealthPolicy {
+    /// Remove relays whose score stays below the threshold
+    #[serde(default)]
+    pub auto_evict: bool,
+    /// Score in `[0.0, 1.0]` below which a check counts as unhealthy
+    #[serde(default = "default_eviction_threshold")]
+    pub eviction_threshold: f64,
+    /// Consecutive unhealthy checks before a relay is evicted
+    #[serde(default = "default_consecutive_checks")]
+    pub consecutive_checks: u32,
+    /// Event rate at which a relay earns the full throughput component of its score
+    #[serde(default = "default_target_events_per_minute")]
+    pub target_events_per_minute: f64,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        Self {
+            auto_evict: false,
+            eviction_threshold: default_eviction_threshold(),
+            consecutive_checks: default_consecutive_checks(),
+            target_events_per_minute: default_target_events_per_minute(),
+        }
+    }
+}
+
+fn default_eviction_threshold() -> f64 {
+    0.3
+}
+
+fn default_consecutive_checks() -> u32 {
+    3
+}
+
+fn default_target_events_per_minute() -> f64 {
+    1.0
+}
+
+/// Exponential backoff settings for reconnecting dropped relays
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReconnectPolicy {
+    #[serde(default = "default_reconnect_base_delay_ms")]
+    pub base_delay_ms: u64,
+    #[serde(default = "default_reconnect_max_delay_ms")]
+    pub max_delay_ms: u64,
+    #[serde(default = "default_reconnect_max_retries")]
+    pub max_retries: u32,
+}
+
+impl ReconnectPolicy {
+    /// Backoff delay before the given (zero-based) reconnect attempt, without jitter
+    pub fn backoff_delay(&self, attempt: u32) -> Duration {
+        let delay_ms = self
+            .base_delay_ms
+            .saturating_mul(1u64 << attempt.min(32))
+            .min(self.max_delay_ms);
+        Duration::from_millis(delay_ms)
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            base_delay_ms: default_reconnect_base_delay_ms(),
+            max_delay_ms: default_reconnect_max_delay_ms(),
+            max_retries: default_reconnect_max_retries(),
+        }
+    }
+}
+
+fn default_reconnect_base_delay_ms() -> u64 {
+    1000
+}
+
+fn default_reconnect_max_delay_ms() -> u64 {
+    60_000
+}
+
+fn default_reconnect_max_retries() -> u32 {
+    10
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct DeduplicationConfig {
     pub hotset_size: usize,
     pub bloom_capacity: usize,
+    /// Target bloom filter false-positive probability; a false positive drops a fresh event
+    #[serde(default = "default_bloom_fp_rate")]
+    pub bloom_fp_rate: f64,
     pub lru_size: usize,
     pub rocksdb_path: String,
+    /// Events older than this many seconds are pruned from RocksDB (disabled when unset)
+    #[serde(default)]
+    pub retention_secs: Option<u64>,
+    /// Per-kind retention overrides in seconds, keyed by event kind (e.g. `"7" = 86400`)
+    #[serde(default)]
+    pub retention: HashMap<String, u64>,
+    /// Interval between pruning runs in seconds
+    #[serde(default = "default_prune_interval_secs")]
+    pub prune_interval_secs: u64,
+    /// Save the bloom filter next to the RocksDB path this often and reload it on startup
+    /// (disabled when unset)
+    #[serde(default)]
+    pub bloom_persist_interval_secs: Option<u64>,
+    /// Which key identifies duplicate events
+    #[serde(default)]
+    pub dedup_key: DedupKey,
+    /// Only filter copies of an event seen within this many seconds; later ones pass as new
+    /// (dedup forever when unset)
+    #[serde(default)]
+    pub dedup_window_secs: Option<u64>,
+    /// RocksDB tuning; unset fields keep the built-in defaults
+    #[serde(default)]
+    pub rocksdb: RocksDbTuning,
+    /// Events whose serialized JSON exceeds this many bytes are rejected on ingest
+    #[serde(default = "default_max_event_bytes")]
+    pub max_event_bytes: usize,
+    /// When repeated RocksDB errors degrade deduplication to the in-memory layers
+    #[serde(default)]
+    pub rocksdb_circuit_breaker: CircuitBreakerConfig,
+    /// Events per second admitted during the startup warmup (unthrottled when unset)
+    #[serde(default)]
+    pub startup_ingest_rate: Option<u32>,
+    /// How long the startup ingest rate applies, in seconds
+    #[serde(default = "default_startup_warmup_secs")]
+    pub startup_warmup_secs: u64,
+    /// Pubkeys (hex or npub) whose events are never filtered as duplicates (still stored)
+    #[serde(default)]
+    pub dedup_bypass_pubkeys: Vec<String>,
+    /// Milliseconds after an event is first seen during which a copy from a higher-priority
+    /// relay takes over its attribution
+    #[serde(default = "default_attribution_window_ms")]
+    pub attribution_window_ms: u64,
+}
+
+impl DeduplicationConfig {
+    /// Where the bloom filter snapshot is kept, alongside the RocksDB directory
+    pub fn bloom_snapshot_path(&self) -> PathBuf {
+        PathBuf::from(format!("{}.bloom", self.rocksdb_path.trim_end_matches('/')))
+    }
+
+    /// Authors whose events skip the duplicate check, parsed from `dedup_bypass_pubkeys`
+    pub fn bypass_pubkeys(&self) -> Result<HashSet<PublicKey>> {
+        parse_pubkeys(
+            &self.dedup_bypass_pubkeys,
+            "deduplication.dedup_bypass_pubkeys",
+        )
+    }
+
+    /// Retention applied by the pruner; kinds that fail to parse are rejected by `validate`
+    pub fn retention_policy(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            default_secs: self.retention_secs,
+            per_kind: self
+                .retention
+                .iter()
+                .filter_map(|(kind, secs)| Some((kind.parse().ok()?, *secs)))
+                .collect(),
+        }
+    }
+}
+
+/// How long stored events are kept before pruning, optionally per event kind
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    /// Retention in seconds for kinds without an override (kept forever when unset)
+    pub default_secs: Option<u64>,
+    /// Retention in seconds by event kind
+    pub per_kind: HashMap<u16, u64>,
+}
+
+impl RetentionPolicy {
+    /// Retention for events of `kind`, `None` when they are never pruned
+    pub fn retention_for(&self, kind: u16) -> Option<u64> {
+        self.per_kind.get(&kind).copied().or(self.default_secs)
+    }
+
+    /// The shortest retention of any kind; events younger than this are never expired
+    pub fn shortest(&self) -> Option<u64> {
+        self.per_kind
+            .values()
+            .copied()
+            .chain(self.default_secs)
+            .min()
+    }
+}
+
+fn default_attribution_window_ms() -> u64 {
+    100
+}
+
+fn default_startup_warmup_secs() -> u64 {
+    60
+}
+
+fn default_max_event_bytes() -> usize {
+    512 * 1024
+}
+
+/// RocksDB options exposed for tuning to the host hardware
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RocksDbTuning {
+    #[serde(default = "default_write_buffer_mb")]
+    pub write_buffer_mb: usize,
+    #[serde(default = "default_max_write_buffers")]
+    pub max_write_buffers: i32,
+    #[serde(default)]
+    pub compression: RocksDbCompression,
+    /// Shared block cache size; RocksDB's built-in default is used when unset
+    #[serde(default)]
+    pub block_cache_mb: Option<usize>,
+    /// Re-verify id and signature when events are read back (REST lookups, replay)
+    #[serde(default)]
+    pub verify_on_read: bool,
+    /// Extra attempts to open the database when it is locked or the volume is not ready yet
+    #[serde(default = "default_open_retries")]
+    pub open_retries: u32,
+    /// Delay before the first open retry, doubled for each further attempt
+    #[serde(default = "default_open_retry_delay_ms")]
+    pub open_retry_delay_ms: u64,
+}
+
+impl Default for RocksDbTuning {
+    fn default() -> Self {
+        Self {
+            write_buffer_mb: default_write_buffer_mb(),
+            max_write_buffers: default_max_write_buffers(),
+            compression: RocksDbCompression::default(),
+            block_cache_mb: None,
+            verify_on_read: false,
+            open_retries: default_open_retries(),
+            open_retry_delay_ms: default_open_retry_delay_ms(),
+        }
+    }
+}
+
+fn default_write_buffer_mb() -> usize {
+    64
 }
 
-#[derive(Debug, Clone, Deserialize)]
+fn default_max_write_buffers() -> i32 {
+    3
+}
+
+fn default_open_retries() -> u32 {
+    5
+}
+
+fn default_open_retry_delay_ms() -> u64 {
+    500
+}
+
+/// Compression algorithms supported by the bundled RocksDB
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RocksDbCompression {
+    None,
+    Snappy,
+    Zlib,
+    Bz2,
+    #[default]
+    Lz4,
+    Lz4hc,
+    Zstd,
+}
+
+/// Key used by the deduplication engine to decide whether two events are the same
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupKey {
+    /// The event id as published
+    #[default]
+    Id,
+    /// Hash of the canonical `(pubkey, kind, created_at, content, tags)` tuple with tags sorted,
+    /// recomputed locally so re-encoded or tag-reordered copies collapse together
+    ContentHash,
+}
+
+fn default_bloom_fp_rate() -> f64 {
+    0.01
+}
+
+fn default_prune_interval_secs() -> u64 {
+    3600
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    30
+}
+
+fn default_ws_ping_interval_secs() -> u64 {
+    30
+}
+
+fn default_ws_idle_timeout_secs() -> u64 {
+    90
+}
+
+fn default_max_subscriptions_per_client() -> usize {
+    20
+}
+
+fn default_ws_client_buffer() -> usize {
+    1024
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OutputConfig {
     pub websocket_enabled: bool,
     pub websocket_port: u16,
     #[serde(default)]
-    pub downstream_tcp: Vec<String>,
+    pub downstream_tcp: Vec<SinkEndpoint>,
+    #[serde(default)]
+    pub downstream_rest: Vec<SinkEndpoint>,
+    /// Local JSONL file every forwarded event is appended to
     #[serde(default)]
-    pub downstream_rest: Vec<String>,
+    pub downstream_file: Option<FileSinkConfig>,
+    /// Kafka topic to publish events to (requires the `kafka` cargo feature)
+    #[serde(default)]
+    pub downstream_kafka: Option<KafkaConfig>,
     pub batch_size: usize,
     pub max_latency_ms: u64,
+    /// Hold events this many milliseconds and emit them sorted by `created_at` (best-effort)
+    #[serde(default)]
+    pub reorder_window_ms: Option<u64>,
+    /// Grow batches up to this size under load and flush early when input pauses
+    /// (fixed `batch_size` batches when unset)
+    #[serde(default)]
+    pub adaptive_batch_max: Option<usize>,
+    /// Capacity of the queue between the event router and the outputs (unbounded when unset)
+    #[serde(default)]
+    pub queue_capacity: Option<usize>,
+    /// What to do when the output queue is full
+    #[serde(default)]
+    pub overflow_policy: OverflowPolicy,
+    /// Time the router, outputs and HTTP server get in total to finish after a shutdown
+    /// signal, in seconds, before they are aborted
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+    /// Interval between server-initiated WebSocket pings in seconds
+    #[serde(default = "default_ws_ping_interval_secs")]
+    pub ws_ping_interval_secs: u64,
+    /// Close WebSocket clients that send nothing (not even a pong) for this many seconds
+    #[serde(default = "default_ws_idle_timeout_secs")]
+    pub ws_idle_timeout_secs: u64,
+    /// Events queued per WebSocket client before the oldest are dropped
+    #[serde(default = "default_ws_client_buffer")]
+    pub ws_client_buffer: usize,
+    /// Disconnect a WebSocket client once this many events have been dropped for it
+    /// (never when unset)
+    #[serde(default)]
+    pub slow_client_disconnect_after: Option<u64>,
+    /// Open `REQ` subscriptions a single WebSocket client may hold
+    #[serde(default = "default_max_subscriptions_per_client")]
+    pub max_subscriptions_per_client: usize,
+    /// Require NIP-42 AUTH from WebSocket clients before streaming (open when unset)
+    #[serde(default)]
+    pub auth: Option<WsAuthConfig>,
+    /// What to do with a batch when a downstream sink keeps failing
+    #[serde(default)]
+    pub sink_failure_policy: SinkFailurePolicy,
+    /// Backoff used when reconnecting or retrying downstream sinks
+    #[serde(default)]
+    pub sink_retry_policy: ReconnectPolicy,
+    /// Fast-fail settings for sinks that keep failing
+    #[serde(default)]
+    pub sink_circuit_breaker: CircuitBreakerConfig,
+    /// Keep-alive connection pool of the HTTP client shared by the REST sinks
+    #[serde(default)]
+    pub rest_pool: RestPoolConfig,
+    /// Serve the REST API, metrics and WebSocket over HTTPS/WSS (plain HTTP when unset)
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+}
+
+/// NIP-42 authentication of WebSocket output clients
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct WsAuthConfig {
+    /// Public keys (hex or npub) whose AUTH events are accepted
+    #[serde(default)]
+    pub allowed_pubkeys: Vec<String>,
+}
+
+impl WsAuthConfig {
+    /// Parsed `allowed_pubkeys`
+    pub fn pubkeys(&self) -> Result<HashSet<PublicKey>> {
+        parse_pubkeys(&self.allowed_pubkeys, "output.auth.allowed_pubkeys")
+    }
+}
+
+/// Parse hex or npub public keys, naming the offending `field` entry on failure
+fn parse_pubkeys<C: FromIterator<PublicKey>>(keys: &[String], field: &str) -> Result<C> {
+    keys.iter()
+        .map(|key| {
+            PublicKey::parse(key).with_context(|| format!("Invalid {} entry {}", field, key))
+        })
+        .collect()
+}
+
+/// PEM certificate chain and private key used to terminate TLS
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+/// Keep-alive connection pool of the HTTP client shared by the REST sinks
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RestPoolConfig {
+    /// Idle keep-alive connections kept open per downstream host
+    #[serde(default = "default_rest_pool_max_idle_per_host")]
+    pub max_idle_per_host: usize,
+    /// Close pooled connections that have been idle this many seconds
+    #[serde(default = "default_rest_pool_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+}
+
+impl Default for RestPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_idle_per_host: default_rest_pool_max_idle_per_host(),
+            idle_timeout_secs: default_rest_pool_idle_timeout_secs(),
+        }
+    }
+}
+
+fn default_rest_pool_max_idle_per_host() -> usize {
+    16
+}
+
+fn default_rest_pool_idle_timeout_secs() -> u64 {
+    90
 }
 
-#[derive(Debug, Clone, Deserialize)]
+/// Circuit breaker wrapped around each downstream sink
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failed delivery attempts that open the circuit
+    #[serde(default = "default_breaker_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Seconds the circuit stays open before a probe delivery is allowed
+    #[serde(default = "default_breaker_cooldown_secs")]
+    pub cooldown_secs: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: default_breaker_failure_threshold(),
+            cooldown_secs: default_breaker_cooldown_secs(),
+        }
+    }
+}
+
+fn default_breaker_failure_threshold() -> u32 {
+    5
+}
+
+fn default_breaker_cooldown_secs() -> u64 {
+    30
+}
+
+/// A TCP or REST downstream endpoint, written either as a bare address or as a table
+/// `{ url = "...", sample_rate = 0.01 }`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum SinkEndpoint {
+    Address(String),
+    Detailed {
+        url: String,
+        /// Fraction of events forwarded to this sink, chosen by event id (all when unset)
+        #[serde(default)]
+        sample_rate: Option<f64>,
+    },
+}
+
+impl SinkEndpoint {
+    pub fn url(&self) -> &str {
+        match self {
+            SinkEndpoint::Address(url) | SinkEndpoint::Detailed { url, .. } => url,
+        }
+    }
+
+    pub fn sample_rate(&self) -> Option<f64> {
+        match self {
+            SinkEndpoint::Address(_) => None,
+            SinkEndpoint::Detailed { sample_rate, .. } => *sample_rate,
+        }
+    }
+}
+
+/// Rotating newline-delimited JSON file written by the file downstream sink
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FileSinkConfig {
+    /// File events are appended to; rotated segments get a `.<timestamp>` suffix
+    pub path: String,
+    /// Start a new segment once the current one would exceed this size
+    #[serde(default)]
+    pub rotate_size_mb: Option<u64>,
+    /// Start a new segment when the UTC day changes
+    #[serde(default)]
+    pub rotate_daily: bool,
+    /// Gzip rotated segments
+    #[serde(default)]
+    pub gzip: bool,
+    /// Fraction of events written to the file, chosen by event id (all when unset)
+    #[serde(default)]
+    pub sample_rate: Option<f64>,
+}
+
+/// Kafka producer settings for the Kafka downstream sink
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct KafkaConfig {
+    /// Bootstrap brokers as `host:port`
+    pub brokers: Vec<String>,
+    pub topic: String,
+    /// Use the event author's pubkey as the message key so each author stays on one partition
+    #[serde(default)]
+    pub key_by_pubkey: bool,
+    /// Fraction of events published to the topic, chosen by event id (all when unset)
+    #[serde(default)]
+    pub sample_rate: Option<f64>,
+}
+
+/// Behaviour of the event router when the output queue is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Wait for space, applying backpressure to ingest
+    #[default]
+    Block,
+    /// Evict the oldest queued event to make room
+    DropOldest,
+    /// Discard the event that did not fit
+    DropNewest,
+}
+
+/// Behaviour of a downstream sink once delivery keeps failing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SinkFailurePolicy {
+    /// Give up on the batch after `max_retries` attempts
+    #[default]
+    Drop,
+    /// Keep retrying until the batch is delivered, applying backpressure upstream
+    Block,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct MonitoringConfig {
     pub prometheus_port: u16,
     pub log_level: String,
+    #[serde(default)]
+    pub log_format: LogFormat,
+    #[serde(default)]
+    pub cors: CorsConfig,
+    /// Window in seconds over which `duplicate_ratio` is computed
+    #[serde(default = "default_duplicate_ratio_window_secs")]
+    pub duplicate_ratio_window_secs: u64,
+    /// Serve the REST API, metrics and WebSocket on this Unix domain socket instead of TCP
+    #[serde(default)]
+    pub unix_socket: Option<String>,
+    /// Expose `POST /api/admin/metrics/reset`; keep disabled in production
+    #[serde(default)]
+    pub allow_metrics_reset: bool,
+}
+
+fn default_duplicate_ratio_window_secs() -> u64 {
+    60
+}
+
+/// Log output format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// One JSON object per line with structured fields
+    Json,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct CorsConfig {
+    /// Origins allowed to call the REST API from a browser; `"*"` allows any origin
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SecurityConfig {
+    /// Bearer token required by mutating REST endpoints
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Directory that backup, export and import paths must stay inside (any path when unset)
+    #[serde(default)]
+    pub admin_dir: Option<String>,
+}
+
+/// Bounds on the `created_at` of events accepted from relays
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub struct IngestConfig {
+    /// Drop events created more than this many seconds ago (unbounded when unset)
+    #[serde(default)]
+    pub max_age_secs: Option<u64>,
+    /// Drop events created more than this many seconds in the future (unbounded when unset)
+    #[serde(default)]
+    pub max_future_skew_secs: Option<u64>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct AppConfig {
     pub relay: RelayConfig,
     pub deduplication: DeduplicationConfig,
     pub output: OutputConfig,
     pub monitoring: MonitoringConfig,
+    #[serde(default)]
+    pub security: SecurityConfig,
+    #[serde(default)]
+    pub ingest: IngestConfig,
+}
+
+/// Supported config file formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfigFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    fn from_path(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            None => Ok(ConfigFormat::Toml),
+            Some(ext) => match ext.to_ascii_lowercase().as_str() {
+                "toml" => Ok(ConfigFormat::Toml),
+                "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+                "json" => Ok(ConfigFormat::Json),
+                other => anyhow::bail!(
+                    "Unsupported config file extension .{} (expected .toml, .yaml, .yml or .json)",
+                    other
+                ),
+            },
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => "TOML",
+            ConfigFormat::Yaml => "YAML",
+            ConfigFormat::Json => "JSON",
+        }
+    }
+
+    /// Parse into a TOML table so environment overrides apply the same way for every format
+    fn parse(&self, data: &str) -> Result<toml::Table> {
+        let table = match self {
+            ConfigFormat::Toml => toml::from_str(data).map_err(anyhow::Error::from),
+            ConfigFormat::Yaml => serde_norway::from_str(data).map_err(anyhow::Error::from),
+            ConfigFormat::Json => serde_json::from_str(data).map_err(anyhow::Error::from),
+        };
+        table.with_context(|| format!("Failed to parse {} config", self.name()))
+    }
+}
+
+/// Result of comparing the running configuration with a re-read one
+#[derive(Debug, Default)]
+pub struct ConfigDiff {
+    /// Bootstrap relays to connect
+    pub added_relays: Vec<String>,
+    /// Bootstrap relays to disconnect
+    pub removed_relays: Vec<String>,
+    /// New `monitoring.log_level`, when it changed
+    pub log_level: Option<String>,
+    /// Dotted paths of changed settings that cannot be applied without a restart
+    pub requires_restart: Vec<String>,
+}
+
+impl ConfigDiff {
+    /// Whether the re-read configuration is identical to the running one
+    pub fn is_empty(&self) -> bool {
+        self.added_relays.is_empty()
+            && self.removed_relays.is_empty()
+            && self.log_level.is_none()
+            && self.requires_restart.is_empty()
+    }
+}
+
+/// Collect the dotted paths of leaves that differ between two JSON values
+fn changed_paths(
+    prefix: &str,
+    old: &serde_json::Value,
+    new: &serde_json::Value,
+    out: &mut Vec<String>,
+) {
+    match (old, new) {
+        (serde_json::Value::Object(old), serde_json::Value::Object(new)) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            let null = serde_json::Value::Null;
+            for key in keys {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                let old = old.get(key).unwrap_or(&null);
+                let new = new.get(key).unwrap_or(&null);
+                changed_paths(&path, old, new, out);
+            }
+        }
+        _ if old != new => out.push(prefix.to_string()),
+        _ => {}
+    }
 }
 
 impl AppConfig {
+    /// Load a config file, choosing the format from its extension:
+    /// `.toml` (also used for extensionless paths), `.yaml`/`.yml` or `.json`
     pub fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let format = ConfigFormat::from_path(path.as_ref())?;
         let data = fs::read_to_string(&path).with_context(|| {
             format!(
                 "Failed to read config file at {}",
                 path.as_ref().to_string_lossy()
             )
         })?;
-        let cfg: AppConfig = toml::from_str(&data).context("Failed to parse TOML config")?;
+        let mut table = format.parse(&data)?;
+        env::apply_env_overrides(&mut table, std::env::vars())?;
+        let cfg: AppConfig = env::deserialize(table)
+            .with_context(|| format!("Failed to parse {} config", format.name()))?;
+        cfg.validate().context("Invalid configuration")?;
         Ok(cfg)
     }
+
+    /// JSON view of the configuration with the API key and relay auth keys masked
+    pub fn redacted(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(api_key) = value.pointer_mut("/security/api_key") {
+            if !api_key.is_null() {
+                *api_key = REDACTED.into();
+            }
+        }
+        if let Some(serde_json::Value::Object(auth)) = value.pointer_mut("/relay/auth") {
+            for secret in auth.values_mut() {
+                *secret = REDACTED.into();
+            }
+        }
+        value
+    }
+
+    /// Compare against a re-read configuration, separating what can be applied to the running
+    /// service from settings that only take effect after a restart
+    pub fn diff(&self, new: &AppConfig) -> ConfigDiff {
+        // Compare relays the way the pool keys them, so respelling a URL is not a change
+        let normalized =
+            |urls: &[String]| -> Vec<String> { urls.iter().map(|url| lookup_key(url)).collect() };
+        let old_relays = normalized(&self.relay.bootstrap_relays);
+        let new_relays = normalized(&new.relay.bootstrap_relays);
+        let added_relays = new_relays
+            .iter()
+            .filter(|url| !old_relays.contains(url))
+            .cloned()
+            .collect();
+        let removed_relays = old_relays
+            .iter()
+            .filter(|url| !new_relays.contains(url))
+            .cloned()
+            .collect();
+        let log_level = (new.monitoring.log_level != self.monitoring.log_level)
+            .then(|| new.monitoring.log_level.clone());
+
+        // Everything except the live-reloadable fields must be unchanged
+        let without_reloadable = |cfg: &AppConfig| {
+            let mut value = serde_json::to_value(cfg).unwrap_or_default();
+            for pointer in ["/relay/bootstrap_relays", "/monitoring/log_level"] {
+                if let Some(field) = value.pointer_mut(pointer) {
+                    *field = serde_json::Value::Null;
+                }
+            }
+            value
+        };
+        let mut requires_restart = Vec::new();
+        changed_paths(
+            "",
+            &without_reloadable(self),
+            &without_reloadable(new),
+            &mut requires_restart,
+        );
+
+        ConfigDiff {
+            added_relays,
+            removed_relays,
+            log_level,
+            requires_restart,
+        }
+    }
+
+    /// Check cross-field invariants that TOML parsing cannot express
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            self.relay.max_connections > 0,
+            "relay.max_connections must be greater than 0"
+        );
+        ensure!(
+            !self.relay.bootstrap_relays.is_empty(),
+            "relay.bootstrap_relays must contain at least one relay URL"
+        );
+        ensure!(
+            self.monitoring.duplicate_ratio_window_secs > 0,
+            "monitoring.duplicate_ratio_window_secs must be greater than 0"
+        );
+        ensure!(
+            self.deduplication.max_event_bytes > 0,
+            "deduplication.max_event_bytes must be greater than 0"
+        );
+        ensure!(
+            self.relay.connect_timeout_ms > 0,
+            "relay.connect_timeout_ms must be greater than 0"
+        );
+        ensure!(
+            self.relay.initial_backfill_secs != Some(0),
+            "relay.initial_backfill_secs must be greater than 0"
+        );
+        ensure!(
+            self.relay.max_events_per_sec != Some(0),
+            "relay.max_events_per_sec must be greater than 0"
+        );
+        for (url, rate) in &self.relay.relay_max_events_per_sec {
+            ensure!(
+                *rate > 0,
+                "relay.relay_max_events_per_sec for {} must be greater than 0",
+                url
+            );
+        }
+        ensure!(
+            self.deduplication.bloom_capacity >= self.deduplication.hotset_size,
+            "deduplication.bloom_capacity ({}) must be at least deduplication.hotset_size ({})",
+            self.deduplication.bloom_capacity,
+            self.deduplication.hotset_size
+        );
+        ensure!(
+            self.deduplication.bloom_fp_rate > 0.0 && self.deduplication.bloom_fp_rate < 1.0,
+            "deduplication.bloom_fp_rate ({}) must be between 0 and 1 (exclusive)",
+            self.deduplication.bloom_fp_rate
+        );
+        ensure!(
+            self.deduplication.startup_ingest_rate != Some(0),
+            "deduplication.startup_ingest_rate must be greater than 0"
+        );
+        ensure!(
+            self.deduplication.dedup_window_secs != Some(0),
+            "deduplication.dedup_window_secs must be greater than 0"
+        );
+        for (kind, secs) in &self.deduplication.retention {
+            ensure!(
+                kind.parse::<u16>().is_ok(),
+                "deduplication.retention keys must be event kinds (0-65535), got {:?}",
+                kind
+            );
+            ensure!(
+                *secs > 0,
+                "deduplication.retention.{} must be greater than 0",
+                kind
+            );
+        }
+        ensure!(
+            self.deduplication.bloom_persist_interval_secs != Some(0),
+            "deduplication.bloom_persist_interval_secs must be greater than 0"
+        );
+        ensure!(
+            self.deduplication.rocksdb.write_buffer_mb > 0,
+            "deduplication.rocksdb.write_buffer_mb must be greater than 0"
+        );
+        ensure!(
+            self.deduplication.rocksdb.max_write_buffers > 0,
+            "deduplication.rocksdb.max_write_buffers must be greater than 0"
+        );
+        ensure!(
+            self.output.reorder_window_ms != Some(0),
+            "output.reorder_window_ms must be greater than 0"
+        );
+        if let Some(max) = self.output.adaptive_batch_max {
+            ensure!(
+                max >= self.output.batch_size,
+                "output.adaptive_batch_max ({}) must be at least output.batch_size ({})",
+                max,
+                self.output.batch_size
+            );
+        }
+        ensure!(
+            self.output.shutdown_timeout_secs > 0,
+            "output.shutdown_timeout_secs must be greater than 0"
+        );
+        ensure!(
+            self.output.queue_capacity != Some(0),
+            "output.queue_capacity must be greater than 0"
+        );
+        ensure!(
+            self.output.ws_client_buffer > 0,
+            "output.ws_client_buffer must be greater than 0"
+        );
+        ensure!(
+            self.output
+                .auth
+                .as_ref()
+                .is_none_or(|auth| !auth.allowed_pubkeys.is_empty()),
+            "output.auth.allowed_pubkeys must list at least one pubkey"
+        );
+        ensure!(
+            self.output.max_subscriptions_per_client > 0,
+            "output.max_subscriptions_per_client must be greater than 0"
+        );
+        ensure!(
+            self.output.slow_client_disconnect_after != Some(0),
+            "output.slow_client_disconnect_after must be greater than 0"
+        );
+        ensure!(
+            self.output.rest_pool.max_idle_per_host > 0,
+            "output.rest_pool.max_idle_per_host must be greater than 0"
+        );
+        ensure!(
+            self.output.rest_pool.idle_timeout_secs > 0,
+            "output.rest_pool.idle_timeout_secs must be greater than 0"
+        );
+        #[cfg(not(feature = "kafka"))]
+        ensure!(
+            self.output.downstream_kafka.is_none(),
+            "output.downstream_kafka requires building with `--features kafka`"
+        );
+        if let Some(kafka) = &self.output.downstream_kafka {
+            ensure!(
+                !kafka.brokers.is_empty(),
+                "output.downstream_kafka.brokers must contain at least one broker"
+            );
+            ensure!(
+                !kafka.topic.is_empty(),
+                "output.downstream_kafka.topic must not be empty"
+            );
+        }
+        ensure!(
+            self.output.websocket_port != 0,
+            "output.websocket_port must be a non-zero port"
+        );
+        if let Some(file) = &self.output.downstream_file {
+            ensure!(
+                !file.path.is_empty(),
+                "output.downstream_file.path must not be empty"
+            );
+            ensure!(
+                file.rotate_size_mb != Some(0),
+                "output.downstream_file.rotate_size_mb must be greater than 0"
+            );
+        }
+        if let Some(tls) = &self.output.tls {
+            ensure!(
+                !tls.cert_path.is_empty() && !tls.key_path.is_empty(),
+                "output.tls requires both cert_path and key_path"
+            );
+        }
+        let sample_rates = self
+            .output
+            .downstream_tcp
+            .iter()
+            .chain(&self.output.downstream_rest)
+            .map(|endpoint| (endpoint.url(), endpoint.sample_rate()))
+            .chain(
+                self.output
+                    .downstream_file
+                    .iter()
+                    .map(|f| ("file", f.sample_rate)),
+            )
+            .chain(
+                self.output
+                    .downstream_kafka
+                    .iter()
+                    .map(|k| ("kafka", k.sample_rate)),
+            );
+        for (sink, rate) in sample_rates {
+            ensure!(
+                rate.is_none_or(|rate| rate > 0.0 && rate <= 1.0),
+                "sample_rate of downstream sink {} must be greater than 0 and at most 1",
+                sink
+            );
+        }
+        if let Some(path) = &self.monitoring.unix_socket {
+            ensure!(
+                cfg!(unix),
+                "monitoring.unix_socket is only supported on Unix"
+            );
+            ensure!(!path.is_empty(), "monitoring.unix_socket must not be empty");
+            ensure!(
+                self.output.tls.is_none(),
+                "monitoring.unix_socket cannot be combined with output.tls"
+            );
+        }
+        ensure!(
+            self.security
+                .admin_dir
+                .as_ref()
+                .is_none_or(|dir| !dir.is_empty()),
+            "security.admin_dir must not be empty"
+        );
+        ensure!(
+            self.ingest
+                .max_age_secs
+                .is_none_or(|secs| secs > 0 && secs <= MAX_INGEST_AGE_SECS),
+            "ingest.max_age_secs must be greater than 0 and at most {}",
+            MAX_INGEST_AGE_SECS
+        );
+        ensure!(
+            self.ingest
+                .max_future_skew_secs
+                .is_none_or(|secs| secs > 0 && secs <= MAX_INGEST_FUTURE_SKEW_SECS),
+            "ingest.max_future_skew_secs must be greater than 0 and at most {}",
+            MAX_INGEST_FUTURE_SKEW_SECS
+        );
+        // Keys are parsed again at startup; checking them here lets --check-config catch typos
+        self.deduplication.bypass_pubkeys()?;
+        self.relay.auth_keys()?;
+        self.relay.subscription.filter()?;
+        if let Some(auth) = &self.output.auth {
+            auth.pubkeys()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr_sdk::nips::nip19::ToBech32;
+
+    fn template_config() -> AppConfig {
+        toml::from_str(include_str!("../../config.template.toml")).unwrap()
+    }
+
+    fn validation_error(cfg: &AppConfig) -> String {
+        format!("{:#}", cfg.validate().unwrap_err())
+    }
+
+    #[test]
+    fn the_same_config_loads_from_toml_yaml_and_json() {
+        let template = include_str!("../../config.template.toml");
+        let table: toml::Table = toml::from_str(template).unwrap();
+        let files = [
+            ("config.toml", template.to_string()),
+            ("config.yml", serde_norway::to_string(&table).unwrap()),
+            ("config.json", serde_json::to_string_pretty(&table).unwrap()),
+        ];
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let loaded: Vec<serde_json::Value> = files
+            .iter()
+            .map(|(name, data)| {
+                let path = dir.path().join(name);
+                fs::write(&path, data).unwrap();
+                let cfg = AppConfig::load_from_path(&path).unwrap();
+                serde_json::to_value(cfg).unwrap()
+            })
+            .collect();
+        assert_eq!(loaded[1], loaded[0]);
+        assert_eq!(loaded[2], loaded[0]);
+    }
+
+    #[test]
+    fn backoff_doubles_from_the_base_delay_up_to_the_max() {
+        let policy = ReconnectPolicy {
+            base_delay_ms: 100,
+            max_delay_ms: 1000,
+            max_retries: 10,
+        };
+        let delays: Vec<u64> = (0..6)
+            .map(|attempt| policy.backoff_delay(attempt).as_millis() as u64)
+            .collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+        assert_eq!(policy.backoff_delay(u32::MAX), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn validate_parses_keys_and_pubkeys() {
+        let keys = Keys::generate();
+        let npub = keys.public_key().to_bech32().unwrap();
+        let relay = "wss://relay.example".to_string();
+        let mut cfg = template_config();
+        cfg.deduplication.dedup_bypass_pubkeys = vec![npub.clone()];
+        cfg.relay
+            .auth
+            .insert(relay.clone(), keys.secret_key().to_secret_hex());
+        cfg.relay.subscription.authors = vec![keys.public_key().to_hex()];
+        cfg.output.auth = Some(WsAuthConfig {
+            allowed_pubkeys: vec![npub],
+        });
+        cfg.validate().unwrap();
+
+        cfg.deduplication.dedup_bypass_pubkeys = vec!["npub1nope".to_string()];
+        assert!(validation_error(&cfg).contains("deduplication.dedup_bypass_pubkeys"));
+        cfg.deduplication.dedup_bypass_pubkeys.clear();
+
+        cfg.relay
+            .auth
+            .insert(relay.clone(), "nsec1nope".to_string());
+        assert!(validation_error(&cfg).contains(&relay));
+        cfg.relay.auth.clear();
+
+        cfg.relay.subscription.authors = vec!["not a key".to_string()];
+        assert!(validation_error(&cfg).contains("relay.subscription.authors"));
+        cfg.relay.subscription.authors.clear();
+
+        cfg.output.auth = Some(WsAuthConfig {
+            allowed_pubkeys: vec!["deadbeef".to_string()],
+        });
+        assert!(validation_error(&cfg).contains("output.auth.allowed_pubkeys"));
+    }
+
+    #[test]
+    fn diff_compares_relays_by_normalized_url() {
+        let mut old = template_config();
+        old.relay.bootstrap_relays = vec![
+            "wss://relay.damus.io".to_string(),
+            "wss://nos.lol".to_string(),
+        ];
+        let mut new = old.clone();
+        new.relay.bootstrap_relays = vec![
+            "wss://Relay.Damus.io/".to_string(),
+            "wss://relay.nostr.band".to_string(),
+        ];
+
+        let diff = old.diff(&new);
+
+        assert_eq!(diff.added_relays, ["wss://relay.nostr.band"]);
+        assert_eq!(diff.removed_relays, ["wss://nos.lol"]);
+        assert!(diff.requires_restart.is_empty());
+    }
+
+    #[test]
+    fn validate_bounds_the_ingest_time_limits() {
+        let mut cfg = template_config();
+        cfg.ingest.max_age_secs = Some(86400);
+        cfg.ingest.max_future_skew_secs = Some(900);
+        cfg.validate().unwrap();
+
+        for secs in [0, MAX_INGEST_FUTURE_SKEW_SECS + 1] {
+            cfg.ingest.max_future_skew_secs = Some(secs);
+            assert!(validation_error(&cfg).contains("ingest.max_future_skew_secs"));
+        }
+        cfg.ingest.max_future_skew_secs = None;
+
+        for secs in [0, MAX_INGEST_AGE_SECS + 1] {
+            cfg.ingest.max_age_secs = Some(secs);
+            assert!(validation_error(&cfg).contains("ingest.max_age_secs"));
+        }
+    }
 }
@@ -0,0 +1,98 @@
+use nostr_sdk::EventId;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::core::relay_pool::normalize_relay_url;
+
+/// Relay currently credited with an event whose race window is still open
+struct Claim {
+    relay_url: String,
+    priority: i32,
+}
+
+#[derive(Default)]
+struct AttributionState {
+    claims: HashMap<EventId, Claim>,
+    /// Window deadlines in arrival order; the window is fixed, so they are sorted
+    deadlines: VecDeque<(Instant, EventId)>,
+}
+
+/// Credits each event to the relay that delivered it first. A copy from a higher-priority relay
+/// arriving within `window` of the first one takes the credit over, so a slightly slower
+/// preferred relay still wins the race. Attributions are settled once their window closes.
+pub struct RelayAttribution {
+    /// Priority by normalized relay URL; unlisted relays have priority 0
+    priorities: HashMap<String, i32>,
+    window: Duration,
+    state: Mutex<AttributionState>,
+}
+
+impl RelayAttribution {
+    /// Prefer relays with a higher priority among copies delivered within `window`
+    pub fn new(priorities: HashMap<String, i32>, window: Duration) -> Self {
+        Self {
+            priorities: priorities
+                .into_iter()
+                .map(|(url, priority)| (normalize_relay_url(&url).unwrap_or(url), priority))
+                .collect(),
+            window,
+            state: Mutex::new(AttributionState::default()),
+        }
+    }
+
+    fn priority(&self, relay_url: &str) -> i32 {
+        self.priorities.get(relay_url).copied().unwrap_or(0)
+    }
+
+    /// Record that `relay_url` delivered `event_id` at `now`; `is_new` opens the event's window,
+    /// while a duplicate only competes for an open one. Returns the relays credited with the
+    /// events whose window has closed since the last call.
+    pub fn observe(
+        &self,
+        event_id: EventId,
+        relay_url: &str,
+        is_new: bool,
+        now: Instant,
+    ) -> Vec<String> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let settled = Self::settle(&mut state, now);
+
+        let priority = self.priority(relay_url);
+        match state.claims.get_mut(&event_id) {
+            Some(claim) => {
+                if priority > claim.priority {
+                    claim.relay_url = relay_url.to_string();
+                    claim.priority = priority;
+                }
+            }
+            None if is_new => {
+                state.claims.insert(
+                    event_id,
+                    Claim {
+                        relay_url: relay_url.to_string(),
+                        priority,
+                    },
+                );
+                state.deadlines.push_back((now + self.window, event_id));
+            }
+            None => {}
+        }
+        settled
+    }
+
+    /// Close every window that ended by `now`, returning the relay credited with each event
+    fn settle(state: &mut AttributionState, now: Instant) -> Vec<String> {
+        let mut settled = Vec::new();
+        while let Some(&(deadline, event_id)) = state.deadlines.front() {
+            if deadline > now {
+                break;
+            }
+            state.deadlines.pop_front();
+            if let Some(claim) = state.claims.remove(&event_id) {
+                settled.push(claim.relay_url);
+            }
+        }
+        settled
+    }
+}
@@ -1,11 +1,21 @@
 use crate::storage::{
-    bloom_filter::BloomFilter, memory_cache::MemoryCache, rocksdb_store::RocksDBStore,
+    bloom_filter::BloomFilter,
+    memory_cache::MemoryCache,
+    rocksdb_store::{RocksDBStore, content_hash},
 };
 // use anyhow::Result;
-use crate::api::metrics::Metrics;
-use dashmap::DashSet;
-use nostr_sdk::{Event, EventId};
+use crate::api::metrics::{DEDUP_LAYER_HOTSET, DEDUP_LAYER_LRU, DEDUP_LAYER_ROCKSDB, Metrics};
+use crate::config::{CircuitBreakerConfig, DedupKey};
+use crate::core::attribution::RelayAttribution;
+use crate::core::warmup::WarmupThrottle;
+use crate::output::circuit_breaker::{BreakerState, CircuitBreaker};
+use dashmap::{DashMap, DashSet};
+use nostr_sdk::{Event, EventId, PublicKey};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 use tracing::{debug, trace};
 
 /// Multi-layer deduplication engine
@@ -13,12 +23,34 @@ use tracing::{debug, trace};
 /// Layer 2: LRU cache (recent events, exact match)
 /// Layer 3: RocksDB (persistent storage, exact match)
 /// Layer 4: Concurrent hash set (hot path for very recent events)
+///
+/// When RocksDB keeps failing, a circuit breaker takes layer 3 out of the path and the engine
+/// degrades to hot set, bloom filter and LRU only until a probe against RocksDB succeeds again.
+///
+/// With a dedup window, an id caught by any layer is only a duplicate if it was accepted as
+/// new within the window; otherwise it passes as new and its window restarts.
+///
+/// Events by bypassed pubkeys never count as duplicates; they are stored and always forwarded.
 pub struct DeduplicationEngine {
     bloom: Arc<BloomFilter>,
     lru_cache: Arc<MemoryCache>,
     rocksdb: Arc<RocksDBStore>,
     hot_set: Arc<DashSet<String>>,
     metrics: Option<Arc<Metrics>>,
+    dedup_key: DedupKey,
+    rocksdb_breaker: CircuitBreaker,
+    /// Seconds an accepted id keeps later copies out; forever when unset
+    dedup_window: Option<u64>,
+    /// Unix time in seconds each recently accepted id was seen, while a window is set
+    last_seen: DashMap<String, u64>,
+    /// `last_seen` size at which ids outside the window are swept
+    last_seen_prune_at: AtomicUsize,
+    /// Caps the check rate for a while after startup
+    warmup: Option<WarmupThrottle>,
+    /// Authors whose events skip deduplication
+    bypass_pubkeys: HashSet<PublicKey>,
+    /// Which relay each event is credited to, when attribution is enabled
+    attribution: Option<RelayAttribution>,
 }
 
 impl DeduplicationEngine {
@@ -30,6 +62,14 @@ impl DeduplicationEngine {
             rocksdb,
             hot_set: Arc::new(DashSet::new()),
             metrics: None,
+            dedup_key: DedupKey::default(),
+            rocksdb_breaker: CircuitBreaker::new(&CircuitBreakerConfig::default()),
+            dedup_window: None,
+            last_seen: DashMap::new(),
+            last_seen_prune_at: AtomicUsize::new(0),
+            warmup: None,
+            bypass_pubkeys: HashSet::new(),
+            attribution: None,
         }
     }
 
@@ -38,14 +78,23 @@ impl DeduplicationEngine {
         rocksdb: Arc<RocksDBStore>,
         hot_set_size: usize,
         bloom_capacity: usize,
+        bloom_fp_rate: f64,
         lru_size: usize,
     ) -> Self {
         Self {
-            bloom: Arc::new(BloomFilter::with_capacity(bloom_capacity, 0.01)),
+            bloom: Arc::new(BloomFilter::with_capacity(bloom_capacity, bloom_fp_rate)),
             lru_cache: Arc::new(MemoryCache::with_capacity(lru_size)),
             rocksdb,
             hot_set: Arc::new(DashSet::with_capacity(hot_set_size)),
             metrics: None,
+            dedup_key: DedupKey::default(),
+            rocksdb_breaker: CircuitBreaker::new(&CircuitBreakerConfig::default()),
+            dedup_window: None,
+            last_seen: DashMap::new(),
+            last_seen_prune_at: AtomicUsize::new(0),
+            warmup: None,
+            bypass_pubkeys: HashSet::new(),
+            attribution: None,
         }
     }
 
@@ -54,6 +103,226 @@ impl DeduplicationEngine {
         self
     }
 
+    /// Select the key used to identify duplicate events
+    pub fn with_dedup_key(mut self, dedup_key: DedupKey) -> Self {
+        self.dedup_key = dedup_key;
+        self
+    }
+
+    /// Only treat ids accepted within the last `window` as duplicates
+    pub fn with_dedup_window(mut self, window: Duration) -> Self {
+        self.dedup_window = Some(window.as_secs().max(1));
+        self.last_seen_prune_at = AtomicUsize::new(self.hot_set.capacity().max(1));
+        self
+    }
+
+    /// Always forward events by these authors, even copies already seen
+    pub fn with_bypass_pubkeys(mut self, pubkeys: HashSet<PublicKey>) -> Self {
+        self.bypass_pubkeys = pubkeys;
+        self
+    }
+
+    /// Credit each event to the relay that delivered it first, preferring relays with a higher
+    /// priority among copies arriving within `window`
+    pub fn with_relay_attribution(
+        mut self,
+        priorities: HashMap<String, i32>,
+        window: Duration,
+    ) -> Self {
+        self.attribution = Some(RelayAttribution::new(priorities, window));
+        self
+    }
+
+    /// Check at most `rate` events per second during the first `duration` after startup
+    pub fn with_startup_throttle(mut self, rate: u32, duration: Duration) -> Self {
+        self.warmup = Some(WarmupThrottle::new(rate, duration));
+        self
+    }
+
+    /// Whether the startup throttle is still limiting the check rate
+    pub fn is_warming_up(&self) -> bool {
+        self.warmup
+            .as_ref()
+            .is_some_and(WarmupThrottle::is_warming_up)
+    }
+
+    /// Configure when persistent RocksDB errors degrade deduplication to in-memory layers
+    pub fn with_rocksdb_breaker(mut self, config: &CircuitBreakerConfig) -> Self {
+        self.rocksdb_breaker = CircuitBreaker::new(config);
+        self
+    }
+
+    /// Whether RocksDB is currently bypassed because of repeated errors
+    pub fn is_degraded(&self) -> bool {
+        self.rocksdb_breaker.state() != BreakerState::Closed
+    }
+
+    /// Whether RocksDB may be used now; once the cooldown elapses the next call is a probe
+    fn rocksdb_available(&self) -> bool {
+        self.rocksdb_breaker.allow_request()
+    }
+
+    /// Feed the outcome of a RocksDB operation to the breaker, logging mode changes
+    fn record_rocksdb_result<T>(&self, result: &anyhow::Result<T>) {
+        let was_degraded = self.is_degraded();
+        let state = match result {
+            Ok(_) => self.rocksdb_breaker.record_success(),
+            Err(_) => self.rocksdb_breaker.record_failure(),
+        };
+        match state {
+            BreakerState::Closed if was_degraded => {
+                tracing::info!("RocksDB recovered, deduplication is persistent again");
+            }
+            BreakerState::Open if !was_degraded => {
+                tracing::error!(
+                    "RocksDB keeps failing, deduplication DEGRADED to in-memory layers only: \
+                     events are not stored and old duplicates may be forwarded"
+                );
+            }
+            _ => {}
+        }
+        if let Some(m) = &self.metrics {
+            m.dedup_degraded.set(if state == BreakerState::Closed {
+                0.0
+            } else {
+                1.0
+            });
+        }
+    }
+
+    /// Compute the identifier used for deduplication under the configured key mode
+    fn dedup_id(&self, event: &Event) -> EventId {
+        match self.dedup_key {
+            DedupKey::Id => event.id,
+            // Same canonical serialization as NIP-01 ids, recomputed from the event fields
+            DedupKey::ContentHash => content_hash(event),
+        }
+    }
+
+    /// Store a new event (and its content hash marker) so it can be served and replayed later
+    /// Skipped while RocksDB is bypassed in degraded mode
+    async fn persist(&self, event: &Event, event_id_hex: &str) {
+        if !self.rocksdb_available() {
+            return;
+        }
+        let stored = self.rocksdb.store_event(event).await;
+        self.record_rocksdb_result(&stored);
+        if let Err(e) = stored {
+            tracing::error!("Failed to store event {} in RocksDB: {}", event_id_hex, e);
+            return;
+        }
+        if self.dedup_window.is_some() {
+            let stored = self.rocksdb.store_last_seen(event_id_hex, unix_now()).await;
+            self.record_rocksdb_result(&stored);
+            if let Err(e) = stored {
+                tracing::error!(
+                    "Failed to store last-seen time of {} in RocksDB: {}",
+                    event_id_hex,
+                    e
+                );
+            }
+        }
+        if self.dedup_key == DedupKey::ContentHash {
+            let stored = self.rocksdb.store_content_hash(event_id_hex).await;
+            self.record_rocksdb_result(&stored);
+            if let Err(e) = stored {
+                tracing::error!(
+                    "Failed to store content hash {} in RocksDB: {}",
+                    event_id_hex,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Whether an id caught by a dedup layer was accepted within the window (always without one)
+    async fn seen_within_window(&self, event_id_hex: &str) -> bool {
+        let Some(window) = self.dedup_window else {
+            return true;
+        };
+        let seen_at = match self.last_seen.get(event_id_hex) {
+            Some(seen_at) => Some(*seen_at),
+            None if self.rocksdb_available() => {
+                let lookup = self.rocksdb.last_seen(event_id_hex).await;
+                self.record_rocksdb_result(&lookup);
+                lookup.unwrap_or_else(|e| {
+                    tracing::error!("Failed to read last-seen time of {}: {}", event_id_hex, e);
+                    None
+                })
+            }
+            None => None,
+        };
+        seen_at.is_some_and(|seen_at| unix_now().saturating_sub(seen_at) < window)
+    }
+
+    /// Start a new window for an id accepted as new, sweeping expired ids as the map grows
+    fn remember_seen(&self, event_id_hex: &str) {
+        let Some(window) = self.dedup_window else {
+            return;
+        };
+        let now = unix_now();
+        self.last_seen.insert(event_id_hex.to_string(), now);
+        if self.last_seen.len() >= self.last_seen_prune_at.load(Ordering::Relaxed) {
+            self.last_seen
+                .retain(|_, seen_at| now.saturating_sub(*seen_at) < window);
+            let next = (self.last_seen.len() * 2)
+                .max(self.hot_set.capacity())
+                .max(1);
+            self.last_seen_prune_at.store(next, Ordering::Relaxed);
+        }
+    }
+
+    /// Count a duplicate against the layer that caught it
+    fn record_duplicate(&self, layer: &str) {
+        if let Some(m) = &self.metrics {
+            m.duplicates_filtered.inc();
+            m.dedup_layer_duplicates.with_label_values(&[layer]).inc();
+            m.record_dedup_outcome(true);
+        }
+    }
+
+    /// Count an event that passed deduplication as new
+    fn record_unique(&self) {
+        if let Some(m) = &self.metrics {
+            m.record_dedup_outcome(false);
+        }
+    }
+
+    /// Count a bloom filter lookup by outcome
+    fn record_bloom_check(&self, positive: bool) {
+        if let Some(m) = &self.metrics {
+            let result = if positive { "positive" } else { "negative" };
+            m.dedup_bloom_checks.with_label_values(&[result]).inc();
+        }
+    }
+
+    /// Count an LRU cache lookup by outcome
+    fn record_lru_lookup(&self, hit: bool) {
+        if let Some(m) = &self.metrics {
+            let result = if hit { "hit" } else { "miss" };
+            m.dedup_lru_lookups.with_label_values(&[result]).inc();
+        }
+    }
+
+    /// Insert an id into the LRU cache, counting the entry it evicts if it was full
+    async fn cache_in_lru(&self, event_id_hex: String) {
+        if self.lru_cache.put(event_id_hex).await {
+            if let Some(m) = &self.metrics {
+                m.dedup_lru_evictions.inc();
+            }
+        }
+    }
+
+    /// Save the bloom filter so a restarted engine can reload it instead of starting empty
+    pub async fn save_bloom(&self, path: &Path) -> anyhow::Result<()> {
+        self.bloom.save(path).await
+    }
+
+    /// Reload a bloom filter saved by [`DeduplicationEngine::save_bloom`], if one exists
+    pub async fn load_bloom(&self, path: &Path) -> anyhow::Result<bool> {
+        self.bloom.load(path).await
+    }
+
     /// Warm in-memory structures from RocksDB successful-forward index.
     /// Loads up to `limit` most recent successfully forwarded events into bloom, hot_set and LRU.
     pub async fn warm_from_db(&self, limit: usize) {
@@ -72,7 +341,7 @@ impl DeduplicationEngine {
                     // continue best-effort using the string forms for caches
                 }
             }
-            self.lru_cache.put(id.clone()).await;
+            self.cache_in_lru(id.clone()).await;
             self.hot_set.insert(id.to_string());
         }
         tracing::info!(
@@ -83,59 +352,110 @@ impl DeduplicationEngine {
 
     /// Check if an event is a duplicate
     /// Returns true if duplicate, false if new event
+    /// While the startup throttle is warming up, waits for a free slot first
     pub async fn is_duplicate(&self, event: &Event) -> bool {
-        let event_id_hex = event.id.to_hex();
+        if let Some(warmup) = &self.warmup {
+            warmup.acquire().await;
+        }
+        let dedup_id = self.dedup_id(event);
+        let event_id_hex = dedup_id.to_hex();
+
+        if self.bypass_pubkeys.contains(&event.pubkey) {
+            trace!(
+                "Event {} is from a bypassed pubkey, skipping dedup",
+                event_id_hex
+            );
+            self.persist(event, &event_id_hex).await;
+            return false;
+        }
 
         // Layer 0: Hot set check (fastest, for very recent events)
         if self.hot_set.contains(&event_id_hex) {
+            if !self.seen_within_window(&event_id_hex).await {
+                return self.accept_new(event, event_id_hex).await;
+            }
             trace!("Event {} found in hot set (duplicate)", event_id_hex);
+            self.record_duplicate(DEDUP_LAYER_HOTSET);
             return true;
         }
 
         // Layer 1: Bloom filter check (fast, in-memory, may have false positives)
-        if self.bloom.contains(event.id.as_bytes()).await {
+        if self.bloom.contains(dedup_id.as_bytes()).await {
             // Bloom filter says it might exist, need to verify
             trace!("Event {} might exist (bloom filter positive)", event_id_hex);
+            self.record_bloom_check(true);
         } else {
             // Bloom filter says it doesn't exist, definitely new
-            self.bloom.insert(event.id.as_bytes()).await;
+            self.bloom.insert(dedup_id.as_bytes()).await;
+            self.persist(event, &event_id_hex).await;
+            self.remember_seen(&event_id_hex);
             self.hot_set.insert(event_id_hex.clone());
-            debug!("New event {} added to bloom filter", event_id_hex);
+            debug!(event_id = %event_id_hex, "New event {} added to bloom filter", event_id_hex);
+            self.record_bloom_check(false);
+            self.record_unique();
             return false;
         }
 
         // Layer 2: LRU cache check (recent events, exact match)
-        if self.lru_cache.contains(&event_id_hex).await {
+        let lru_hit = self.lru_cache.contains(&event_id_hex).await;
+        self.record_lru_lookup(lru_hit);
+        if lru_hit {
+            if !self.seen_within_window(&event_id_hex).await {
+                return self.accept_new(event, event_id_hex).await;
+            }
             trace!("Event {} found in LRU cache (duplicate)", event_id_hex);
             self.hot_set.insert(event_id_hex);
-            if let Some(m) = &self.metrics {
-                m.duplicates_filtered.inc();
-            }
+            self.record_duplicate(DEDUP_LAYER_LRU);
             return true;
         }
 
-        // Layer 3: RocksDB check (persistent storage, exact match)
-        if self.rocksdb.exists(&event_id_hex).await {
+        // Layer 3: RocksDB check (persistent storage, exact match), skipped while degraded
+        let exists = if self.rocksdb_available() {
+            let lookup = match self.dedup_key {
+                DedupKey::Id => self.rocksdb.exists(&event_id_hex).await,
+                DedupKey::ContentHash => self.rocksdb.content_hash_exists(&event_id_hex).await,
+            };
+            self.record_rocksdb_result(&lookup);
+            match lookup {
+                Ok(exists) => exists,
+                Err(e) => {
+                    tracing::error!("Failed to look up event {} in RocksDB: {}", event_id_hex, e);
+                    false
+                }
+            }
+        } else {
+            false
+        };
+        if exists && !self.seen_within_window(&event_id_hex).await {
+            return self.accept_new(event, event_id_hex).await;
+        }
+        if exists {
             // Found in persistent storage, add to cache layers
-            self.lru_cache.put(event_id_hex.clone()).await;
+            self.cache_in_lru(event_id_hex.clone()).await;
             self.hot_set.insert(event_id_hex.clone());
             trace!("Event {} found in RocksDB (duplicate)", event_id_hex);
-            if let Some(m) = &self.metrics {
-                m.duplicates_filtered.inc();
-            }
+            self.record_duplicate(DEDUP_LAYER_ROCKSDB);
             return true;
         }
 
+        self.accept_new(event, event_id_hex).await
+    }
+
+    /// Store an event that passed every layer (or whose window expired) and report it as new
+    async fn accept_new(&self, event: &Event, event_id_hex: String) -> bool {
         // New event - store in all layers
-        debug!("New event {} detected, storing in all layers", event_id_hex);
+        debug!(
+            event_id = %event_id_hex,
+            "New event {} detected, storing in all layers",
+            event_id_hex
+        );
 
         // Store in persistent storage
-        if let Err(e) = self.rocksdb.store_event(event).await {
-            tracing::error!("Failed to store event {} in RocksDB: {}", event_id_hex, e);
-        }
+        self.persist(event, &event_id_hex).await;
+        self.remember_seen(&event_id_hex);
 
         // Store in cache layers
-        self.lru_cache.put(event_id_hex.clone()).await;
+        self.cache_in_lru(event_id_hex.clone()).await;
         self.hot_set.insert(event_id_hex);
 
         // Limit hot set size to prevent unbounded growth
@@ -153,9 +473,54 @@ impl DeduplicationEngine {
             }
         }
 
+        self.record_unique();
         false
     }
 
+    /// Note that `relay_url` delivered `event`; `is_new` is the outcome of `is_duplicate`.
+    /// Counts the credited relay of every event whose attribution window has closed.
+    pub fn attribute(&self, event: &Event, relay_url: &str, is_new: bool) {
+        let Some(attribution) = &self.attribution else {
+            return;
+        };
+        let settled = attribution.observe(self.dedup_id(event), relay_url, is_new, Instant::now());
+        if let Some(m) = &self.metrics {
+            for relay_url in settled {
+                m.record_relay_attribution(&relay_url);
+            }
+        }
+    }
+
+    /// Mark events stored outside the ingest path (e.g. imported) as seen, so copies arriving
+    /// from relays later are caught as duplicates
+    pub async fn remember(&self, events: &[Event]) {
+        for event in events {
+            let dedup_id = self.dedup_id(event);
+            self.bloom.insert(dedup_id.as_bytes()).await;
+            if self.dedup_key == DedupKey::ContentHash {
+                let stored = self.rocksdb.store_content_hash(&dedup_id.to_hex()).await;
+                if let Err(e) = stored {
+                    tracing::error!("Failed to store content hash of {}: {}", event.id, e);
+                }
+            }
+        }
+    }
+
+    /// Forget an event so that re-ingesting it is no longer treated as a duplicate, removing the
+    /// markers `persist` wrote under its dedup id. The bloom filter cannot unlearn it, but a
+    /// positive there falls through to exact checks.
+    pub async fn forget(&self, event: &Event) -> anyhow::Result<()> {
+        let event_id_hex = self.dedup_id(event).to_hex();
+        self.hot_set.remove(&event_id_hex);
+        self.last_seen.remove(&event_id_hex);
+        self.lru_cache.remove(&event_id_hex).await;
+        self.rocksdb.delete_last_seen(&event_id_hex).await?;
+        if self.dedup_key == DedupKey::ContentHash {
+            self.rocksdb.delete_content_hash(&event_id_hex).await?;
+        }
+        Ok(())
+    }
+
     /// Get statistics about the deduplication engine
     pub async fn get_stats(&self) -> DedupeStats {
         DedupeStats {
@@ -163,10 +528,17 @@ impl DeduplicationEngine {
             lru_cache_size: self.lru_cache.len().await,
             hot_set_size: self.hot_set.len(),
             rocksdb_approximate_count: self.rocksdb.approximate_count().await,
+            degraded: self.is_degraded(),
+            warming_up: self.is_warming_up(),
         }
     }
 }
 
+/// Current Unix time in seconds
+fn unix_now() -> u64 {
+    chrono::Utc::now().timestamp().max(0) as u64
+}
+
 /// Statistics about the deduplication engine
 #[derive(Debug, Clone)]
 pub struct DedupeStats {
@@ -174,4 +546,284 @@ pub struct DedupeStats {
     pub lru_cache_size: usize,
     pub hot_set_size: usize,
     pub rocksdb_approximate_count: u64,
+    /// RocksDB is bypassed and only the in-memory layers deduplicate
+    pub degraded: bool,
+    /// Ingest is still capped by the startup throttle
+    pub warming_up: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::relay_pool::normalize_relay_url;
+    use crate::test_support;
+    use crate::test_support::{signed_events, temp_store};
+    use nostr_sdk::{EventBuilder, Keys, Tag, Timestamp};
+
+    /// Dedup counters are shared by the whole test binary; tests reading them hold this lock
+    /// so the changes they see are their own
+    static METRICS_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    /// Duplicates caught per layer, then bloom positives and negatives
+    fn layer_counts(m: &Metrics) -> [u64; 5] {
+        let layer = |l: &str| m.dedup_layer_duplicates.with_label_values(&[l]).get();
+        let bloom = |r: &str| m.dedup_bloom_checks.with_label_values(&[r]).get();
+        [
+            layer(DEDUP_LAYER_HOTSET),
+            layer(DEDUP_LAYER_LRU),
+            layer(DEDUP_LAYER_ROCKSDB),
+            bloom("positive"),
+            bloom("negative"),
+        ]
+    }
+
+    /// Change in each counter since `before`
+    fn delta<const N: usize>(before: [u64; N], after: [u64; N]) -> [u64; N] {
+        std::array::from_fn(|i| after[i] - before[i])
+    }
+
+    /// Two copies of one note by the same author whose tags are listed in opposite orders, so
+    /// they have different ids but the same content hash
+    fn reordered_copies() -> (Event, Event) {
+        let keys = Keys::generate();
+        let created_at = Timestamp::now();
+        let tags = [Tag::hashtag("nostr"), Tag::hashtag("relay")];
+        let copy = |tags: Vec<Tag>| {
+            EventBuilder::text_note("same note")
+                .tags(tags)
+                .custom_created_at(created_at)
+                .sign_with_keys(&keys)
+                .unwrap()
+        };
+        let first = copy(tags.to_vec());
+        let second = copy(tags.into_iter().rev().collect());
+        (first, second)
+    }
+
+    #[tokio::test]
+    async fn copies_with_the_same_content_collapse_under_the_content_hash() {
+        let (first, second) = reordered_copies();
+        assert_ne!(first.id, second.id);
+
+        let (_dir, store) = temp_store();
+        let engine = DeduplicationEngine::new(store).with_dedup_key(DedupKey::ContentHash);
+        assert!(!engine.is_duplicate(&first).await);
+        assert!(engine.is_duplicate(&second).await);
+    }
+
+    #[tokio::test]
+    async fn copies_with_the_same_content_are_distinct_under_the_id() {
+        let (first, second) = reordered_copies();
+        let (_dir, store) = temp_store();
+        let engine = DeduplicationEngine::new(store);
+        assert!(!engine.is_duplicate(&first).await);
+        assert!(!engine.is_duplicate(&second).await);
+        assert!(engine.is_duplicate(&second).await);
+    }
+
+    #[tokio::test]
+    async fn forget_removes_the_markers_written_under_the_content_hash() {
+        let (_dir, store) = temp_store();
+        let engine = DeduplicationEngine::new(store.clone())
+            .with_dedup_key(DedupKey::ContentHash)
+            .with_dedup_window(Duration::from_secs(3600));
+        let event = signed_events(1).remove(0);
+        let id = event.id.to_hex();
+        let hash = content_hash(&event).to_hex();
+        assert!(!engine.is_duplicate(&event).await);
+        assert!(engine.is_duplicate(&event).await);
+        assert!(store.last_seen(&hash).await.unwrap().is_some());
+
+        store.delete_event(&id).await.unwrap();
+        engine.forget(&event).await.unwrap();
+
+        assert!(store.get_event(&id).await.unwrap().is_none());
+        assert!(store.last_seen(&hash).await.unwrap().is_none());
+        assert!(!store.content_hash_exists(&hash).await.unwrap());
+        assert!(!engine.is_duplicate(&event).await);
+    }
+
+    #[tokio::test]
+    async fn an_id_seen_again_after_the_window_is_new_and_starts_a_fresh_window() {
+        let (_dir, store) = temp_store();
+        let engine =
+            DeduplicationEngine::new(store.clone()).with_dedup_window(Duration::from_secs(1));
+        let event = signed_events(1).remove(0);
+        let id = event.id.to_hex();
+        assert!(!engine.is_duplicate(&event).await);
+        assert!(engine.is_duplicate(&event).await);
+        let first_seen = store.last_seen(&id).await.unwrap().unwrap();
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(!engine.is_duplicate(&event).await);
+        assert!(store.last_seen(&id).await.unwrap().unwrap() > first_seen);
+        // The refreshed window filters the next copy again
+        assert!(engine.is_duplicate(&event).await);
+    }
+
+    #[tokio::test]
+    async fn checks_during_warmup_are_paced_to_the_startup_rate() {
+        let (_dir, store) = temp_store();
+        let engine =
+            DeduplicationEngine::new(store).with_startup_throttle(20, Duration::from_secs(60));
+        let events = signed_events(10);
+        assert!(engine.get_stats().await.warming_up);
+
+        let started = Instant::now();
+        for event in &events {
+            assert!(!engine.is_duplicate(event).await);
+        }
+        assert!(started.elapsed() >= Duration::from_millis(450));
+    }
+
+    #[tokio::test]
+    async fn each_layer_counts_the_duplicates_it_catches() {
+        let _lock = METRICS_LOCK.lock().await;
+        let metrics = test_support::metrics();
+        let (_dir, store) = temp_store();
+        let engine = DeduplicationEngine::new(store).with_metrics(metrics.clone());
+        let event = signed_events(1).remove(0);
+
+        let before = layer_counts(&metrics);
+        assert!(!engine.is_duplicate(&event).await);
+        assert_eq!(delta(before, layer_counts(&metrics)), [0, 0, 0, 0, 1]);
+
+        // Caught by the hot set before the other layers are consulted
+        let before = layer_counts(&metrics);
+        assert!(engine.is_duplicate(&event).await);
+        assert_eq!(delta(before, layer_counts(&metrics)), [1, 0, 0, 0, 0]);
+
+        // Without the hot set, the bloom positive is confirmed by RocksDB, which fills the LRU
+        engine.hot_set.clear();
+        let before = layer_counts(&metrics);
+        assert!(engine.is_duplicate(&event).await);
+        assert_eq!(delta(before, layer_counts(&metrics)), [0, 0, 1, 1, 0]);
+
+        engine.hot_set.clear();
+        let before = layer_counts(&metrics);
+        assert!(engine.is_duplicate(&event).await);
+        assert_eq!(delta(before, layer_counts(&metrics)), [0, 1, 0, 1, 0]);
+    }
+
+    /// LRU hits, misses and evictions
+    fn lru_counts(m: &Metrics) -> [u64; 3] {
+        let lookups = |r: &str| m.dedup_lru_lookups.with_label_values(&[r]).get();
+        [lookups("hit"), lookups("miss"), m.dedup_lru_evictions.get()]
+    }
+
+    #[tokio::test]
+    async fn the_lru_counts_hits_misses_and_evictions() {
+        let _lock = METRICS_LOCK.lock().await;
+        let metrics = test_support::metrics();
+        let (_dir, store) = temp_store();
+        let engine = DeduplicationEngine::new_with_params(store, 100, 1000, 0.0001, 2)
+            .with_metrics(metrics.clone());
+        let events = signed_events(3);
+        for event in &events {
+            assert!(!engine.is_duplicate(event).await);
+        }
+
+        // Clearing the hot set sends each repeat on to the LRU; RocksDB answers the misses and
+        // the third id pushes the first out of the two-entry cache
+        let before = lru_counts(&metrics);
+        for event in &events {
+            engine.hot_set.clear();
+            assert!(engine.is_duplicate(event).await);
+        }
+        assert_eq!(delta(before, lru_counts(&metrics)), [0, 3, 1]);
+
+        let before = lru_counts(&metrics);
+        engine.hot_set.clear();
+        assert!(engine.is_duplicate(&events[2]).await);
+        assert_eq!(delta(before, lru_counts(&metrics)), [1, 0, 0]);
+
+        let before = lru_counts(&metrics);
+        engine.hot_set.clear();
+        assert!(engine.is_duplicate(&events[0]).await);
+        assert_eq!(delta(before, lru_counts(&metrics)), [0, 1, 1]);
+    }
+
+    #[tokio::test]
+    async fn failing_writes_degrade_dedup_to_the_memory_layers() {
+        let (dir, _primary) = temp_store();
+        // A secondary instance refuses every write, like a primary whose disk has failed
+        let store = Arc::new(
+            RocksDBStore::open_secondary(dir.path().join("db"), dir.path().join("secondary"))
+                .unwrap(),
+        );
+        let engine = DeduplicationEngine::new(store).with_rocksdb_breaker(&CircuitBreakerConfig {
+            failure_threshold: 2,
+            cooldown_secs: 3600,
+        });
+        let events = signed_events(3);
+
+        assert!(!engine.is_duplicate(&events[0]).await);
+        assert!(!engine.is_degraded());
+        assert!(!engine.is_duplicate(&events[1]).await);
+        assert!(engine.is_degraded());
+        assert!(engine.get_stats().await.degraded);
+
+        // New events keep flowing and repeats are still caught in memory
+        assert!(!engine.is_duplicate(&events[2]).await);
+        for event in &events {
+            assert!(engine.is_duplicate(event).await);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_restarted_engine_reloads_the_ids_its_filter_had_seen() {
+        let _lock = METRICS_LOCK.lock().await;
+        let metrics = test_support::metrics();
+        let (dir, store) = temp_store();
+        let path = dir.path().join("dedup.bloom");
+        let event = signed_events(1).remove(0);
+        let before_restart = DeduplicationEngine::new(store.clone());
+        assert!(!before_restart.is_duplicate(&event).await);
+        before_restart.save_bloom(&path).await.unwrap();
+
+        let restarted = DeduplicationEngine::new(store).with_metrics(metrics.clone());
+        assert!(restarted.load_bloom(&path).await.unwrap());
+        let before = layer_counts(&metrics);
+        assert!(restarted.is_duplicate(&event).await);
+        // The reloaded filter already knows the id, so RocksDB only confirms the positive
+        assert_eq!(delta(before, layer_counts(&metrics)), [0, 0, 1, 1, 0]);
+    }
+
+    /// Deduplicate `event` as the router does, then credit its delivery by `relay_url`
+    async fn deliver(engine: &DeduplicationEngine, event: &Event, relay_url: &str) {
+        let is_new = !engine.is_duplicate(event).await;
+        engine.attribute(event, relay_url, is_new);
+    }
+
+    #[tokio::test]
+    async fn a_preferred_relay_takes_credit_within_the_race_window() {
+        let metrics = test_support::metrics();
+        // Labels unique to this test, so other tests cannot move these counters
+        let preferred = normalize_relay_url("wss://preferred.attribution.test").unwrap();
+        let public = normalize_relay_url("wss://public.attribution.test").unwrap();
+        let credited = |url: &str| metrics.relay_attributions.with_label_values(&[url]).get();
+        let (_dir, store) = temp_store();
+        let engine = DeduplicationEngine::new(store)
+            .with_metrics(metrics.clone())
+            .with_relay_attribution(
+                HashMap::from([(preferred.clone(), 10)]),
+                Duration::from_millis(200),
+            );
+        let events = signed_events(2);
+
+        // The public relay is first, but the preferred copy lands inside the window
+        deliver(&engine, &events[0], &public).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        deliver(&engine, &events[0], &preferred).await;
+        tokio::time::sleep(Duration::from_millis(250)).await;
+
+        // A preferred copy arriving after the window closed does not take the credit
+        deliver(&engine, &events[1], &public).await;
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        deliver(&engine, &events[1], &preferred).await;
+
+        // Each window was settled, and counted, by the first delivery after it closed
+        assert_eq!(credited(&preferred), 1);
+        assert_eq!(credited(&public), 1);
+    }
 }
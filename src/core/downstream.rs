@@ -1,136 +1,613 @@
+use crate::api::metrics::Metrics;
+use crate::config::{
+    CircuitBreakerConfig, FileSinkConfig, KafkaConfig, ReconnectPolicy, RestPoolConfig,
+    SinkEndpoint, SinkFailurePolicy,
+};
+use crate::core::event_router::RoutedEvent;
+#[cfg(feature = "kafka")]
+use crate::output::kafka_sink::KafkaSink;
+use crate::output::{file_sink::FileSink, rest_sink::RestSink, tcp_sink::TcpSink};
 use crate::storage::rocksdb_store::RocksDBStore;
 use anyhow::{Context, Result};
 use flume::Receiver;
 use nostr_sdk::Event;
-use serde_json;
+use std::borrow::Cow;
 use std::sync::Arc;
-use tokio::io::AsyncWriteExt;
-use tokio::net::TcpStream;
+use std::time::Duration;
+use tokio::time::Instant;
 use tracing::{error, info};
 
+/// An adaptive batch flushes early once no event has arrived for this fraction of `max_latency`
+const IDLE_FLUSH_DIVISOR: u32 = 4;
+
+/// Sample rates are applied in steps of `1 / SAMPLE_BUCKETS`
+const SAMPLE_BUCKETS: u64 = 1_000_000;
+
 /// Downstream forwarder that can send events via TCP or HTTP to multiple endpoints
 pub struct DownstreamForwarder {
-    tcp_endpoints: Vec<String>,
-    rest_endpoints: Vec<String>,
-    client: Arc<reqwest::Client>,
+    tcp_endpoints: Vec<SinkEndpoint>,
+    rest_endpoints: Vec<SinkEndpoint>,
+    file: Option<FileSinkConfig>,
+    #[cfg_attr(not(feature = "kafka"), allow(dead_code))]
+    kafka: Option<KafkaConfig>,
+    rest_pool: RestPoolConfig,
     rocksdb: Arc<RocksDBStore>,
+    batch_size: usize,
+    /// Largest batch adaptive batching may grow to; `None` keeps batches at `batch_size`
+    max_batch_size: Option<usize>,
+    max_latency: Duration,
+    retry_policy: ReconnectPolicy,
+    failure_policy: SinkFailurePolicy,
+    breaker_config: CircuitBreakerConfig,
+    metrics: Option<Arc<Metrics>>,
 }
 
 impl DownstreamForwarder {
     /// Create a new downstream forwarder
     pub fn new(
-        tcp_endpoints: Vec<String>,
-        rest_endpoints: Vec<String>,
+        tcp_endpoints: Vec<SinkEndpoint>,
+        rest_endpoints: Vec<SinkEndpoint>,
         rocksdb: Arc<RocksDBStore>,
     ) -> Self {
         Self {
             tcp_endpoints,
             rest_endpoints,
-            client: Arc::new(reqwest::Client::new()),
+            file: None,
+            kafka: None,
+            rest_pool: RestPoolConfig::default(),
             rocksdb,
+            batch_size: 100,
+            max_batch_size: None,
+            max_latency: Duration::from_millis(100),
+            retry_policy: ReconnectPolicy::default(),
+            failure_policy: SinkFailurePolicy::default(),
+            breaker_config: CircuitBreakerConfig::default(),
+            metrics: None,
         }
     }
 
-    /// Forward events from a receiver channel
-    pub async fn forward_events(self, rx: Receiver<Event>) -> Result<()> {
-        let tcp_endpoints = self.tcp_endpoints.clone();
-        let rest_endpoints = self.rest_endpoints.clone();
-        let client = self.client.clone();
+    /// Attach metrics collection
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Flush a batch once it reaches `batch_size` events or `max_latency` has elapsed
+    pub fn with_batching(mut self, batch_size: usize, max_latency: Duration) -> Self {
+        self.batch_size = batch_size.max(1);
+        self.max_latency = max_latency;
+        self
+    }
+
+    /// Let batches grow up to `max_batch_size` under high load and flush early when input pauses
+    pub fn with_adaptive_batching(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = Some(max_batch_size.max(self.batch_size));
+        self
+    }
+
+    /// Configure retry backoff and the give-up behaviour for failing sinks
+    pub fn with_sink_policy(
+        mut self,
+        retry_policy: ReconnectPolicy,
+        failure_policy: SinkFailurePolicy,
+    ) -> Self {
+        self.retry_policy = retry_policy;
+        self.failure_policy = failure_policy;
+        self
+    }
+
+    /// Also append events to a rotating local JSONL file
+    pub fn with_file(mut self, config: FileSinkConfig) -> Self {
+        self.file = Some(config);
+        self
+    }
+
+    /// Also publish events to a Kafka topic (only available with the `kafka` feature)
+    pub fn with_kafka(mut self, config: KafkaConfig) -> Self {
+        self.kafka = Some(config);
+        self
+    }
+
+    /// Size the keep-alive connection pool shared by the REST sinks
+    pub fn with_rest_pool(mut self, config: RestPoolConfig) -> Self {
+        self.rest_pool = config;
+        self
+    }
+
+    /// Configure the circuit breaker wrapped around every sink
+    pub fn with_circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.breaker_config = config;
+        self
+    }
+
+    /// Forward events from a receiver channel, recording each event's end-to-end latency once
+    /// every sink has finished with its batch
+    pub async fn forward_events(self, rx: Receiver<RoutedEvent>) -> Result<()> {
+        let mut tcp_sinks: Vec<(TcpSink, Sampler)> = self
+            .tcp_endpoints
+            .iter()
+            .map(|endpoint| {
+                let sink = TcpSink::new(
+                    endpoint.url().to_string(),
+                    self.retry_policy.clone(),
+                    self.failure_policy,
+                )
+                .with_circuit_breaker(&self.breaker_config);
+                let sink = match &self.metrics {
+                    Some(m) => sink.with_metrics(m.clone()),
+                    None => sink,
+                };
+                (sink, Sampler::new(endpoint.sample_rate()))
+            })
+            .collect();
+        // One client for every REST sink so keep-alive connections are pooled and reused
+        let idle_timeout = Duration::from_secs(self.rest_pool.idle_timeout_secs);
+        let client = Arc::new(
+            reqwest::Client::builder()
+                .pool_max_idle_per_host(self.rest_pool.max_idle_per_host)
+                .pool_idle_timeout(idle_timeout)
+                .build()
+                .context("Failed to build HTTP client for REST sinks")?,
+        );
+        let rest_sinks: Vec<(RestSink, Sampler)> = self
+            .rest_endpoints
+            .iter()
+            .map(|endpoint| {
+                let sink = RestSink::new(
+                    endpoint.url().to_string(),
+                    client.clone(),
+                    self.retry_policy.clone(),
+                    self.failure_policy,
+                )
+                .with_circuit_breaker(&self.breaker_config)
+                .with_idle_timeout(idle_timeout);
+                let sink = match &self.metrics {
+                    Some(m) => sink.with_metrics(m.clone()),
+                    None => sink,
+                };
+                (sink, Sampler::new(endpoint.sample_rate()))
+            })
+            .collect();
+        let mut file_sinks: Vec<(FileSink, Sampler)> = Vec::new();
+        if let Some(config) = &self.file {
+            let sink = FileSink::open(config).await?;
+            let sink = match &self.metrics {
+                Some(m) => sink.with_metrics(m.clone()),
+                None => sink,
+            };
+            file_sinks.push((sink, Sampler::new(config.sample_rate)));
+        }
+        #[cfg(feature = "kafka")]
+        let kafka_sinks: Vec<(KafkaSink, Sampler)> = self
+            .kafka
+            .iter()
+            .map(|config| {
+                let sink = KafkaSink::new(config, self.batch_size, self.max_latency)?;
+                let sink = match &self.metrics {
+                    Some(m) => sink.with_metrics(m.clone()),
+                    None => sink,
+                };
+                Ok((sink, Sampler::new(config.sample_rate)))
+            })
+            .collect::<Result<_>>()?;
         let rocksdb = self.rocksdb.clone();
+        let mut batcher = match self.max_batch_size {
+            Some(max) => Batcher::adaptive(self.batch_size, max, self.max_latency),
+            None => Batcher::fixed(self.batch_size, self.max_latency),
+        };
 
         loop {
-            match rx.recv_async().await {
-                Ok(event) => {
-                    let mut all_ok = true;
-                    // Forward to all TCP endpoints in parallel
-                    if !tcp_endpoints.is_empty() {
-                        let mut tcp_tasks = Vec::new();
-                        for endpoint in &tcp_endpoints {
-                            let endpoint = endpoint.clone();
-                            let event = event.clone();
-                            tcp_tasks.push(tokio::spawn(async move {
-                                Self::forward_via_tcp(&endpoint, &event).await
-                            }));
-                        }
-                        // Wait for all TCP forwards to complete (fire and forget errors)
-                        for task in tcp_tasks {
-                            if let Ok(Err(e)) = task.await {
-                                error!("Failed to forward event via TCP: {}", e);
-                                all_ok = false;
-                            }
-                        }
-                    }
+            let routed = batcher.next_batch(&rx).await;
+            if routed.is_empty() {
+                info!("Downstream forwarder: event channel closed");
+                break;
+            }
+            let (batch, received_at): (Vec<Event>, Vec<std::time::Instant>) = routed
+                .into_iter()
+                .map(|routed| (routed.event, routed.received_at))
+                .unzip();
+            if let Some(m) = &self.metrics {
+                m.observe_batch_size(batch.len());
+            }
 
-                    // Forward to all REST endpoints in parallel
-                    if !rest_endpoints.is_empty() {
-                        let mut rest_tasks = Vec::new();
-                        for endpoint in &rest_endpoints {
-                            let endpoint = endpoint.clone();
-                            let event = event.clone();
-                            let client = client.clone();
-                            rest_tasks.push(tokio::spawn(async move {
-                                Self::forward_via_rest(&endpoint, &event, &client).await
-                            }));
-                        }
-                        // Wait for all REST forwards to complete (fire and forget errors)
-                        for task in rest_tasks {
-                            if let Ok(Err(e)) = task.await {
-                                error!("Failed to forward event via REST: {}", e);
-                                all_ok = false;
-                            }
-                        }
-                    }
+            // Forward the batch to all endpoints concurrently
+            let events = batch.as_slice();
+            let tcp_deliveries =
+                futures::future::join_all(tcp_sinks.iter_mut().map(|(sink, sampler)| async move {
+                    let sampled = sampler.sample(events);
+                    let result = if sampled.is_empty() {
+                        Ok(())
+                    } else {
+                        sink.send_batch(&sampled).await
+                    };
+                    (sink.endpoint().to_string(), *sampler, result)
+                }));
+            let rest_deliveries =
+                futures::future::join_all(rest_sinks.iter().map(|(sink, sampler)| async move {
+                    let sampled = sampler.sample(events);
+                    let result = if sampled.is_empty() {
+                        Ok(())
+                    } else {
+                        sink.send_batch(&sampled).await
+                    };
+                    (sink.endpoint().to_string(), *sampler, result)
+                }));
+            let file_deliveries = futures::future::join_all(file_sinks.iter_mut().map(
+                |(sink, sampler)| async move {
+                    let sampled = sampler.sample(events);
+                    let result = if sampled.is_empty() {
+                        Ok(())
+                    } else {
+                        sink.send_batch(&sampled).await
+                    };
+                    (sink.endpoint().to_string(), *sampler, result)
+                },
+            ));
+            #[cfg(feature = "kafka")]
+            let kafka_deliveries =
+                futures::future::join_all(kafka_sinks.iter().map(|(sink, sampler)| async move {
+                    let sampled = sampler.sample(events);
+                    let result = if sampled.is_empty() {
+                        Ok(())
+                    } else {
+                        sink.send_batch(&sampled).await
+                    };
+                    (sink.endpoint().to_string(), *sampler, result)
+                }));
+            #[cfg(not(feature = "kafka"))]
+            let kafka_deliveries = async { Vec::<(String, Sampler, Result<()>)>::new() };
+            let (tcp_results, rest_results, file_results, kafka_results) = tokio::join!(
+                tcp_deliveries,
+                rest_deliveries,
+                file_deliveries,
+                kafka_deliveries
+            );
 
-                    if all_ok {
-                        if let Err(e) = rocksdb.mark_forward_success(&event.id.to_hex()).await {
-                            error!("Failed to mark forward success: {}", e);
-                        }
+            // An event counts as forwarded once every sink that samples it has it
+            let mut delivered = vec![true; batch.len()];
+            for (endpoint, sampler, result) in tcp_results
+                .into_iter()
+                .chain(rest_results)
+                .chain(file_results)
+                .chain(kafka_results)
+            {
+                if let Err(e) = result {
+                    error!("Failed to forward batch to {}: {:#}", endpoint, e);
+                    for (delivered, event) in delivered.iter_mut().zip(&batch) {
+                        *delivered &= !sampler.keeps(event);
                     }
                 }
-                Err(_) => {
-                    info!("Downstream forwarder: event channel closed");
-                    break;
+            }
+            if let Some(m) = &self.metrics {
+                for received_at in received_at {
+                    m.observe_end_to_end_latency(received_at.elapsed().as_secs_f64());
                 }
             }
+
+            let ids: Vec<String> = batch
+                .iter()
+                .zip(delivered)
+                .filter(|(_, delivered)| *delivered)
+                .map(|(event, _)| event.id.to_hex())
+                .collect();
+            if let Err(e) = rocksdb.mark_forward_success_many(&ids).await {
+                error!("Failed to mark forward success: {}", e);
+            }
         }
 
         Ok(())
     }
+}
 
-    /// Forward a single event via TCP
-    async fn forward_via_tcp(endpoint: &str, event: &Event) -> Result<()> {
-        let mut stream = TcpStream::connect(endpoint)
-            .await
-            .with_context(|| format!("Failed to connect to TCP endpoint: {}", endpoint))?;
+/// Deterministic per-sink sampling. Event ids are SHA-256 digests, so an id's leading bytes
+/// modulo `SAMPLE_BUCKETS` are uniformly distributed and the same event is kept or skipped on
+/// every run.
+#[derive(Clone, Copy)]
+struct Sampler {
+    /// Events whose bucket falls below this are kept; `None` keeps everything
+    threshold: Option<u64>,
+}
+
+impl Sampler {
+    fn new(rate: Option<f64>) -> Self {
+        let threshold = rate
+            .filter(|rate| *rate < 1.0)
+            .map(|rate| (rate * SAMPLE_BUCKETS as f64).round() as u64);
+        Self { threshold }
+    }
 
-        let serialized = serde_json::to_vec(event).context("Failed to serialize event to JSON")?;
+    fn keeps(&self, event: &Event) -> bool {
+        let Some(threshold) = self.threshold else {
+            return true;
+        };
+        let id = event.id.as_bytes();
+        let prefix: [u8; 8] = std::array::from_fn(|i| id[i]);
+        u64::from_le_bytes(prefix) % SAMPLE_BUCKETS < threshold
+    }
 
-        // Send length prefix (4 bytes) + data
-        let len = serialized.len() as u32;
-        stream.write_all(&len.to_be_bytes()).await?;
-        stream.write_all(&serialized).await?;
-        stream.flush().await?;
+    /// The events of a batch this sink should receive, borrowing the batch when unsampled
+    fn sample<'a>(&self, events: &'a [Event]) -> Cow<'a, [Event]> {
+        if self.threshold.is_none() {
+            return Cow::Borrowed(events);
+        }
+        Cow::Owned(events.iter().filter(|e| self.keeps(e)).cloned().collect())
+    }
+}
 
-        Ok(())
+/// Groups events into batches for the forwarder.
+/// A fixed batcher always targets `batch_size`. An adaptive one doubles its target (up to the
+/// maximum) whenever a batch fills before the latency deadline, halves it (down to `batch_size`)
+/// when a batch comes back less than half full, and flushes early when input pauses.
+struct Batcher {
+    min_size: usize,
+    max_size: usize,
+    target: usize,
+    max_latency: Duration,
+    adaptive: bool,
+}
+
+impl Batcher {
+    fn fixed(batch_size: usize, max_latency: Duration) -> Self {
+        Self {
+            min_size: batch_size,
+            max_size: batch_size,
+            target: batch_size,
+            max_latency,
+            adaptive: false,
+        }
+    }
+
+    fn adaptive(batch_size: usize, max_batch_size: usize, max_latency: Duration) -> Self {
+        Self {
+            min_size: batch_size,
+            max_size: max_batch_size,
+            target: batch_size,
+            max_latency,
+            adaptive: true,
+        }
+    }
+
+    /// Collect up to the current target, waiting at most `max_latency` after the first event.
+    /// Returns an empty batch once the channel is closed and drained.
+    async fn next_batch(&mut self, rx: &Receiver<RoutedEvent>) -> Vec<RoutedEvent> {
+        let mut batch = Vec::with_capacity(self.target);
+        match rx.recv_async().await {
+            Ok(event) => batch.push(event),
+            Err(_) => return batch,
+        }
+
+        let deadline = Instant::now() + self.max_latency;
+        let idle_gap = self.max_latency / IDLE_FLUSH_DIVISOR;
+        while batch.len() < self.target {
+            let wait_until = if self.adaptive {
+                deadline.min(Instant::now() + idle_gap)
+            } else {
+                deadline
+            };
+            match tokio::time::timeout_at(wait_until, rx.recv_async()).await {
+                Ok(Ok(event)) => batch.push(event),
+                // Channel closed, input paused or latency budget exhausted
+                Ok(Err(_)) | Err(_) => break,
+            }
+        }
+
+        if self.adaptive {
+            self.adjust(batch.len());
+        }
+        batch
     }
 
-    /// Forward a single event via HTTP REST
-    async fn forward_via_rest(
-        endpoint: &str,
-        event: &Event,
-        client: &reqwest::Client,
-    ) -> Result<()> {
-        let response = client
-            .post(endpoint)
-            .json(event)
-            .send()
+    /// Resize the target from how full the last batch got
+    fn adjust(&mut self, filled: usize) {
+        if filled >= self.target {
+            self.target = self.target.saturating_mul(2).min(self.max_size);
+        } else if filled < self.target / 2 {
+            self.target = (self.target / 2).max(self.min_size);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, signed_events, temp_store};
+
+    /// A forwarder writing every event to a JSONL file in `dir`
+    fn file_forwarder(dir: &std::path::Path, rocksdb: Arc<RocksDBStore>) -> DownstreamForwarder {
+        DownstreamForwarder::new(Vec::new(), Vec::new(), rocksdb)
+            .with_batching(10, Duration::from_millis(20))
+            .with_file(FileSinkConfig {
+                path: dir.join("events.jsonl").to_string_lossy().into_owned(),
+                rotate_size_mb: None,
+                rotate_daily: false,
+                gzip: false,
+                sample_rate: None,
+            })
+    }
+
+    #[tokio::test]
+    async fn latency_is_recorded_once_the_sinks_have_the_event() {
+        let (dir, store) = temp_store();
+        let metrics = test_support::metrics();
+        let latency = &metrics.end_to_end_latency;
+        let (count, sum) = (latency.get_sample_count(), latency.get_sample_sum());
+
+        let (tx, rx) = flume::unbounded();
+        let received_at = std::time::Instant::now() - Duration::from_secs(2);
+        for event in signed_events(3) {
+            tx.send(RoutedEvent { event, received_at }).unwrap();
+        }
+        drop(tx);
+        file_forwarder(dir.path(), store)
+            .with_metrics(metrics.clone())
+            .forward_events(rx)
             .await
-            .with_context(|| format!("Failed to send POST request to {}", endpoint))?;
+            .unwrap();
 
-        if !response.status().is_success() {
-            anyhow::bail!("REST endpoint returned error status: {}", response.status());
+        // Other tests share the histogram, so only a lower bound can be checked
+        assert!(latency.get_sample_count() >= count + 3);
+        assert!(latency.get_sample_sum() - sum >= 6.0);
+    }
+
+    #[tokio::test]
+    async fn every_event_of_a_delivered_batch_is_marked_forwarded() {
+        let (dir, store) = temp_store();
+        let events = signed_events(3);
+
+        let (tx, rx) = flume::unbounded();
+        for event in events.clone() {
+            tx.send(routed(event)).unwrap();
         }
+        drop(tx);
+        file_forwarder(dir.path(), store.clone())
+            .forward_events(rx)
+            .await
+            .unwrap();
 
-        Ok(())
+        for event in &events {
+            assert!(store.is_forward_success(&event.id.to_hex()).await);
+        }
+    }
+
+    fn routed(event: Event) -> RoutedEvent {
+        RoutedEvent {
+            event,
+            received_at: std::time::Instant::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn an_adaptive_batcher_grows_under_load_and_shrinks_when_input_pauses() {
+        let mut batcher = Batcher::adaptive(2, 8, Duration::from_millis(200));
+        let (tx, rx) = flume::unbounded();
+        for event in signed_events(22) {
+            tx.send(routed(event)).unwrap();
+        }
+
+        // A backlog fills every batch, doubling the target up to the maximum
+        let mut sizes = Vec::new();
+        for _ in 0..4 {
+            sizes.push(batcher.next_batch(&rx).await.len());
+        }
+        assert_eq!(sizes, vec![2, 4, 8, 8]);
+        assert_eq!(batcher.target, 8);
+
+        // A lone event is flushed once input pauses, well before the latency deadline,
+        // and each nearly empty batch halves the target back down to the minimum
+        for expected in [4, 2, 2] {
+            tx.send(routed(signed_events(1).remove(0))).unwrap();
+            let started = Instant::now();
+            assert_eq!(batcher.next_batch(&rx).await.len(), 1);
+            assert!(started.elapsed() < Duration::from_millis(200));
+            assert_eq!(batcher.target, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_fixed_batcher_keeps_its_size() {
+        let mut batcher = Batcher::fixed(3, Duration::from_millis(20));
+        let (tx, rx) = flume::unbounded();
+        for event in signed_events(7) {
+            tx.send(routed(event)).unwrap();
+        }
+        drop(tx);
+
+        let mut sizes = Vec::new();
+        loop {
+            let batch = batcher.next_batch(&rx).await;
+            if batch.is_empty() {
+                break;
+            }
+            sizes.push(batch.len());
+        }
+        assert_eq!(sizes, vec![3, 3, 1]);
+    }
+
+    #[test]
+    fn sampling_keeps_about_the_configured_share_of_events() {
+        let events = signed_events(2000);
+        let sampler = Sampler::new(Some(0.25));
+
+        let kept = events.iter().filter(|event| sampler.keeps(event)).count();
+        assert!((400..=600).contains(&kept), "kept {} of 2000", kept);
+        assert_eq!(sampler.sample(&events).len(), kept);
+        assert!(events.iter().all(|event| Sampler::new(None).keeps(event)));
+        assert!(
+            events
+                .iter()
+                .all(|event| Sampler::new(Some(1.0)).keeps(event))
+        );
+        assert!(
+            !events
+                .iter()
+                .any(|event| Sampler::new(Some(0.0)).keeps(event))
+        );
+    }
+
+    #[test]
+    fn sampling_picks_the_same_events_every_time() {
+        let events = signed_events(200);
+        let picks = |sampler: Sampler| -> Vec<bool> {
+            events.iter().map(|event| sampler.keeps(event)).collect()
+        };
+
+        assert_eq!(
+            picks(Sampler::new(Some(0.5))),
+            picks(Sampler::new(Some(0.5)))
+        );
+        // A lower rate keeps a subset of what a higher one keeps
+        let half = picks(Sampler::new(Some(0.5)));
+        let tenth = picks(Sampler::new(Some(0.1)));
+        assert!(tenth.iter().zip(&half).all(|(tenth, half)| !tenth || *half));
+    }
+
+    #[tokio::test]
+    async fn a_failing_sink_only_holds_back_the_events_it_samples() {
+        let (dir, store) = temp_store();
+        let failing = test_support::serve(axum::Router::new().route(
+            "/",
+            axum::routing::post(|| async { axum::http::StatusCode::INTERNAL_SERVER_ERROR }),
+        ))
+        .await;
+        let sample_rate = Some(0.5);
+        let events = signed_events(20);
+
+        let (tx, rx) = flume::unbounded();
+        for event in events.clone() {
+            tx.send(routed(event)).unwrap();
+        }
+        drop(tx);
+        let rest = SinkEndpoint::Detailed {
+            url: failing,
+            sample_rate,
+        };
+        let retry = ReconnectPolicy {
+            base_delay_ms: 1,
+            max_delay_ms: 1,
+            max_retries: 1,
+        };
+        DownstreamForwarder::new(Vec::new(), vec![rest], store.clone())
+            .with_batching(20, Duration::from_millis(20))
+            .with_sink_policy(retry, SinkFailurePolicy::Drop)
+            .with_file(FileSinkConfig {
+                path: dir
+                    .path()
+                    .join("events.jsonl")
+                    .to_string_lossy()
+                    .into_owned(),
+                rotate_size_mb: None,
+                rotate_daily: false,
+                gzip: false,
+                sample_rate: None,
+            })
+            .forward_events(rx)
+            .await
+            .unwrap();
+
+        let sampler = Sampler::new(sample_rate);
+        for event in &events {
+            let forwarded = store.is_forward_success(&event.id.to_hex()).await;
+            assert_eq!(forwarded, !sampler.keeps(event));
+        }
     }
 }
@@ -1,19 +1,36 @@
 use anyhow::Result;
-use flume::{Receiver, Sender};
+use flume::{Receiver, Sender, TrySendError};
 use nostr_sdk::Event;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, trace};
 
 use crate::api::metrics::Metrics;
+use crate::config::OverflowPolicy;
 use crate::core::dedupe_engine::DeduplicationEngine;
+use crate::core::relay_pool::{InFlightGuard, RelayEvent};
+use crate::core::shutdown::Shutdown;
+
+/// Number of times per reorder window that held events are checked for release
+const REORDER_CHECKS_PER_WINDOW: u32 = 4;
+
+/// An event on its way to the outputs, stamped with when its relay delivered it so the
+/// output that finally sends it can record the end-to-end latency
+#[derive(Debug, Clone)]
+pub struct RoutedEvent {
+    pub event: Event,
+    pub received_at: Instant,
+}
 
 /// Wrapper for Event to enable sorting by timestamp
 #[derive(Clone)]
 struct EventWrapper {
     event: Event,
     timestamp: u64,
+    received_at: Instant,
+    /// Released once the event has been emitted downstream
+    _in_flight: InFlightGuard,
 }
 
 impl PartialEq for EventWrapper {
@@ -41,9 +58,15 @@ pub struct EventRouter {
     dedupe_engine: Arc<DeduplicationEngine>,
     batch_size: usize,
     max_latency: Duration,
-    downstream_tx: Sender<Event>,
+    downstream_tx: Sender<RoutedEvent>,
+    overflow_policy: OverflowPolicy,
+    /// Receiving side of the output queue, used to evict the oldest event under `DropOldest`
+    overflow_rx: Option<Receiver<RoutedEvent>>,
     pending_events: Arc<RwLock<Vec<EventWrapper>>>,
+    /// Hold events this long and release them sorted by `created_at` (batching by size when unset)
+    reorder_window: Option<Duration>,
     metrics: Option<Arc<Metrics>>,
+    shutdown: Option<Shutdown>,
 }
 
 impl EventRouter {
@@ -52,15 +75,19 @@ impl EventRouter {
         dedupe_engine: Arc<DeduplicationEngine>,
         batch_size: usize,
         max_latency: Duration,
-        downstream_tx: Sender<Event>,
+        downstream_tx: Sender<RoutedEvent>,
     ) -> Self {
         Self {
             dedupe_engine,
             batch_size,
             max_latency,
             downstream_tx,
+            overflow_policy: OverflowPolicy::Block,
+            overflow_rx: None,
             pending_events: Arc::new(RwLock::new(Vec::new())),
+            reorder_window: None,
             metrics: None,
+            shutdown: None,
         }
     }
 
@@ -70,27 +97,77 @@ impl EventRouter {
         self
     }
 
+    /// Set how a full output queue is handled; `queue_rx` is the queue's receiving side
+    pub fn with_overflow_policy(
+        mut self,
+        policy: OverflowPolicy,
+        queue_rx: Receiver<RoutedEvent>,
+    ) -> Self {
+        self.overflow_policy = policy;
+        self.overflow_rx = Some(queue_rx);
+        self
+    }
+
+    /// Hold every event for up to `window` so events from different relays leave in
+    /// `created_at` order. Best-effort: an event arriving after a later one was released
+    /// is still emitted, out of order.
+    pub fn with_reorder_window(mut self, window: Duration) -> Self {
+        self.reorder_window = Some(window);
+        self
+    }
+
+    /// Stop processing and flush pending events once shutdown is triggered
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// Resolve when shutdown is triggered; never resolves without a coordinator
+    async fn wait_for_shutdown(&self) {
+        match &self.shutdown {
+            Some(shutdown) => shutdown.wait().await,
+            None => std::future::pending().await,
+        }
+    }
+
     /// Process incoming event stream, deduplicate, and route to downstream
-    pub async fn process_stream(self, input: Receiver<Event>) -> Result<()> {
+    pub async fn process_stream(self, input: Receiver<RelayEvent>) -> Result<()> {
         let mut last_flush = Instant::now();
+        let tick = match self.reorder_window {
+            Some(window) => (window / REORDER_CHECKS_PER_WINDOW).max(Duration::from_millis(1)),
+            None => self.max_latency,
+        };
 
         loop {
             // Use timeout to periodically flush even if no new events arrive
-            let timeout = tokio::time::sleep(self.max_latency);
+            let timeout = tokio::time::sleep(tick);
             tokio::pin!(timeout);
 
             tokio::select! {
                 // Receive new event
                 result = input.recv_async() => {
                     match result {
-                        Ok(event) => {
+                        Ok(RelayEvent {
+                            relay_url,
+                            event,
+                            received_at,
+                            in_flight,
+                        }) => {
                             // Deduplication check
-                            if !self.dedupe_engine.is_duplicate(&event).await {
+                            let duplicate = self.dedupe_engine.is_duplicate(&event).await;
+                            self.dedupe_engine.attribute(&event, &relay_url, !duplicate);
+                            if duplicate {
+                                if let Some(m) = &self.metrics {
+                                    m.record_relay_duplicate(&relay_url);
+                                }
+                            } else {
                                 // Add to pending events (will be sorted before flushing)
                                 let timestamp = event.created_at.as_secs();
                                 let wrapper = EventWrapper {
                                     event,
                                     timestamp,
+                                    received_at,
+                                    _in_flight: in_flight,
                                 };
 
                                 let mut pending = self.pending_events.write().await;
@@ -99,8 +176,11 @@ impl EventRouter {
                                     m.events_in_queue.set(pending.len() as f64);
                                 }
 
-                                // If we have enough events, flush a batch
-                                if pending.len() >= self.batch_size {
+                                // If we have enough events, flush a batch (the reorder
+                                // window releases events on the timer instead)
+                                if self.reorder_window.is_none()
+                                    && pending.len() >= self.batch_size
+                                {
                                     drop(pending);
                                     self.flush_batch().await?;
                                     last_flush = Instant::now();
@@ -114,8 +194,18 @@ impl EventRouter {
                         }
                     }
                 }
+                // Shutdown - flush everything still pending and stop
+                _ = self.wait_for_shutdown() => {
+                    info!("Shutdown requested, flushing remaining events");
+                    self.flush_all().await?;
+                    break;
+                }
                 // Timeout - flush if we have events and enough time has passed
                 _ = timeout => {
+                    if let Some(window) = self.reorder_window {
+                        self.flush_reordered(window).await?;
+                        continue;
+                    }
                     let pending = self.pending_events.read().await;
                     if !pending.is_empty() && last_flush.elapsed() >= self.max_latency {
                         drop(pending);
@@ -147,22 +237,12 @@ impl EventRouter {
         pending.sort();
 
         // Take the oldest events (first batch_size events)
-        let batch: Vec<Event> = pending
-            .drain(0..batch_size)
-            .map(|wrapper| wrapper.event)
-            .collect();
+        let batch: Vec<EventWrapper> = pending.drain(0..batch_size).collect();
 
         drop(pending);
 
         // Send events to downstream in timestamp order
-        for event in batch {
-            if let Err(e) = self.downstream_tx.send_async(event).await {
-                error!("Failed to send event to downstream: {}", e);
-            }
-            if let Some(m) = &self.metrics {
-                m.events_processed.inc();
-            }
-        }
+        self.emit(batch).await;
 
         debug!("Flushed batch of {} events", batch_size);
         if let Some(m) = &self.metrics {
@@ -172,24 +252,112 @@ impl EventRouter {
         Ok(())
     }
 
-    /// Flush all remaining events
-    async fn flush_all(&self) -> Result<()> {
+    /// Release, in `created_at` order, every held event up to the newest timestamp among the
+    /// events that have waited out the reorder window
+    async fn flush_reordered(&self, window: Duration) -> Result<()> {
         let mut pending = self.pending_events.write().await;
-        let count = pending.len();
+        let cutoff = pending
+            .iter()
+            .filter(|wrapper| wrapper.received_at.elapsed() >= window)
+            .map(|wrapper| wrapper.timestamp)
+            .max();
+        let Some(cutoff) = cutoff else {
+            return Ok(());
+        };
 
-        // Sort by timestamp before flushing
+        // Stable sort keeps arrival order among events with the same timestamp
         pending.sort();
+        let ready = pending.partition_point(|wrapper| wrapper.timestamp <= cutoff);
+        let batch: Vec<EventWrapper> = pending.drain(..ready).collect();
+        let remaining = pending.len();
+        drop(pending);
 
-        let events: Vec<Event> = pending.drain(..).map(|wrapper| wrapper.event).collect();
+        self.emit(batch).await;
 
-        for event in events {
-            if let Err(e) = self.downstream_tx.send_async(event).await {
-                error!("Failed to send event to downstream: {}", e);
+        debug!("Released {} reordered events", ready);
+        if let Some(m) = &self.metrics {
+            m.events_in_queue.set(remaining as f64);
+        }
+        Ok(())
+    }
+
+    /// Queue events for the outputs in order. End-to-end latency is recorded by the output
+    /// that sends them, not here.
+    async fn emit(&self, events: Vec<EventWrapper>) {
+        for wrapper in events {
+            let event_id = wrapper.event.id;
+            let routed = RoutedEvent {
+                event: wrapper.event,
+                received_at: wrapper.received_at,
+            };
+            if !self.send_downstream(routed).await {
+                continue;
             }
+            trace!(event_id = %event_id, "Event queued downstream");
             if let Some(m) = &self.metrics {
                 m.events_processed.inc();
             }
         }
+    }
+
+    /// Queue an event for the outputs, applying the overflow policy when the queue is full.
+    /// Returns whether the event was queued.
+    async fn send_downstream(&self, event: RoutedEvent) -> bool {
+        let mut event = event;
+        loop {
+            let full = match self.overflow_policy {
+                OverflowPolicy::Block => match self.downstream_tx.send_async(event).await {
+                    Ok(()) => return true,
+                    Err(e) => {
+                        error!("Failed to send event to downstream: {}", e);
+                        return false;
+                    }
+                },
+                _ => match self.downstream_tx.try_send(event) {
+                    Ok(()) => return true,
+                    Err(TrySendError::Full(returned)) => returned,
+                    Err(TrySendError::Disconnected(_)) => {
+                        error!("Failed to send event to downstream: channel closed");
+                        return false;
+                    }
+                },
+            };
+
+            match (self.overflow_policy, &self.overflow_rx) {
+                (OverflowPolicy::DropOldest, Some(queue_rx)) => {
+                    // Make room and retry; an empty queue means the consumer just caught up
+                    if queue_rx.try_recv().is_ok() {
+                        self.record_dropped();
+                    }
+                    event = full;
+                }
+                _ => {
+                    self.record_dropped();
+                    return false;
+                }
+            }
+        }
+    }
+
+    fn record_dropped(&self) {
+        trace!("Output queue full, dropping event");
+        if let Some(m) = &self.metrics {
+            m.events_dropped.inc();
+        }
+    }
+
+    /// Flush all remaining events
+    async fn flush_all(&self) -> Result<()> {
+        let mut pending = self.pending_events.write().await;
+        let count = pending.len();
+
+        // Sort by timestamp before flushing
+        pending.sort();
+
+        let events: Vec<EventWrapper> = pending.drain(..).collect();
+        drop(pending);
+
+        self.emit(events).await;
 
         info!("Flushed all remaining {} events", count);
         if let Some(m) = &self.metrics {
@@ -198,3 +366,145 @@ impl EventRouter {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{signed_event, temp_store};
+    use nostr_sdk::{EventBuilder, Keys, Timestamp};
+    use std::collections::HashSet;
+    use tempfile::TempDir;
+
+    const NOW: u64 = 1_700_000_000;
+
+    /// A router emitting every event as soon as it is accepted into an output queue of
+    /// `capacity`, with the queue's receiving side
+    fn router(
+        policy: OverflowPolicy,
+        capacity: usize,
+    ) -> (EventRouter, Receiver<RoutedEvent>, TempDir) {
+        let (dir, store) = temp_store();
+        let dedupe = Arc::new(DeduplicationEngine::new(store));
+        let (tx, rx) = flume::bounded(capacity);
+        let router = EventRouter::new(dedupe, 1, Duration::from_secs(3600), tx)
+            .with_overflow_policy(policy, rx.clone());
+        (router, rx, dir)
+    }
+
+    /// Notes created one second apart from `NOW`, in the given order of offsets
+    fn notes(offsets: &[u64]) -> Vec<Event> {
+        offsets
+            .iter()
+            .map(|offset| signed_event(&format!("note {}", offset), Timestamp::from(NOW + offset)))
+            .collect()
+    }
+
+    /// An input stream delivering `events` in order, then closing
+    fn input(events: &[Event]) -> Receiver<RelayEvent> {
+        let (tx, rx) = flume::unbounded();
+        for event in events {
+            tx.send(RelayEvent::received("ws://relay.test", event.clone()))
+                .unwrap();
+        }
+        rx
+    }
+
+    fn emitted(output: &Receiver<RoutedEvent>) -> Vec<Event> {
+        output.drain().map(|routed| routed.event).collect()
+    }
+
+    #[tokio::test]
+    async fn drop_newest_keeps_the_queued_events() {
+        let events = notes(&[0, 1, 2, 3, 4]);
+        let (router, output, _dir) = router(OverflowPolicy::DropNewest, 2);
+
+        router.process_stream(input(&events)).await.unwrap();
+
+        assert_eq!(emitted(&output), events[..2]);
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_keeps_the_latest_events() {
+        let events = notes(&[0, 1, 2, 3, 4]);
+        let (router, output, _dir) = router(OverflowPolicy::DropOldest, 2);
+
+        router.process_stream(input(&events)).await.unwrap();
+
+        assert_eq!(emitted(&output), events[3..]);
+    }
+
+    #[tokio::test]
+    async fn block_waits_for_room_and_loses_nothing() {
+        let events = notes(&[0, 1, 2, 3, 4]);
+        let (router, output, _dir) = router(OverflowPolicy::Block, 2);
+        let stream = input(&events);
+
+        let routing = tokio::spawn(router.process_stream(stream));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!routing.is_finished());
+        assert_eq!(output.len(), 2);
+
+        let mut received = Vec::new();
+        while let Ok(routed) = output.recv_async().await {
+            received.push(routed.event);
+        }
+        routing.await.unwrap().unwrap();
+        assert_eq!(received, events);
+    }
+
+    #[tokio::test]
+    async fn the_reorder_window_releases_events_in_created_at_order() {
+        let events = notes(&[0, 1, 2, 3]);
+        let (router, output, _dir) = router(OverflowPolicy::Block, 16);
+        let router = router.with_reorder_window(Duration::from_millis(300));
+        let (tx, stream) = flume::unbounded();
+        let routing = tokio::spawn(router.process_stream(stream));
+
+        for event in [&events[2], &events[0], &events[1]] {
+            tx.send(RelayEvent::received("ws://relay.test", event.clone()))
+                .unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(output.is_empty(), "events released before the window ended");
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(emitted(&output), events[..3]);
+
+        // A straggler older than what was already released still goes out, late
+        tx.send(RelayEvent::received("ws://relay.test", events[3].clone()))
+            .unwrap();
+        let late = signed_event("straggler", Timestamp::from(NOW));
+        tx.send(RelayEvent::received("ws://relay.test", late.clone()))
+            .unwrap();
+        drop(tx);
+        routing.await.unwrap().unwrap();
+        assert_eq!(emitted(&output), vec![late, events[3].clone()]);
+    }
+
+    #[tokio::test]
+    async fn a_bypassed_author_is_forwarded_every_time_and_still_stored() {
+        let trusted = Keys::generate();
+        let note = |keys: &Keys, content: &str| {
+            EventBuilder::text_note(content)
+                .sign_with_keys(keys)
+                .unwrap()
+        };
+        let bypassed = note(&trusted, "from our own service");
+        let other = note(&Keys::generate(), "from anyone else");
+
+        let (_dir, store) = temp_store();
+        let dedupe = DeduplicationEngine::new(store.clone())
+            .with_bypass_pubkeys(HashSet::from([trusted.public_key()]));
+        let (tx, output) = flume::unbounded();
+        let router = EventRouter::new(Arc::new(dedupe), 1, Duration::from_secs(3600), tx);
+
+        let stream = [&bypassed, &other, &bypassed, &other, &bypassed].map(Event::clone);
+        router.process_stream(input(&stream)).await.unwrap();
+
+        let forwarded = emitted(&output);
+        assert_eq!(forwarded.iter().filter(|e| **e == bypassed).count(), 3);
+        assert_eq!(forwarded.iter().filter(|e| **e == other).count(), 1);
+        let stored = store.get_event(&bypassed.id.to_hex()).await.unwrap();
+        assert_eq!(stored, Some(bypassed));
+    }
+}
@@ -1,5 +1,11 @@
+pub mod attribution;
 pub mod dedupe_engine;
 pub mod downstream;
 pub mod event_router;
+pub mod rate_limit;
+pub mod relay_health;
+pub mod relay_info;
 pub mod relay_pool;
+pub mod shutdown;
+pub mod warmup;
 
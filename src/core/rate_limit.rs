@@ -0,0 +1,39 @@
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Token bucket admitting `rate` events per second on average, with bursts of up to one
+/// second's worth. Callers that are refused a token drop the event instead of waiting.
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    /// Tokens available and when they were last refilled
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    /// A full bucket refilling at `rate` tokens per second
+    pub fn new(rate: u32) -> Self {
+        let rate = f64::from(rate.max(1));
+        Self {
+            rate,
+            capacity: rate,
+            state: Mutex::new((rate, Instant::now())),
+        }
+    }
+
+    /// Take a token if one is available
+    pub fn try_acquire(&self) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let (tokens, last_refill) = &mut *state;
+        let now = Instant::now();
+        let refilled = now.duration_since(*last_refill).as_secs_f64() * self.rate;
+        *tokens = (*tokens + refilled).min(self.capacity);
+        *last_refill = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::time::Instant;
+
+use crate::config::HealthPolicy;
+
+/// Weight of the connection-uptime component in a health sample
+const UPTIME_WEIGHT: f64 = 0.4;
+/// Weight of the event-throughput component in a health sample
+const THROUGHPUT_WEIGHT: f64 = 0.4;
+/// Weight of the error-rate component in a health sample
+const ERROR_WEIGHT: f64 = 0.2;
+/// Smoothing factor applied to new samples (exponentially weighted moving average)
+const SMOOTHING: f64 = 0.5;
+
+/// Rolling health score for a single relay connection
+pub struct RelayHealth {
+    events_since_check: AtomicU64,
+    errors_since_check: AtomicU64,
+    /// When the tracker was created; event times are stored as offsets from it
+    created_at: Instant,
+    /// Milliseconds after `created_at` of the last event (0 until one arrives)
+    last_event_ms: AtomicU64,
+    state: RwLock<HealthState>,
+}
+
+struct HealthState {
+    score: f64,
+    consecutive_low: u32,
+}
+
+impl RelayHealth {
+    /// Create a health tracker starting from a perfect score
+    pub fn new() -> Self {
+        Self {
+            events_since_check: AtomicU64::new(0),
+            errors_since_check: AtomicU64::new(0),
+            created_at: Instant::now(),
+            last_event_ms: AtomicU64::new(0),
+            state: RwLock::new(HealthState {
+                score: 1.0,
+                consecutive_low: 0,
+            }),
+        }
+    }
+
+    /// Count an event received from the relay
+    pub fn record_event(&self) {
+        self.events_since_check.fetch_add(1, Ordering::Relaxed);
+        let offset = self.created_at.elapsed().as_millis() as u64;
+        self.last_event_ms.fetch_max(offset, Ordering::Relaxed);
+    }
+
+    /// Time since the last event, or since the relay joined the pool if none has arrived yet
+    pub fn since_last_event(&self) -> Duration {
+        let last = Duration::from_millis(self.last_event_ms.load(Ordering::Relaxed));
+        self.created_at.elapsed().saturating_sub(last)
+    }
+
+    /// Count a connection or delivery error for the relay
+    pub fn record_error(&self) {
+        self.errors_since_check.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Current health score in `[0.0, 1.0]`
+    pub async fn score(&self) -> f64 {
+        self.state.read().await.score
+    }
+
+    /// Fold the activity since the last check into the score.
+    /// Returns the new score and whether the relay has been below the eviction
+    /// threshold for the configured number of consecutive checks.
+    pub async fn evaluate(
+        &self,
+        connected: bool,
+        elapsed: Duration,
+        policy: &HealthPolicy,
+    ) -> (f64, bool) {
+        let events = self.events_since_check.swap(0, Ordering::Relaxed);
+        let errors = self.errors_since_check.swap(0, Ordering::Relaxed);
+
+        let uptime = if connected { 1.0 } else { 0.0 };
+        let minutes = (elapsed.as_secs_f64() / 60.0).max(f64::EPSILON);
+        let events_per_minute = events as f64 / minutes;
+        let throughput = if policy.target_events_per_minute > 0.0 {
+            (events_per_minute / policy.target_events_per_minute).min(1.0)
+        } else {
+            1.0
+        };
+        let error_rate = errors as f64 / (events + errors).max(1) as f64;
+        let sample = UPTIME_WEIGHT * uptime
+            + THROUGHPUT_WEIGHT * throughput
+            + ERROR_WEIGHT * (1.0 - error_rate);
+
+        let mut state = self.state.write().await;
+        state.score = (1.0 - SMOOTHING) * state.score + SMOOTHING * sample;
+        if state.score < policy.eviction_threshold {
+            state.consecutive_low += 1;
+        } else {
+            state.consecutive_low = 0;
+        }
+        (
+            state.score,
+            state.consecutive_low >= policy.consecutive_checks,
+        )
+    }
+}
+
+impl Default for RelayHealth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,113 @@
+use anyhow::{Context, Result};
+use dashmap::DashMap;
+use nostr_sdk::Url;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Upper bound on a single NIP-11 request
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// NIP-11 relay information document (fields a relay omits are left empty)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RelayInformation {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub pubkey: Option<String>,
+    #[serde(default)]
+    pub contact: Option<String>,
+    #[serde(default)]
+    pub supported_nips: Vec<u16>,
+    #[serde(default)]
+    pub software: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Server limits (`max_message_length`, `auth_required`, ...) kept as sent
+    #[serde(default)]
+    pub limitation: Option<serde_json::Value>,
+}
+
+struct CachedInfo {
+    info: RelayInformation,
+    fetched_at: Instant,
+}
+
+/// Fetches NIP-11 documents over HTTP and caches them per relay for `ttl`
+pub struct RelayInfoCache {
+    client: reqwest::Client,
+    ttl: Duration,
+    entries: DashMap<String, CachedInfo>,
+}
+
+impl RelayInfoCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            ttl,
+            entries: DashMap::new(),
+        }
+    }
+
+    /// Cached document for a relay, if one has been fetched
+    pub fn get(&self, relay_url: &str) -> Option<RelayInformation> {
+        self.entries.get(relay_url).map(|entry| entry.info.clone())
+    }
+
+    /// Whether the relay has no document yet or its document is older than the TTL
+    pub fn is_stale(&self, relay_url: &str) -> bool {
+        self.entries
+            .get(relay_url)
+            .is_none_or(|entry| entry.fetched_at.elapsed() >= self.ttl)
+    }
+
+    /// Fetch the relay's document and replace the cached copy
+    pub async fn refresh(&self, relay_url: &str) -> Result<()> {
+        let info = self.fetch(relay_url).await?;
+        debug!(relay = %relay_url, "Fetched NIP-11 document for {}", relay_url);
+        self.entries.insert(
+            relay_url.to_string(),
+            CachedInfo {
+                info,
+                fetched_at: Instant::now(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Drop the cached document of a relay that left the pool
+    pub fn remove(&self, relay_url: &str) {
+        self.entries.remove(relay_url);
+    }
+
+    async fn fetch(&self, relay_url: &str) -> Result<RelayInformation> {
+        let http_url = http_url(relay_url)?;
+        self.client
+            .get(http_url)
+            .header(reqwest::header::ACCEPT, "application/nostr+json")
+            .timeout(FETCH_TIMEOUT)
+            .send()
+            .await
+            .with_context(|| format!("Failed to request NIP-11 document from {}", relay_url))?
+            .error_for_status()
+            .with_context(|| format!("NIP-11 request to {} failed", relay_url))?
+            .json::<RelayInformation>()
+            .await
+            .with_context(|| format!("Invalid NIP-11 document from {}", relay_url))
+    }
+}
+
+/// The relay's HTTP(S) address: `ws://` maps to `http://` and `wss://` to `https://`
+fn http_url(relay_url: &str) -> Result<Url> {
+    let mut url = Url::parse(relay_url).context("Invalid relay URL")?;
+    let scheme = match url.scheme() {
+        "ws" => "http",
+        "wss" => "https",
+        other => anyhow::bail!("Unsupported relay URL scheme: {}", other),
+    };
+    url.set_scheme(scheme)
+        .map_err(|_| anyhow::anyhow!("Cannot convert {} to an HTTP URL", relay_url))?;
+    Ok(url)
+}
@@ -1,13 +1,40 @@
 use crate::api::metrics::Metrics;
+use crate::config::{HealthPolicy, IngestConfig, ReconnectPolicy};
+use crate::core::rate_limit::TokenBucket;
+use crate::core::relay_health::RelayHealth;
+use crate::core::relay_info::{RelayInfoCache, RelayInformation};
+use crate::storage::rocksdb_store::RocksDBStore;
 use anyhow::{Context, Result};
 use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
 use flume::{Receiver, Sender};
-use nostr_sdk::{Client, Event, Filter, Keys, RelayPoolNotification};
-use std::sync::Arc;
+use nostr_sdk::{
+    Client, ClientOptions, Event, Filter, JsonUtil, Keys, RelayPoolNotification, Timestamp, Url,
+};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 use std::sync::Arc as StdArc;
-use std::time::Duration;
-use tokio::sync::RwLock;
-use tracing::{error, info, warn};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{RwLock, broadcast};
+use tracing::{debug, error, info, warn};
+
+/// How often a draining relay re-checks its in-flight event count
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Upper bound on a single reconnect attempt
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default upper bound on an event's serialized JSON size
+const DEFAULT_MAX_EVENT_BYTES: usize = 512 * 1024;
+
+/// How often a new relay is checked for a completed handshake while connecting
+const CONNECT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Status changes buffered for slow subscribers before they start missing some
+const STATUS_CHANGE_CAPACITY: usize = 256;
 
 /// Connection status for a relay
 #[derive(Debug, Clone, PartialEq)]
@@ -15,96 +42,652 @@ pub enum RelayStatus {
     Connected,
     Disconnected,
     Connecting,
+    /// Waiting before the next reconnect attempt
+    Backoff,
+    /// Reconnect attempts exhausted; the relay is no longer retried
+    Failed,
+    /// Being removed; no new events are accepted while in-flight ones finish the pipeline
+    Draining,
+    /// Connected with its subscription closed until resumed
+    Paused,
     Error(String),
 }
 
+impl RelayStatus {
+    /// Whether two statuses are the same state, ignoring any attached error message
+    pub fn same_state(&self, other: &RelayStatus) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    /// Stable lowercase name used in the API, accepted back by `FromStr`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Connected => "connected",
+            Self::Disconnected => "disconnected",
+            Self::Connecting => "connecting",
+            Self::Backoff => "backoff",
+            Self::Failed => "failed",
+            Self::Draining => "draining",
+            Self::Paused => "paused",
+            Self::Error(_) => "error",
+        }
+    }
+
+    /// Message attached to an error status
+    pub fn error(&self) -> Option<&str> {
+        match self {
+            Self::Error(message) => Some(message),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for RelayStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for RelayStatus {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// A relay's status and when it entered it
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionStatus {
+    pub status: RelayStatus,
+    /// Unix time in milliseconds of the last transition into `status`
+    pub since: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl FromStr for RelayStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "connected" => Ok(Self::Connected),
+            "disconnected" => Ok(Self::Disconnected),
+            "connecting" => Ok(Self::Connecting),
+            "backoff" => Ok(Self::Backoff),
+            "failed" => Ok(Self::Failed),
+            "draining" => Ok(Self::Draining),
+            "paused" => Ok(Self::Paused),
+            "error" => Ok(Self::Error(String::new())),
+            other => anyhow::bail!("Unknown relay status: {}", other),
+        }
+    }
+}
+
+/// Relay lifecycle transition published to status change subscribers
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelayStatusEvent {
+    /// Connected for the first time or reconnected after a drop
+    Connected,
+    /// The connection dropped; the supervisor is about to reconnect
+    Disconnected,
+    /// Reconnect attempts exhausted
+    Failed,
+    /// Removed by the health check for a low score
+    Evicted,
+    /// Removed through the API or on shutdown
+    Removed,
+    Paused,
+    Resumed,
+}
+
+/// Structured notification of a relay status change
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayStatusChange {
+    pub relay: String,
+    pub event: RelayStatusEvent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Unix timestamp in milliseconds
+    pub timestamp: i64,
+}
+
+/// Event received from a relay, tagged with its source URL and receipt time.
+/// Only `event` is forwarded downstream; the metadata stays inside the pipeline.
+#[derive(Debug, Clone)]
+pub struct RelayEvent {
+    pub relay_url: String,
+    pub event: Event,
+    pub received_at: Instant,
+    /// Keeps the event counted as in flight for its relay until dropped
+    pub in_flight: InFlightGuard,
+}
+
+#[cfg(test)]
+impl RelayEvent {
+    /// An event as if just received from `relay_url`, counted against a throwaway counter
+    pub fn received(relay_url: &str, event: Event) -> Self {
+        Self {
+            relay_url: relay_url.to_string(),
+            event,
+            received_at: Instant::now(),
+            in_flight: InFlightGuard::new(Arc::new(AtomicUsize::new(0))),
+        }
+    }
+}
+
+/// Counts an event as in flight for its relay while alive, so removal can wait for the pipeline
+#[derive(Debug)]
+pub struct InFlightGuard {
+    counter: Arc<AtomicUsize>,
+}
+
+impl InFlightGuard {
+    fn new(counter: Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::AcqRel);
+        Self { counter }
+    }
+}
+
+impl Clone for InFlightGuard {
+    fn clone(&self) -> Self {
+        Self::new(self.counter.clone())
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
 /// Connection state for a single relay
 #[derive(Clone)]
 pub struct RelayConnection {
     url: String,
     client: Arc<Client>,
     status: Arc<RwLock<RelayStatus>>,
-    event_tx: Sender<Event>,
+    /// Unix time in milliseconds of the last status transition
+    status_since: Arc<AtomicI64>,
+    health: Arc<RelayHealth>,
+    /// Events from this relay that have not yet left the pipeline
+    in_flight: Arc<AtomicUsize>,
+    /// Subscription closed on request; kept across reconnects until resumed
+    paused: Arc<AtomicBool>,
+    /// Being removed; the status stays `Draining` until the relay leaves the pool
+    draining: Arc<AtomicBool>,
+    event_tx: Sender<RelayEvent>,
+    status_changes: broadcast::Sender<RelayStatusChange>,
+}
+
+impl RelayConnection {
+    /// Status to report while the underlying connection is up
+    fn live_status(&self) -> RelayStatus {
+        if self.draining.load(Ordering::Acquire) {
+            RelayStatus::Draining
+        } else if self.paused.load(Ordering::Acquire) {
+            RelayStatus::Paused
+        } else {
+            RelayStatus::Connected
+        }
+    }
+
+    /// Record that the status just changed
+    fn stamp_status(&self) {
+        self.status_since
+            .store(chrono::Utc::now().timestamp_millis(), Ordering::Release);
+    }
+
+    /// Change the status, stamping the transition time unless the state stays the same
+    async fn set_status(&self, status: RelayStatus) {
+        let mut current = self.status.write().await;
+        if !current.same_state(&status) {
+            self.stamp_status();
+        }
+        *current = status;
+    }
+
+    /// Current status with the time it was entered
+    async fn connection_status(&self) -> ConnectionStatus {
+        let status = self.status.read().await.clone();
+        ConnectionStatus {
+            error: status.error().map(str::to_string),
+            status,
+            since: self.status_since.load(Ordering::Acquire),
+        }
+    }
+
+    /// Publish a status change; an error only means nobody is subscribed
+    fn notify(&self, event: RelayStatusEvent, reason: Option<String>) {
+        let _ = self.status_changes.send(RelayStatusChange {
+            relay: self.url.clone(),
+            event,
+            reason,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+        });
+    }
+}
+
+/// Returned when adding a relay would exceed `max_connections`
+#[derive(Debug)]
+pub struct ConnectionLimitReached(pub usize);
+
+impl std::fmt::Display for ConnectionLimitReached {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "connection limit {} reached", self.0)
+    }
+}
+
+impl std::error::Error for ConnectionLimitReached {}
+
+/// Returned when a relay URL normalizes to one that is already in the pool
+#[derive(Debug)]
+pub struct RelayAlreadyConnected {
+    pub requested: String,
+    pub normalized: String,
+}
+
+impl std::fmt::Display for RelayAlreadyConnected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.requested == self.normalized {
+            write!(f, "relay {} is already connected", self.normalized)
+        } else {
+            write!(
+                f,
+                "relay {} is already connected as {}",
+                self.requested, self.normalized
+            )
+        }
+    }
+}
+
+impl std::error::Error for RelayAlreadyConnected {}
+
+/// Returned when an operation names a relay that is not in the pool
+#[derive(Debug)]
+pub struct RelayNotFound(pub String);
+
+impl std::fmt::Display for RelayNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "relay {} not found", self.0)
+    }
+}
+
+impl std::error::Error for RelayNotFound {}
+
+/// Returned when a relay was found and taken out of the pool but its removal did not complete
+#[derive(Debug)]
+pub struct RelayDisconnectFailed {
+    pub url: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for RelayDisconnectFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "relay {} was disconnected but {}", self.url, self.reason)
+    }
+}
+
+impl std::error::Error for RelayDisconnectFailed {}
+
+/// Returned when a relay does not finish connecting and subscribing within the connect timeout
+#[derive(Debug)]
+pub struct RelayConnectTimeout {
+    pub url: String,
+    pub timeout: Duration,
 }
 
+impl std::fmt::Display for RelayConnectTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "timed out connecting to relay {} after {}ms",
+            self.url,
+            self.timeout.as_millis()
+        )
+    }
+}
+
+impl std::error::Error for RelayConnectTimeout {}
+
 /// Pool of relay connections with health checking and load balancing
 pub struct RelayPool {
     connections: Arc<DashMap<String, RelayConnection>>,
+    /// Relays added without waiting: `Connecting` until they join `connections`, or `Failed`
+    /// with the error until they are added again or removed
+    background: Arc<DashMap<String, ConnectionStatus>>,
+    /// Held while a connected relay is checked against the cap and inserted
+    registration: Arc<Mutex<()>>,
     health_check_interval: Duration,
     max_connections: usize,
-    event_tx: Sender<Event>,
+    /// Upper bound on connecting and subscribing to a newly added relay
+    connect_timeout: Duration,
+    /// Events whose serialized JSON is larger than this are rejected on ingest
+    max_event_bytes: usize,
+    /// Events per second accepted from any single relay; excess events are dropped
+    max_events_per_sec: Option<u32>,
+    /// Per-relay overrides of `max_events_per_sec`, keyed by relay URL
+    relay_max_events_per_sec: Arc<HashMap<String, u32>>,
+    /// Accepted range of `created_at` around the time an event is received
+    ingest_limits: IngestConfig,
+    event_tx: Sender<RelayEvent>,
+    reconnect_policy: ReconnectPolicy,
+    health_policy: HealthPolicy,
+    /// NIP-42 signing keys for relays that require AUTH, keyed by relay URL
+    auth_keys: Arc<HashMap<String, Keys>>,
+    /// Filter sent in the REQ to every relay
+    subscription_filter: Filter,
+    /// How far back a newly connected relay is asked for stored events
+    initial_backfill: Option<Duration>,
+    /// Where the live relay set is persisted, when enabled
+    relay_store: Option<Arc<RocksDBStore>>,
+    /// NIP-11 documents of connected relays, when fetching is enabled
+    relay_info: Option<Arc<RelayInfoCache>>,
+    /// Broadcasts connect, disconnect, failure and eviction notifications
+    status_changes: broadcast::Sender<RelayStatusChange>,
     metrics: Option<StdArc<Metrics>>,
 }
 
 impl RelayPool {
     /// Create a new relay pool
-    pub fn new(health_check_interval: Duration, max_connections: usize) -> (Self, Receiver<Event>) {
+    pub fn new(
+        health_check_interval: Duration,
+        max_connections: usize,
+    ) -> (Self, Receiver<RelayEvent>) {
         let (tx, rx) = flume::unbounded();
+        let (status_changes, _) = broadcast::channel(STATUS_CHANGE_CAPACITY);
         let pool = Self {
             connections: Arc::new(DashMap::new()),
+            background: Arc::new(DashMap::new()),
+            registration: Arc::new(Mutex::new(())),
             health_check_interval,
             max_connections,
+            connect_timeout: RECONNECT_TIMEOUT,
+            max_event_bytes: DEFAULT_MAX_EVENT_BYTES,
+            max_events_per_sec: None,
+            relay_max_events_per_sec: Arc::new(HashMap::new()),
+            ingest_limits: IngestConfig::default(),
             event_tx: tx,
+            reconnect_policy: ReconnectPolicy::default(),
+            health_policy: HealthPolicy::default(),
+            auth_keys: Arc::new(HashMap::new()),
+            // Empty filter means all events
+            subscription_filter: Filter::new(),
+            initial_backfill: None,
+            relay_store: None,
+            relay_info: None,
+            status_changes,
             metrics: None,
         };
         (pool, rx)
     }
 
+    /// Set per-relay keys used to answer NIP-42 AUTH challenges
+    pub fn with_auth_keys(mut self, auth_keys: HashMap<String, Keys>) -> Self {
+        let auth_keys = auth_keys
+            .into_iter()
+            .map(|(url, keys)| (lookup_key(&url), keys))
+            .collect();
+        self.auth_keys = Arc::new(auth_keys);
+        self
+    }
+
+    /// Restrict what relays send by subscribing with this filter instead of everything
+    pub fn with_subscription_filter(mut self, filter: Filter) -> Self {
+        self.subscription_filter = filter;
+        self
+    }
+
+    /// Ask each newly connected relay for its stored events from the last `backfill` as well
+    pub fn with_initial_backfill(mut self, backfill: Duration) -> Self {
+        self.initial_backfill = Some(backfill);
+        self
+    }
+
+    /// Filter for the first REQ on a new connection, reaching back `initial_backfill` when set.
+    /// A configured `since` that is more recent still wins.
+    fn connect_filter(&self) -> Filter {
+        let Some(backfill) = self.initial_backfill else {
+            return self.subscription_filter.clone();
+        };
+        let since = Timestamp::now() - backfill;
+        let since = self
+            .subscription_filter
+            .since
+            .map_or(since, |s| s.max(since));
+        self.subscription_filter.clone().since(since)
+    }
+
+    /// Persist relays as they are added and removed so they can be restored on restart
+    pub fn with_relay_persistence(mut self, store: Arc<RocksDBStore>) -> Self {
+        self.relay_store = Some(store);
+        self
+    }
+
+    /// Fetch each relay's NIP-11 document on connect and refresh it once older than `ttl`
+    pub fn with_relay_info(mut self, ttl: Duration) -> Self {
+        self.relay_info = Some(Arc::new(RelayInfoCache::new(ttl)));
+        self
+    }
+
+    /// Drop events beyond `max_events_per_sec` from any one relay, with per-relay overrides
+    pub fn with_rate_limits(
+        mut self,
+        max_events_per_sec: Option<u32>,
+        per_relay: HashMap<String, u32>,
+    ) -> Self {
+        self.max_events_per_sec = max_events_per_sec;
+        self.relay_max_events_per_sec = Arc::new(
+            per_relay
+                .into_iter()
+                .map(|(url, rate)| (lookup_key(&url), rate))
+                .collect(),
+        );
+        self
+    }
+
+    /// Reject events whose serialized JSON exceeds `max_event_bytes` before they are processed
+    pub fn with_max_event_bytes(mut self, max_event_bytes: usize) -> Self {
+        self.max_event_bytes = max_event_bytes;
+        self
+    }
+
+    /// Drop events whose `created_at` is older than `max_age_secs` or further than
+    /// `max_future_skew_secs` in the future when they are received
+    pub fn with_ingest_limits(mut self, limits: IngestConfig) -> Self {
+        self.ingest_limits = limits;
+        self
+    }
+
+    /// Give up on a new relay that has not connected and subscribed within `timeout`
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Set the health scoring and eviction policy
+    pub fn with_health_policy(mut self, policy: HealthPolicy) -> Self {
+        self.health_policy = policy;
+        self
+    }
+
+    /// Set the backoff policy used to reconnect dropped relays
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = policy;
+        self
+    }
+
     pub fn with_metrics(mut self, metrics: StdArc<Metrics>) -> Self {
         self.metrics = Some(metrics);
         self
     }
 
-    /// Connect to a relay and subscribe to events
-    pub async fn connect_and_subscribe(&self, relay_url: String) -> Result<()> {
+    /// Validate that `relay_url` could be connected right now: it must parse, not already be
+    /// connected and fit under `max_connections`. Returns the normalized URL.
+    pub fn check_can_connect(&self, relay_url: &str) -> Result<String> {
+        let normalized = normalize_relay_url(relay_url)?;
+        if self.connections.contains_key(&normalized) {
+            info!("Relay {} already connected", normalized);
+            return Err(RelayAlreadyConnected {
+                requested: relay_url.to_string(),
+                normalized,
+            }
+            .into());
+        }
+
         if self.connections.len() >= self.max_connections {
-            warn!("Max connections reached, skipping {}", relay_url);
-            return Ok(());
+            warn!("Max connections reached, rejecting {}", normalized);
+            return Err(ConnectionLimitReached(self.max_connections).into());
         }
+        Ok(normalized)
+    }
 
-        if self.connections.contains_key(&relay_url) {
-            info!("Relay {} already connected", relay_url);
-            return Ok(());
+    /// Insert a connected relay unless it is already in the pool or the pool is full.
+    /// Registrations are serialized because a DashMap entry guard cannot count the other
+    /// shards, so `connections.len()` is checked under `registration` instead.
+    fn register(
+        &self,
+        requested: &str,
+        relay_url: &str,
+        connection: &RelayConnection,
+    ) -> Result<()> {
+        let _guard = self.registration.lock().unwrap_or_else(|e| e.into_inner());
+        if self.connections.contains_key(relay_url) {
+            return Err(RelayAlreadyConnected {
+                requested: requested.to_string(),
+                normalized: relay_url.to_string(),
+            }
+            .into());
+        }
+        if self.connections.len() >= self.max_connections {
+            warn!("Max connections reached, rejecting {}", relay_url);
+            return Err(ConnectionLimitReached(self.max_connections).into());
         }
+        self.connections
+            .insert(relay_url.to_string(), connection.clone());
+        Ok(())
+    }
 
-        info!("Connecting to relay: {}", relay_url);
+    /// Connect to a relay and subscribe to events
+    /// Fails with [`ConnectionLimitReached`] once the pool holds `max_connections` relays.
+    /// The URL is normalized first; equivalent spellings of a connected relay are rejected
+    /// with [`RelayAlreadyConnected`].
+    /// On `Ok` the relay is already registered, so [`Self::list_relays`] and
+    /// [`Self::get_connection_statuses`] include it.
+    pub async fn connect_and_subscribe(&self, relay_url: String) -> Result<()> {
+        let requested = relay_url;
+        let relay_url = self.check_can_connect(&requested)?;
 
-        let client = Client::new(Keys::generate());
+        info!(relay = %relay_url, "Connecting to relay: {}", relay_url);
 
-        // Add relay to client
-        client
-            .add_relay(&relay_url)
-            .await
-            .context(format!("Failed to add relay: {}", relay_url))?;
+        // Relays with configured keys answer AUTH challenges; others connect anonymously
+        let client = match self.auth_keys.get(&relay_url) {
+            Some(keys) => Client::builder()
+                .signer(keys.clone())
+                .opts(ClientOptions::new().automatic_authentication(true))
+                .build(),
+            None => Client::default(),
+        };
+
+        // A relay can accept the TCP connection but never finish the WebSocket handshake,
+        // so the whole attempt is bounded by the connect timeout
+        let attempt = async {
+            client
+                .add_relay(&relay_url)
+                .await
+                .context(format!("Failed to add relay: {}", relay_url))?;
 
-        // Connect to relay
-        // Connect to relay (no error returned in this version)
-        client.connect().await;
+            // Connecting runs in the background; wait for the handshake to complete
+            client.connect().await;
+            let relay = client
+                .relay(relay_url.as_str())
+                .await
+                .context(format!("Failed to add relay: {}", relay_url))?;
+            while !relay.is_connected() {
+                tokio::time::sleep(CONNECT_POLL_INTERVAL).await;
+            }
+
+            client
+                .subscribe(self.connect_filter(), None)
+                .await
+                .context("Failed to subscribe to relay")?;
+            Ok::<(), anyhow::Error>(())
+        };
+        let outcome = match tokio::time::timeout(self.connect_timeout, attempt).await {
+            Ok(result) => result,
+            Err(_) => Err(RelayConnectTimeout {
+                url: relay_url.clone(),
+                timeout: self.connect_timeout,
+            }
+            .into()),
+        };
+        if let Err(e) = outcome {
+            warn!(relay = %relay_url, "Could not connect to relay {}: {:#}", relay_url, e);
+            client.shutdown().await;
+            return Err(e);
+        }
 
         let status = Arc::new(RwLock::new(RelayStatus::Connected));
         let event_tx = self.event_tx.clone();
 
-        // Subscribe to all events (empty filter means all events)
-        let filter = Filter::new();
-        client
-            .subscribe(filter, None)
-            .await
-            .context("Failed to subscribe to relay")?;
-
         let connection = RelayConnection {
             url: relay_url.clone(),
             client: Arc::new(client),
             status: status.clone(),
+            status_since: Arc::new(AtomicI64::new(chrono::Utc::now().timestamp_millis())),
+            health: Arc::new(RelayHealth::new()),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            paused: Arc::new(AtomicBool::new(false)),
+            draining: Arc::new(AtomicBool::new(false)),
             event_tx: event_tx.clone(),
+            status_changes: self.status_changes.clone(),
         };
 
-        self.connections
-            .insert(relay_url.clone(), connection.clone());
+        // Register before returning so a list call made after `Ok` always includes the relay.
+        // Concurrent adds passed `check_can_connect` before connecting, so the duplicate and
+        // cap checks are repeated here in the same step that inserts.
+        let registered = self.register(&requested, &relay_url, &connection);
+        if let Err(e) = registered {
+            connection.client.shutdown().await;
+            return Err(e);
+        }
+        if let Some(store) = &self.relay_store {
+            if let Err(e) = store.store_relay(&relay_url).await {
+                error!("Failed to persist relay {}: {}", relay_url, e);
+            }
+        }
+
+        self.spawn_relay_info_refresh(&relay_url);
+        connection.notify(RelayStatusEvent::Connected, None);
 
         // Spawn task to handle events from this relay
-        tokio::spawn(Self::handle_relay_events(connection, event_tx));
+        let rate_limit = self
+            .relay_max_events_per_sec
+            .get(&relay_url)
+            .copied()
+            .or(self.max_events_per_sec);
+        tokio::spawn(Self::handle_relay_events(
+            connection.clone(),
+            event_tx,
+            self.max_event_bytes,
+            self.ingest_limits,
+            rate_limit.map(TokenBucket::new),
+            self.metrics.clone(),
+        ));
+
+        // Spawn supervisor to reconnect this relay if it drops
+        tokio::spawn(Self::supervise_connection(
+            connection,
+            self.connections.clone(),
+            self.reconnect_policy.clone(),
+            self.health_check_interval,
+            self.metrics.clone(),
+        ));
 
         info!(
+            relay = %relay_url,
             "Successfully connected and subscribed to relay: {}",
             relay_url
         );
@@ -112,19 +695,92 @@ impl RelayPool {
     }
 
     /// Handle events from a single relay connection
-    async fn handle_relay_events(connection: RelayConnection, event_tx: Sender<Event>) {
+    async fn handle_relay_events(
+        connection: RelayConnection,
+        event_tx: Sender<RelayEvent>,
+        max_event_bytes: usize,
+        ingest_limits: IngestConfig,
+        rate_limit: Option<TokenBucket>,
+        metrics: Option<StdArc<Metrics>>,
+    ) {
         let mut notifications = connection.client.notifications();
 
         while let Ok(notification) = notifications.recv().await {
             match notification {
                 RelayPoolNotification::Event { event, .. } => {
-                    if let Err(e) = event_tx.send_async(*event).await {
+                    // Stop accepting new events once the relay is being removed or paused
+                    if connection.paused.load(Ordering::Acquire)
+                        || connection.draining.load(Ordering::Acquire)
+                    {
+                        continue;
+                    }
+                    let size = serialized_len(&event);
+                    if rate_limit
+                        .as_ref()
+                        .is_some_and(|bucket| !bucket.try_acquire())
+                    {
+                        debug!(
+                            relay = %connection.url,
+                            "Dropped event {} from {}: over the relay rate limit",
+                            event.id,
+                            connection.url
+                        );
+                        if let Some(m) = &metrics {
+                            m.record_event_rejected("relay_rate_limit");
+                        }
+                        continue;
+                    }
+                    let now = Timestamp::now().as_secs();
+                    if let Some(reason) = event_age_reason(&event, now, &ingest_limits) {
+                        debug!(
+                            relay = %connection.url,
+                            "Dropped event {} from {}: {} (created_at {})",
+                            event.id,
+                            connection.url,
+                            reason,
+                            event.created_at.as_secs()
+                        );
+                        if let Some(m) = &metrics {
+                            m.record_event_rejected(reason);
+                        }
+                        continue;
+                    }
+                    if let Some(reason) = invalid_event_reason(&event, size, max_event_bytes) {
+                        warn!(
+                            relay = %connection.url,
+                            event_id = %event.id,
+                            "Rejected event from {}: {}",
+                            connection.url,
+                            reason
+                        );
+                        connection.health.record_error();
+                        if let Some(m) = &metrics {
+                            m.record_event_rejected(reason);
+                        }
+                        continue;
+                    }
+                    connection.health.record_event();
+                    if let Some(m) = &metrics {
+                        m.record_relay_event(&connection.url);
+                        m.record_event_kind(event.kind.as_u16());
+                    }
+                    let relay_event = RelayEvent {
+                        relay_url: connection.url.clone(),
+                        event: *event,
+                        received_at: Instant::now(),
+                        in_flight: InFlightGuard::new(connection.in_flight.clone()),
+                    };
+                    if let Err(e) = event_tx.send_async(relay_event).await {
                         error!("Failed to send event to pipeline: {}", e);
                         break;
                     }
                 }
                 RelayPoolNotification::Message { message, .. } => {
-                    // Handle other message types if needed
+                    // Every frame counts toward the relay's bandwidth, including events the
+                    // client already saw and frames that carry no event
+                    if let Some(m) = &metrics {
+                        m.record_relay_bytes(&connection.url, message.as_json().len());
+                    }
                     info!("Received message from {}: {:?}", connection.url, message);
                 }
                 _ => {}
@@ -132,7 +788,156 @@ impl RelayPool {
         }
 
         warn!("Event stream ended for relay: {}", connection.url);
-        *connection.status.write().await = RelayStatus::Disconnected;
+        connection.set_status(RelayStatus::Disconnected).await;
+    }
+
+    /// Watch a relay connection and reconnect it with exponential backoff when it drops.
+    /// Exits once the relay is removed from the pool or marked `Failed`.
+    async fn supervise_connection(
+        connection: RelayConnection,
+        connections: Arc<DashMap<String, RelayConnection>>,
+        policy: ReconnectPolicy,
+        check_interval: Duration,
+        metrics: Option<StdArc<Metrics>>,
+    ) {
+        let mut interval_timer = tokio::time::interval(check_interval);
+        loop {
+            interval_timer.tick().await;
+            if !connections.contains_key(&connection.url) {
+                return;
+            }
+            // A relay being drained is on its way out: leave it `Draining`, even if it drops
+            if connection.draining.load(Ordering::Acquire) {
+                continue;
+            }
+            if Self::is_relay_connected(&connection).await {
+                connection.set_status(connection.live_status()).await;
+                continue;
+            }
+
+            warn!("Relay {} dropped, starting reconnection", connection.url);
+            connection.notify(
+                RelayStatusEvent::Disconnected,
+                Some("connection lost".to_string()),
+            );
+            if !Self::reconnect_with_backoff(&connection, &connections, &policy).await {
+                if !connections.contains_key(&connection.url) {
+                    return;
+                }
+                error!(
+                    "Relay {} failed after {} reconnect attempts",
+                    connection.url, policy.max_retries
+                );
+                connection.set_status(RelayStatus::Failed).await;
+                connection.notify(
+                    RelayStatusEvent::Failed,
+                    Some(format!(
+                        "gave up after {} reconnect attempts",
+                        policy.max_retries
+                    )),
+                );
+                if let Some(m) = &metrics {
+                    m.relays_failed.inc();
+                }
+                return;
+            }
+        }
+    }
+
+    /// Retry connecting a relay following the backoff schedule; returns whether it reconnected
+    async fn reconnect_with_backoff(
+        connection: &RelayConnection,
+        connections: &DashMap<String, RelayConnection>,
+        policy: &ReconnectPolicy,
+    ) -> bool {
+        for attempt in 0..policy.max_retries {
+            connection.set_status(RelayStatus::Backoff).await;
+            let delay = with_jitter(
+                policy.backoff_delay(attempt),
+                Duration::from_millis(policy.max_delay_ms),
+            );
+            info!(
+                "Reconnecting to {} in {:?} (attempt {}/{})",
+                connection.url,
+                delay,
+                attempt + 1,
+                policy.max_retries
+            );
+            tokio::time::sleep(delay).await;
+            if !connections.contains_key(&connection.url) {
+                return false;
+            }
+
+            connection.set_status(RelayStatus::Connecting).await;
+            match connection
+                .client
+                .try_connect_relay(connection.url.as_str(), RECONNECT_TIMEOUT)
+                .await
+            {
+                Ok(()) => {
+                    info!(relay = %connection.url, "Reconnected to relay {}", connection.url);
+                    connection.set_status(connection.live_status()).await;
+                    connection.notify(RelayStatusEvent::Connected, Some("reconnected".to_string()));
+                    return true;
+                }
+                Err(e) => {
+                    connection.health.record_error();
+                    warn!("Reconnect to {} failed: {}", connection.url, e);
+                }
+            }
+        }
+        false
+    }
+
+    /// Check whether the underlying client currently holds a live connection to the relay
+    async fn is_relay_connected(connection: &RelayConnection) -> bool {
+        match connection.client.relay(connection.url.as_str()).await {
+            Ok(relay) => relay.is_connected(),
+            Err(_) => false,
+        }
+    }
+
+    /// Start connecting to a relay without waiting for it, returning its normalized URL once
+    /// it is listed as `Connecting`. A failed attempt stays listed as `Failed` with its error.
+    pub fn connect_in_background(&self, relay_url: &str) -> Result<String> {
+        let normalized = self.check_can_connect(relay_url)?;
+        let connecting = ConnectionStatus {
+            status: RelayStatus::Connecting,
+            since: chrono::Utc::now().timestamp_millis(),
+            error: None,
+        };
+        match self.background.entry(normalized.clone()) {
+            Entry::Occupied(entry) if entry.get().status == RelayStatus::Connecting => {
+                return Err(RelayAlreadyConnected {
+                    requested: relay_url.to_string(),
+                    normalized,
+                }
+                .into());
+            }
+            entry => {
+                entry.insert(connecting);
+            }
+        }
+
+        let pool = self.clone();
+        let url = normalized.clone();
+        tokio::spawn(async move {
+            match pool.connect_and_subscribe(url.clone()).await {
+                Ok(()) => {
+                    pool.background.remove(&url);
+                }
+                Err(e) => {
+                    error!("Failed to add relay {} in the background: {}", url, e);
+                    let failed = ConnectionStatus {
+                        status: RelayStatus::Failed,
+                        since: chrono::Utc::now().timestamp_millis(),
+                        error: Some(e.to_string()),
+                    };
+                    pool.background.insert(url, failed);
+                }
+            }
+        });
+        Ok(normalized)
     }
 
     /// Connect to multiple relays in parallel
@@ -153,66 +958,292 @@ impl RelayPool {
         Ok(())
     }
 
+    /// Connect to several relays concurrently, reporting the outcome per URL.
+    /// URLs that would push the pool past `max_connections` fail with
+    /// [`ConnectionLimitReached`]; the cap is enforced when each relay registers, so concurrent
+    /// batches and single adds cannot overshoot it. Equivalent spellings are attempted once.
+    pub async fn connect_batch(&self, relay_urls: Vec<String>) -> Vec<(String, Result<()>)> {
+        let (relay_urls, _) = dedup_relay_urls(relay_urls);
+        futures::future::join_all(relay_urls.into_iter().map(|url| async move {
+            let result = self.connect_and_subscribe(url.clone()).await;
+            (url, result)
+        }))
+        .await
+    }
+
     /// Start health checking for all connections
+    /// Each check updates every relay's health score and, when enabled, evicts relays
+    /// that stayed below the threshold. Reconnection is handled by each connection's supervisor.
     pub async fn start_health_checks(&self) {
-        let connections = self.connections.clone();
+        let pool = self.clone();
         let interval = self.health_check_interval;
 
-        let metrics = self.metrics.clone();
         tokio::spawn(async move {
             let mut interval_timer = tokio::time::interval(interval);
+            // The first tick completes immediately; score only after a full interval
+            interval_timer.tick().await;
             loop {
                 interval_timer.tick().await;
 
-                for entry in connections.iter() {
-                    let connection = entry.value();
-                    let current_status = connection.status.read().await.clone();
-
-                    // Check if connection is still alive by trying to get status
-                    // In a real implementation, you might want to ping the relay
-                    if current_status == RelayStatus::Disconnected {
+                let connections: Vec<RelayConnection> =
+                    pool.connections.iter().map(|e| e.value().clone()).collect();
+                for connection in connections {
+                    if pool
+                        .relay_info
+                        .as_ref()
+                        .is_some_and(|cache| cache.is_stale(&connection.url))
+                    {
+                        pool.spawn_relay_info_refresh(&connection.url);
+                    }
+                    // A paused relay is still connected; it only loses the throughput component
+                    let connected = matches!(
+                        *connection.status.read().await,
+                        RelayStatus::Connected | RelayStatus::Paused
+                    );
+                    if let Some(m) = &pool.metrics {
+                        let lag = connection.health.since_last_event();
+                        m.set_relay_event_lag(&connection.url, lag);
+                        m.sample_relay_bandwidth(&connection.url);
+                    }
+                    let (score, unhealthy) = connection
+                        .health
+                        .evaluate(connected, interval, &pool.health_policy)
+                        .await;
+                    if unhealthy && pool.health_policy.auto_evict {
                         warn!(
-                            "Relay {} is disconnected, attempting reconnect",
-                            connection.url
+                            relay = %connection.url,
+                            "Evicting relay {} with health score {:.2}",
+                            connection.url,
+                            score
                         );
-                        // Attempt reconnection
-                        connection.client.connect().await;
-                        *connection.status.write().await = RelayStatus::Connected;
+                        let reason = format!("health score {:.2}", score);
+                        if pool
+                            .remove_connection(
+                                &connection.url,
+                                RelayStatusEvent::Evicted,
+                                Some(reason),
+                            )
+                            .await
+                            .is_ok()
+                        {
+                            if let Some(m) = &pool.metrics {
+                                m.relays_evicted.inc();
+                            }
+                        }
                     }
                 }
-                if let Some(m) = &metrics {
-                    m.active_connections.set(connections.len() as f64);
+
+                if let Some(m) = &pool.metrics {
+                    m.active_connections.set(pool.connections.len() as f64);
                 }
             }
         });
     }
 
+    /// Current health score for a relay in the pool
+    pub async fn health_score(&self, relay_url: &str) -> Option<f64> {
+        let health = self.connections.get(&lookup_key(relay_url))?.health.clone();
+        Some(health.score().await)
+    }
+
+    /// Maximum number of relays the pool accepts
+    pub fn max_connections(&self) -> usize {
+        self.max_connections
+    }
+
+    /// Number of relays that can still be added before `max_connections` is reached
+    pub fn connection_headroom(&self) -> usize {
+        self.max_connections.saturating_sub(self.connections.len())
+    }
+
     /// Get the number of active connections
     pub fn active_connections(&self) -> usize {
         self.connections.len()
     }
 
     /// Get connection status for all relays
-    pub async fn get_connection_statuses(&self) -> Vec<(String, RelayStatus)> {
+    pub async fn get_connection_statuses(&self) -> Vec<(String, ConnectionStatus)> {
         let mut statuses = Vec::new();
         for entry in self.connections.iter() {
-            let status = entry.value().status.read().await.clone();
+            let status = entry.value().connection_status().await;
             statuses.push((entry.key().clone(), status));
         }
+        for entry in self.background.iter() {
+            if !self.connections.contains_key(entry.key()) {
+                statuses.push((entry.key().clone(), entry.value().clone()));
+            }
+        }
         statuses
     }
 
-    /// Disconnect and remove a relay
+    /// Stop receiving events from a relay by closing its subscription, keeping the connection open
+    pub async fn pause_relay(&self, relay_url: &str) -> Result<()> {
+        let connection = self.connection(relay_url)?;
+        if connection.paused.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        connection.client.unsubscribe_all().await;
+        let mut status = connection.status.write().await;
+        if *status == RelayStatus::Connected {
+            *status = RelayStatus::Paused;
+            connection.stamp_status();
+        }
+        connection.notify(RelayStatusEvent::Paused, None);
+        info!(relay = %connection.url, "Paused relay {}", connection.url);
+        Ok(())
+    }
+
+    /// Re-send the subscription to a paused relay on its existing connection
+    pub async fn resume_relay(&self, relay_url: &str) -> Result<()> {
+        let connection = self.connection(relay_url)?;
+        if !connection.paused.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        connection
+            .client
+            .subscribe(self.subscription_filter.clone(), None)
+            .await
+            .context(format!(
+                "Failed to resubscribe to relay: {}",
+                connection.url
+            ))?;
+        connection.paused.store(false, Ordering::Release);
+        let mut status = connection.status.write().await;
+        if *status == RelayStatus::Paused {
+            *status = RelayStatus::Connected;
+            connection.stamp_status();
+        }
+        connection.notify(RelayStatusEvent::Resumed, None);
+        info!(relay = %connection.url, "Resumed relay {}", connection.url);
+        Ok(())
+    }
+
+    /// Look up a relay in the pool by any spelling of its URL
+    fn connection(&self, relay_url: &str) -> Result<RelayConnection> {
+        let key = lookup_key(relay_url);
+        match self.connections.get(&key) {
+            Some(entry) => Ok(entry.value().clone()),
+            None => Err(RelayNotFound(key).into()),
+        }
+    }
+
+    /// Gracefully remove a relay: stop its subscriptions, wait up to `drain_timeout`
+    /// for events already received from it to leave the pipeline, then disconnect.
+    pub async fn drain_relay(&self, relay_url: &str, drain_timeout: Duration) -> Result<()> {
+        if self.forget_failed(relay_url) {
+            return Ok(());
+        }
+        let connection = self.connection(relay_url)?;
+        let relay_url = connection.url.as_str();
+
+        connection.draining.store(true, Ordering::Release);
+        connection.set_status(RelayStatus::Draining).await;
+        connection.client.unsubscribe_all().await;
+        info!("Draining relay {} before removal", relay_url);
+
+        let deadline = Instant::now() + drain_timeout;
+        while connection.in_flight.load(Ordering::Acquire) > 0 {
+            if Instant::now() >= deadline {
+                warn!(
+                    "Drain timeout for relay {}, {} events still in flight",
+                    relay_url,
+                    connection.in_flight.load(Ordering::Acquire)
+                );
+                break;
+            }
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+
+        self.disconnect_relay(relay_url).await
+    }
+
+    /// Disconnect and remove a relay.
+    /// Fails with [`RelayNotFound`] for unknown relays and [`RelayDisconnectFailed`] when the relay
+    /// left the pool but could not be dropped from the persisted relay set.
     pub async fn disconnect_relay(&self, relay_url: &str) -> Result<()> {
-        if let Some((_, connection)) = self.connections.remove(relay_url) {
-            *connection.status.write().await = RelayStatus::Disconnected;
-            // Note: The client will be dropped when the connection is removed
-            // The handle_relay_events task will naturally terminate
-            info!("Disconnected and removed relay: {}", relay_url);
-            Ok(())
-        } else {
-            anyhow::bail!("Relay {} not found", relay_url)
+        if self.forget_failed(relay_url) {
+            return Ok(());
+        }
+        self.remove_connection(relay_url, RelayStatusEvent::Removed, None)
+            .await
+    }
+
+    /// Drop the listing of a relay whose background connect failed; such a relay was never
+    /// connected, so there is nothing else to remove
+    fn forget_failed(&self, relay_url: &str) -> bool {
+        let key = lookup_key(relay_url);
+        let failed = self
+            .background
+            .remove_if(&key, |_, status| status.status == RelayStatus::Failed);
+        if failed.is_none() || self.connections.contains_key(&key) {
+            return false;
         }
+        info!("Removed failed relay: {}", key);
+        true
+    }
+
+    /// Disconnect and remove a relay, announcing it to status change subscribers as `event`
+    async fn remove_connection(
+        &self,
+        relay_url: &str,
+        event: RelayStatusEvent,
+        reason: Option<String>,
+    ) -> Result<()> {
+        let key = lookup_key(relay_url);
+        let relay_url = key.as_str();
+        let Some((_, connection)) = self.connections.remove(relay_url) else {
+            return Err(RelayNotFound(key).into());
+        };
+        connection.set_status(RelayStatus::Disconnected).await;
+        if let Some(m) = &self.metrics {
+            m.remove_relay(relay_url);
+        }
+        if let Some(cache) = &self.relay_info {
+            cache.remove(relay_url);
+        }
+        let mut unpersist_error = None;
+        if let Some(store) = &self.relay_store {
+            if let Err(e) = store.remove_relay(relay_url).await {
+                error!("Failed to remove persisted relay {}: {}", relay_url, e);
+                unpersist_error = Some(e);
+            }
+        }
+        // Shutting the client down ends its notification stream, so the
+        // handle_relay_events task terminates; the supervisor exits on its next tick
+        connection.client.shutdown().await;
+        connection.notify(event, reason);
+        info!("Disconnected and removed relay: {}", relay_url);
+        match unpersist_error {
+            Some(e) => Err(RelayDisconnectFailed {
+                url: key,
+                reason: format!("could not be removed from the persisted relays: {}", e),
+            }
+            .into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Receive every relay status change published from now on
+    pub fn subscribe_status_changes(&self) -> broadcast::Receiver<RelayStatusChange> {
+        self.status_changes.subscribe()
+    }
+
+    /// Cached NIP-11 document for a relay, if it has been fetched
+    pub fn relay_info(&self, relay_url: &str) -> Option<RelayInformation> {
+        self.relay_info.as_ref()?.get(&lookup_key(relay_url))
+    }
+
+    /// Fetch a relay's NIP-11 document in the background; failures keep the previous copy
+    fn spawn_relay_info_refresh(&self, relay_url: &str) {
+        let Some(cache) = self.relay_info.clone() else {
+            return;
+        };
+        let relay_url = relay_url.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = cache.refresh(&relay_url).await {
+                warn!(relay = %relay_url, "NIP-11 fetch failed for {}: {:#}", relay_url, e);
+            }
+        });
     }
 
     /// Get list of all relay URLs
@@ -228,10 +1259,397 @@ impl Clone for RelayPool {
     fn clone(&self) -> Self {
         Self {
             connections: self.connections.clone(),
+            background: self.background.clone(),
+            registration: self.registration.clone(),
             health_check_interval: self.health_check_interval,
             max_connections: self.max_connections,
+            connect_timeout: self.connect_timeout,
+            max_event_bytes: self.max_event_bytes,
+            max_events_per_sec: self.max_events_per_sec,
+            relay_max_events_per_sec: self.relay_max_events_per_sec.clone(),
+            ingest_limits: self.ingest_limits,
             event_tx: self.event_tx.clone(),
+            reconnect_policy: self.reconnect_policy.clone(),
+            health_policy: self.health_policy.clone(),
+            auth_keys: self.auth_keys.clone(),
+            subscription_filter: self.subscription_filter.clone(),
+            initial_backfill: self.initial_backfill,
+            relay_store: self.relay_store.clone(),
+            relay_info: self.relay_info.clone(),
+            status_changes: self.status_changes.clone(),
             metrics: self.metrics.clone(),
         }
     }
 }
+
+/// Canonical form of a relay URL: lowercase scheme and host, default port removed and no
+/// trailing slash, so `wss://Relay.example.com:443/` and `wss://relay.example.com` match
+pub fn normalize_relay_url(relay_url: &str) -> Result<String> {
+    let url = Url::parse(relay_url.trim())
+        .with_context(|| format!("Invalid relay URL: {}", relay_url))?;
+    anyhow::ensure!(
+        matches!(url.scheme(), "ws" | "wss"),
+        "Relay URL must use ws:// or wss://: {}",
+        relay_url
+    );
+    let host = url
+        .host_str()
+        .with_context(|| format!("Relay URL has no host: {}", relay_url))?;
+
+    // `Url` already lowercases the host and drops the scheme's default port
+    let mut normalized = format!("{}://{}", url.scheme(), host);
+    if let Some(port) = url.port() {
+        normalized.push_str(&format!(":{}", port));
+    }
+    normalized.push_str(url.path().trim_end_matches('/'));
+    if let Some(query) = url.query() {
+        normalized.push('?');
+        normalized.push_str(query);
+    }
+    Ok(normalized)
+}
+
+/// Split `relay_urls` into the URLs to connect, keeping the first spelling of each relay, and
+/// the later entries that normalize to a relay already listed
+pub fn dedup_relay_urls(relay_urls: Vec<String>) -> (Vec<String>, Vec<String>) {
+    let mut seen = HashSet::new();
+    relay_urls
+        .into_iter()
+        .partition(|url| seen.insert(lookup_key(url)))
+}
+
+/// Key under which a relay is stored in the pool, falling back to the raw URL if it is invalid
+pub fn lookup_key(relay_url: &str) -> String {
+    normalize_relay_url(relay_url).unwrap_or_else(|_| relay_url.to_string())
+}
+
+/// Check that an event of serialized length `size` is within the size limit, its id matches its
+/// content and its signature is valid. Returns the rejection reason used as the
+/// `events_rejected_total` label.
+fn invalid_event_reason(
+    event: &Event,
+    size: usize,
+    max_event_bytes: usize,
+) -> Option<&'static str> {
+    if size > max_event_bytes {
+        Some("too_large")
+    } else if !event.verify_id() {
+        Some("bad_id")
+    } else if !event.verify_signature() {
+        Some("bad_sig")
+    } else {
+        None
+    }
+}
+
+/// Why an event created outside the accepted range around `now` (Unix seconds) is dropped,
+/// used as the `events_rejected_total` label
+fn event_age_reason(event: &Event, now: u64, limits: &IngestConfig) -> Option<&'static str> {
+    let created_at = event.created_at.as_secs();
+    if limits
+        .max_age_secs
+        .is_some_and(|max_age| created_at < now.saturating_sub(max_age))
+    {
+        Some("too_old")
+    } else if limits
+        .max_future_skew_secs
+        .is_some_and(|skew| created_at > now.saturating_add(skew))
+    {
+        Some("too_far_future")
+    } else {
+        None
+    }
+}
+
+/// Size of an event's JSON serialization, counted without buffering it
+fn serialized_len(event: &Event) -> usize {
+    struct ByteCounter(usize);
+
+    impl std::io::Write for ByteCounter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = ByteCounter(0);
+    match serde_json::to_writer(&mut counter, event) {
+        Ok(()) => counter.0,
+        Err(_) => usize::MAX,
+    }
+}
+
+/// Add up to 50% random jitter to a backoff delay so reconnecting relays do not synchronize,
+/// without going past `max`
+fn with_jitter(delay: Duration, max: Duration) -> Duration {
+    (delay + delay.mul_f64(rand::random_range(0.0..=0.5))).min(max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        closable_relay, mock_relay, relay_sending, signed_event, signed_events,
+    };
+    use nostr_sdk::RelayMessage;
+    use serde_json::{Value, json};
+
+    /// `event` as JSON with one field replaced
+    fn with_field(event: &Event, field: &str, value: Value) -> Value {
+        let mut json = serde_json::to_value(event).unwrap();
+        json[field] = value;
+        json
+    }
+
+    fn forged(json: Value) -> Event {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn pool(max_connections: usize) -> RelayPool {
+        let (pool, _events) = RelayPool::new(Duration::from_secs(60), max_connections);
+        pool.with_connect_timeout(Duration::from_secs(5))
+    }
+
+    #[test]
+    fn jitter_adds_at_most_half_the_delay_and_varies() {
+        let delay = Duration::from_secs(10);
+        let max = Duration::from_secs(60);
+        let jittered: HashSet<_> = (0..100).map(|_| with_jitter(delay, max)).collect();
+        assert!(
+            jittered
+                .iter()
+                .all(|d| *d >= delay && *d <= delay + delay / 2)
+        );
+        assert!(jittered.len() > 1);
+    }
+
+    #[test]
+    fn jitter_never_exceeds_the_max_delay() {
+        let max = Duration::from_secs(10);
+        assert!((0..100).all(|_| with_jitter(max, max) == max));
+        assert!((0..100).all(|_| with_jitter(Duration::from_secs(8), max) <= max));
+    }
+
+    #[test]
+    fn invalid_events_are_rejected_with_their_reason() {
+        let [event, other] = <[Event; 2]>::try_from(signed_events(2)).unwrap();
+        let bad_sig = forged(with_field(&event, "sig", json!(other.sig)));
+        let bad_id = forged(with_field(&event, "content", json!("edited after signing")));
+
+        assert_eq!(invalid_event_reason(&event, 1_000, 1_000), None);
+        assert_eq!(
+            invalid_event_reason(&event, 1_001, 1_000),
+            Some("too_large")
+        );
+        assert_eq!(invalid_event_reason(&bad_id, 1_000, 1_000), Some("bad_id"));
+        assert_eq!(
+            invalid_event_reason(&bad_sig, 1_000, 1_000),
+            Some("bad_sig")
+        );
+    }
+
+    #[test]
+    fn events_outside_the_ingest_window_are_rejected_with_their_reason() {
+        let now = 1_700_000_000;
+        let limits = IngestConfig {
+            max_age_secs: Some(60),
+            max_future_skew_secs: Some(10),
+        };
+        let at = |secs: u64| signed_event("note", Timestamp::from(secs));
+
+        assert_eq!(event_age_reason(&at(now - 60), now, &limits), None);
+        assert_eq!(event_age_reason(&at(now + 10), now, &limits), None);
+        assert_eq!(
+            event_age_reason(&at(now - 61), now, &limits),
+            Some("too_old")
+        );
+        assert_eq!(
+            event_age_reason(&at(now + 11), now, &limits),
+            Some("too_far_future")
+        );
+        let unbounded = IngestConfig::default();
+        assert_eq!(event_age_reason(&at(0), now, &unbounded), None);
+    }
+
+    #[tokio::test]
+    async fn malformed_and_forged_events_never_reach_the_pipeline() {
+        let [event, other] = <[Event; 2]>::try_from(signed_events(2)).unwrap();
+        let relay = relay_sending(vec![
+            json!({"id": "not an event"}),
+            with_field(&other, "sig", json!(event.sig)),
+            with_field(&other, "content", json!("edited after signing")),
+            serde_json::to_value(&event).unwrap(),
+        ])
+        .await;
+        let (pool, events) = RelayPool::new(Duration::from_secs(60), 1);
+        pool.connect_and_subscribe(relay).await.unwrap();
+
+        let received = tokio::time::timeout(Duration::from_secs(5), events.recv_async())
+            .await
+            .expect("the valid event was not delivered")
+            .unwrap();
+        assert_eq!(received.event, event);
+        let more = tokio::time::timeout(Duration::from_millis(300), events.recv_async()).await;
+        assert!(more.is_err());
+    }
+
+    #[tokio::test]
+    async fn every_frame_from_a_relay_counts_toward_its_bytes() {
+        let events = signed_events(2);
+        let relay = relay_sending(events.iter().map(|e| json!(e)).collect()).await;
+        let metrics = crate::test_support::metrics();
+        let (pool, received) = RelayPool::new(Duration::from_secs(60), 1);
+        let pool = pool.with_metrics(metrics.clone());
+        pool.connect_and_subscribe(relay).await.unwrap();
+        for _ in &events {
+            tokio::time::timeout(Duration::from_secs(5), received.recv_async())
+                .await
+                .expect("event was not delivered")
+                .unwrap();
+        }
+
+        let connection = pool.connections.iter().next().unwrap().value().clone();
+        let subscriptions = connection.client.subscriptions().await;
+        let subscription_id = subscriptions.keys().next().unwrap();
+        let expected: usize = events
+            .iter()
+            .map(|event| RelayMessage::event(subscription_id.clone(), event.clone()))
+            .chain(std::iter::once(RelayMessage::eose(subscription_id.clone())))
+            .map(|message| message.as_json().len())
+            .sum();
+        let counter = metrics
+            .relay_bytes_received
+            .with_label_values(&[connection.url.as_str()]);
+        // EOSE may still be on its way
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while counter.get() < expected as u64 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("not every frame was counted");
+        assert_eq!(counter.get(), expected as u64);
+    }
+
+    #[tokio::test]
+    async fn a_relay_that_stays_down_is_marked_failed_after_the_last_retry() {
+        let (pool, _events) = RelayPool::new(Duration::from_millis(20), 2);
+        let pool = pool.with_reconnect_policy(ReconnectPolicy {
+            base_delay_ms: 10,
+            max_delay_ms: 20,
+            max_retries: 3,
+        });
+        let (relay, server) = closable_relay().await;
+        pool.connect_and_subscribe(relay.clone()).await.unwrap();
+        let mut changes = pool.subscribe_status_changes();
+
+        server.abort();
+        let mut events = Vec::new();
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while events.last() != Some(&RelayStatusEvent::Failed) {
+                events.push(changes.recv().await.unwrap().event);
+            }
+        })
+        .await
+        .expect("relay was not marked failed");
+
+        assert_eq!(
+            events,
+            [RelayStatusEvent::Disconnected, RelayStatusEvent::Failed]
+        );
+        let statuses = pool.get_connection_statuses().await;
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].1.status, RelayStatus::Failed);
+    }
+
+    #[tokio::test]
+    async fn a_draining_relay_stays_draining_until_it_is_removed() {
+        // Supervisor ticks every 20ms, so several pass while the drain waits
+        let (pool, _events) = RelayPool::new(Duration::from_millis(20), 2);
+        let relay = mock_relay().await;
+        pool.connect_and_subscribe(relay.clone()).await.unwrap();
+        let in_flight = InFlightGuard::new(pool.connection(&relay).unwrap().in_flight.clone());
+
+        let draining = pool.clone();
+        let drained = relay.clone();
+        let drain = tokio::spawn(async move {
+            draining
+                .drain_relay(&drained, Duration::from_secs(10))
+                .await
+        });
+        for _ in 0..5 {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            let statuses = pool.get_connection_statuses().await;
+            assert_eq!(statuses[0].1.status, RelayStatus::Draining);
+        }
+
+        drop(in_flight);
+        drain.await.unwrap().unwrap();
+        assert!(pool.get_connection_statuses().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn refuses_the_connection_past_max_connections_until_a_slot_frees() {
+        let pool = pool(2);
+        let relays = [mock_relay().await, mock_relay().await, mock_relay().await];
+        pool.connect_and_subscribe(relays[0].clone()).await.unwrap();
+        pool.connect_and_subscribe(relays[1].clone()).await.unwrap();
+
+        let refused = pool
+            .connect_and_subscribe(relays[2].clone())
+            .await
+            .unwrap_err();
+        assert!(refused.is::<ConnectionLimitReached>());
+        assert_eq!(refused.to_string(), "connection limit 2 reached");
+        assert_eq!(pool.connection_headroom(), 0);
+
+        pool.disconnect_relay(&relays[0]).await.unwrap();
+        assert_eq!(pool.connection_headroom(), 1);
+        pool.connect_and_subscribe(relays[2].clone()).await.unwrap();
+        assert_eq!(pool.active_connections(), 2);
+    }
+
+    #[tokio::test]
+    async fn concurrent_adds_cannot_push_the_pool_past_max_connections() {
+        let pool = pool(2);
+        let mut relays = Vec::new();
+        for _ in 0..6 {
+            relays.push(mock_relay().await);
+        }
+
+        let pool = &pool;
+        let results = futures::future::join_all(
+            relays
+                .into_iter()
+                .map(|url| async move { pool.connect_and_subscribe(url).await }),
+        )
+        .await;
+
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 2);
+        assert_eq!(pool.active_connections(), 2);
+        for error in results.into_iter().filter_map(Result::err) {
+            assert!(error.is::<ConnectionLimitReached>());
+        }
+    }
+
+    #[tokio::test]
+    async fn batch_rejects_urls_past_the_cap_per_url() {
+        let pool = pool(2);
+        let urls = vec![mock_relay().await, mock_relay().await, mock_relay().await];
+
+        let results = pool.connect_batch(urls).await;
+
+        assert_eq!(results.len(), 3);
+        let refused: Vec<_> = results
+            .iter()
+            .filter_map(|(_, r)| r.as_ref().err())
+            .collect();
+        assert_eq!(refused.len(), 1);
+        assert_eq!(refused[0].to_string(), "connection limit 2 reached");
+        assert_eq!(pool.active_connections(), 2);
+    }
+}
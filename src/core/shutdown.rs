@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::storage::rocksdb_store::RocksDBStore;
+
+/// Coordinates graceful shutdown across background tasks
+#[derive(Clone)]
+pub struct Shutdown {
+    tx: Arc<watch::Sender<bool>>,
+}
+
+impl Shutdown {
+    /// Create a new, untriggered shutdown coordinator
+    pub fn new() -> Self {
+        let (tx, _) = watch::channel(false);
+        Self { tx: Arc::new(tx) }
+    }
+
+    /// Signal every waiting task to shut down
+    pub fn trigger(&self) {
+        self.tx.send_replace(true);
+    }
+
+    /// Resolve once shutdown has been triggered
+    pub async fn wait(&self) {
+        let mut rx = self.tx.subscribe();
+        let _ = rx.wait_for(|triggered| *triggered).await;
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Trigger `shutdown` and wait for `tasks` in order, aborting any still running once `timeout`
+/// has passed in total, then flush `store` so nothing written before the signal is lost
+pub async fn drain(
+    shutdown: &Shutdown,
+    tasks: Vec<(&str, JoinHandle<()>)>,
+    store: &RocksDBStore,
+    timeout: Duration,
+) -> Result<()> {
+    shutdown.trigger();
+    let deadline = tokio::time::Instant::now() + timeout;
+    for (name, mut handle) in tasks {
+        if tokio::time::timeout_at(deadline, &mut handle)
+            .await
+            .is_err()
+        {
+            warn!("{} did not finish within {:?}, aborting", name, timeout);
+            handle.abort();
+        }
+    }
+    store.flush().await
+}
+
+/// Wait for SIGINT (Ctrl+C) or, on Unix, SIGTERM
+pub async fn wait_for_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        let mut sigterm =
+            signal(SignalKind::terminate()).context("Failed to listen for SIGTERM")?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result.context("Failed to listen for SIGINT")?,
+            _ = sigterm.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c()
+            .await
+            .context("Failed to listen for shutdown signal")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::dedupe_engine::DeduplicationEngine;
+    use crate::core::event_router::EventRouter;
+    use crate::core::relay_pool::RelayEvent;
+    use crate::test_support::{signed_events, temp_store};
+    use std::collections::HashSet;
+
+    const MEMTABLE_ENTRIES: &str = "rocksdb.num-entries-active-mem-table";
+
+    async fn memtable_entries(store: &RocksDBStore) -> u64 {
+        store.int_properties(&[MEMTABLE_ENTRIES]).await[0].1
+    }
+
+    #[tokio::test]
+    async fn drain_flushes_pending_events_and_the_store() {
+        let (_dir, store) = temp_store();
+        let dedupe = Arc::new(DeduplicationEngine::new(store.clone()));
+        let (downstream_tx, downstream_rx) = flume::unbounded();
+        let shutdown = Shutdown::new();
+        // A batch larger than the input and a long latency keep events pending until shutdown
+        let router = EventRouter::new(dedupe, 1000, Duration::from_secs(3600), downstream_tx)
+            .with_shutdown(shutdown.clone());
+        let (input_tx, input_rx) = flume::unbounded();
+        let events = signed_events(3);
+        for event in &events {
+            input_tx
+                .send(RelayEvent::received("ws://relay.test", event.clone()))
+                .unwrap();
+        }
+        let router_handle = tokio::spawn(async move {
+            router.process_stream(input_rx).await.unwrap();
+        });
+        while !input_tx.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(downstream_rx.is_empty());
+
+        let tasks = vec![("Event router", router_handle)];
+        drain(&shutdown, tasks, &store, Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        let forwarded: HashSet<_> = downstream_rx
+            .drain()
+            .map(|routed| routed.event.id)
+            .collect();
+        assert_eq!(forwarded, events.iter().map(|event| event.id).collect());
+        // Every stored event has been flushed out of the memtable
+        assert_eq!(memtable_entries(&store).await, 0);
+        for event in &events {
+            assert!(store.get_event(&event.id.to_hex()).await.unwrap().is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn drain_aborts_tasks_that_outlive_the_timeout() {
+        let (_dir, store) = temp_store();
+        let stuck = tokio::spawn(std::future::pending::<()>());
+
+        let started = tokio::time::Instant::now();
+        drain(
+            &Shutdown::new(),
+            vec![("Stuck", stuck)],
+            &store,
+            Duration::from_millis(50),
+        )
+        .await
+        .unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
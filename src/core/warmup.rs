@@ -0,0 +1,93 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Paces ingest to a fixed rate for a while after startup, so relays delivering history on
+/// connect do not all hit the dedup engine and RocksDB at once
+pub struct WarmupThrottle {
+    started: Instant,
+    duration: Duration,
+    /// Time between two admitted events
+    interval: Duration,
+    /// Earliest time the next event may be admitted
+    next_slot: Mutex<Instant>,
+}
+
+impl WarmupThrottle {
+    /// Admit at most `rate` events per second during the first `duration`
+    pub fn new(rate: u32, duration: Duration) -> Self {
+        let started = Instant::now();
+        Self {
+            started,
+            duration,
+            interval: Duration::from_secs(1) / rate.max(1),
+            next_slot: Mutex::new(started),
+        }
+    }
+
+    /// Whether the warmup period is still running
+    pub fn is_warming_up(&self) -> bool {
+        self.started.elapsed() < self.duration
+    }
+
+    /// Wait for the next slot while warming up; returns at once afterwards
+    pub async fn acquire(&self) {
+        if !self.is_warming_up() {
+            return;
+        }
+        let wait = {
+            let mut next_slot = self.next_slot.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let slot = (*next_slot).max(now);
+            *next_slot = slot + self.interval;
+            slot - now
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn concurrent_ingest_during_warmup_stays_under_the_rate() {
+        let throttle = Arc::new(WarmupThrottle::new(20, Duration::from_secs(60)));
+        let started = Instant::now();
+
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let throttle = throttle.clone();
+                tokio::spawn(async move {
+                    for _ in 0..5 {
+                        throttle.acquire().await;
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        // The first event goes through at once, each of the other 19 waits its 50ms slot
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(950), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(3), "{:?}", elapsed);
+        assert!(throttle.is_warming_up());
+    }
+
+    #[tokio::test]
+    async fn the_throttle_lifts_once_warmup_is_over() {
+        let throttle = WarmupThrottle::new(1, Duration::from_millis(100));
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(!throttle.is_warming_up());
+
+        let started = Instant::now();
+        for _ in 0..10 {
+            throttle.acquire().await;
+        }
+        assert!(started.elapsed() < Duration::from_millis(100));
+    }
+}
@@ -1,36 +1,49 @@
 mod api;
 mod config;
 mod core;
+mod output;
 mod storage;
+#[cfg(test)]
+mod test_support;
 
 use anyhow::{Context, Result};
 use api::{metrics::Metrics, rest_api, websocket};
+use axum_server::tls_rustls::RustlsConfig;
 use clap::Parser;
-use config::AppConfig;
+use config::{AppConfig, LogFormat, SinkEndpoint, TlsConfig};
 use core::{
-    dedupe_engine::DeduplicationEngine, downstream::DownstreamForwarder, event_router::EventRouter,
-    relay_pool::RelayPool,
+    dedupe_engine::DeduplicationEngine,
+    downstream::DownstreamForwarder,
+    event_router::EventRouter,
+    relay_pool::{RelayPool, dedup_relay_urls},
+    shutdown::{self, Shutdown},
 };
+use nostr_sdk::Filter;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
-use storage::rocksdb_store::RocksDBStore;
-use tokio::signal;
+use storage::rocksdb_store::{MONITORED_PROPERTIES, RocksDBStore};
 use tracing::{error, info, warn};
-use tracing_subscriber;
+use tracing_subscriber::EnvFilter;
 
 #[derive(Parser, Debug)]
 #[command(name = "iso-relayer")]
 #[command(about = "ISO Relayer service", version)]
 struct Cli {
-    /// Path to configuration TOML file
+    /// Path to configuration file (.toml, .yaml/.yml or .json)
     #[arg(long)]
     config: Option<std::path::PathBuf>,
+
+    /// Load and validate the configuration, print what would start, then exit
+    #[arg(long, requires = "config")]
+    check_config: bool,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // CLI
     let cli = Cli::parse();
+    install_crypto_provider();
 
     // Load config if provided
     let cfg: Option<AppConfig> = match &cli.config {
@@ -38,47 +51,148 @@ async fn main() -> Result<()> {
         None => None,
     };
 
+    if cli.check_config {
+        if let Some(cfg) = &cfg {
+            print_config_summary(cfg);
+        }
+        return Ok(());
+    }
+
     // Initialize tracing - prefer config log level if provided, else env, else default
     let default_level = cfg
         .as_ref()
         .map(|c| c.monitoring.log_level.clone())
         .unwrap_or_else(|| "info".to_string());
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| format!("iso_relayer={}", default_level).into()),
-        )
-        .init();
+    let log_format = cfg
+        .as_ref()
+        .map(|c| c.monitoring.log_format)
+        .unwrap_or_default();
+    let (subscriber, reload_log_level) = build_subscriber(&default_level, log_format);
+    tracing::subscriber::set_global_default(subscriber).context("Failed to initialize tracing")?;
 
     info!("Starting ISO Relayer...");
 
     // Initialize metrics
-    let metrics = Arc::new(Metrics::new().context("Failed to initialize metrics")?);
+    let mut metrics = Metrics::new().context("Failed to initialize metrics")?;
+    if let Some(c) = &cfg {
+        metrics = metrics.with_duplicate_ratio_window(Duration::from_secs(
+            c.monitoring.duplicate_ratio_window_secs,
+        ));
+    }
+    let metrics = Arc::new(metrics);
 
     // Initialize RocksDB storage
     let rocks_path = cfg
         .as_ref()
-        .map(|c| c.deduplication.rocksdb_path.as_str())
-        .unwrap_or("./data/rocksdb");
-    let rocksdb =
-        Arc::new(RocksDBStore::new(rocks_path).context("Failed to initialize RocksDB storage")?);
+        .map(|c| c.deduplication.rocksdb_path.clone())
+        .unwrap_or_else(|| "./data/rocksdb".to_string());
+    let rocks_tuning = cfg
+        .as_ref()
+        .map(|c| c.deduplication.rocksdb.clone())
+        .unwrap_or_default();
+    // Opening blocks (WAL replay, retry delays), so keep it off the runtime's workers
+    let rocksdb = Arc::new(
+        tokio::task::spawn_blocking(move || RocksDBStore::with_tuning(rocks_path, &rocks_tuning))
+            .await
+            .context("RocksDB open task panicked")?
+            .context("Failed to initialize RocksDB storage")?,
+    );
     info!("RocksDB storage initialized");
 
+    // Periodically prune events older than the configured retention window of their kind
+    if let Some(dedup_cfg) = cfg.as_ref().map(|c| c.deduplication.clone()) {
+        let retention = dedup_cfg.retention_policy();
+        if retention.shortest().is_some() {
+            let rocksdb = rocksdb.clone();
+            let prune_interval = Duration::from_secs(dedup_cfg.prune_interval_secs.max(1));
+            info!(
+                "Event retention enabled (default: {:?}s, per kind: {:?})",
+                retention.default_secs, retention.per_kind
+            );
+            tokio::spawn(async move {
+                let mut interval_timer = tokio::time::interval(prune_interval);
+                loop {
+                    interval_timer.tick().await;
+                    let now = chrono::Utc::now().timestamp().max(0) as u64;
+                    match rocksdb.prune_expired(now, &retention).await {
+                        Ok(pruned) => info!("Pruned {} expired events", pruned),
+                        Err(e) => error!("Failed to prune expired events: {}", e),
+                    }
+                }
+            });
+        }
+    }
+
     // Initialize deduplication engine
     let dedupe_engine = match &cfg {
-        Some(c) => Arc::new(
-            DeduplicationEngine::new_with_params(
+        Some(c) => {
+            let mut engine = DeduplicationEngine::new_with_params(
                 rocksdb.clone(),
                 c.deduplication.hotset_size,
                 c.deduplication.bloom_capacity,
+                c.deduplication.bloom_fp_rate,
                 c.deduplication.lru_size,
             )
-            .with_metrics(metrics.clone()),
-        ),
+            .with_dedup_key(c.deduplication.dedup_key)
+            .with_rocksdb_breaker(&c.deduplication.rocksdb_circuit_breaker)
+            .with_relay_attribution(
+                c.relay.relay_priority.clone(),
+                Duration::from_millis(c.deduplication.attribution_window_ms),
+            )
+            .with_metrics(metrics.clone());
+            if let Some(secs) = c.deduplication.dedup_window_secs {
+                engine = engine.with_dedup_window(Duration::from_secs(secs));
+            }
+            if !c.deduplication.dedup_bypass_pubkeys.is_empty() {
+                let bypass = c.deduplication.bypass_pubkeys()?;
+                info!("Deduplication bypassed for {} pubkeys", bypass.len());
+                engine = engine.with_bypass_pubkeys(bypass);
+            }
+            if let Some(rate) = c.deduplication.startup_ingest_rate {
+                let warmup = Duration::from_secs(c.deduplication.startup_warmup_secs);
+                info!(
+                    "Ingest capped at {} events/s for the first {:?}",
+                    rate, warmup
+                );
+                engine = engine.with_startup_throttle(rate, warmup);
+            }
+            Arc::new(engine)
+        }
         None => Arc::new(DeduplicationEngine::new(rocksdb.clone()).with_metrics(metrics.clone())),
     };
     info!("Deduplication engine initialized");
 
+    // Reload the bloom filter saved by the previous run and keep saving it periodically
+    let bloom_snapshot = cfg.as_ref().and_then(|c| {
+        c.deduplication.bloom_persist_interval_secs.map(|secs| {
+            (
+                c.deduplication.bloom_snapshot_path(),
+                Duration::from_secs(secs),
+            )
+        })
+    });
+    if let Some((path, persist_interval)) = bloom_snapshot.clone() {
+        match dedupe_engine.load_bloom(&path).await {
+            Ok(true) => info!("Bloom filter restored from {}", path.display()),
+            Ok(false) => info!(
+                "No bloom filter snapshot at {}, starting empty",
+                path.display()
+            ),
+            Err(e) => warn!("Ignoring bloom filter snapshot: {:#}", e),
+        }
+        let dedupe_engine = dedupe_engine.clone();
+        tokio::spawn(async move {
+            let mut interval_timer = tokio::time::interval(persist_interval);
+            interval_timer.tick().await;
+            loop {
+                interval_timer.tick().await;
+                if let Err(e) = dedupe_engine.save_bloom(&path).await {
+                    error!("Failed to save bloom filter: {:#}", e);
+                }
+            }
+        });
+    }
+
     // Warm dedup engine from RocksDB successful-forward index to avoid duplicate downstream sends after restart
     let warm_limit = cfg
         .as_ref()
@@ -94,8 +208,52 @@ async fn main() -> Result<()> {
         ),
         None => (Duration::from_secs(30), 10_000),
     };
-    let (relay_pool, relay_event_rx) = RelayPool::new(health_check_interval, max_connections);
-    let relay_pool = Arc::new(relay_pool.with_metrics(metrics.clone()));
+    let reconnect_policy = cfg
+        .as_ref()
+        .map(|c| c.relay.reconnect_policy.clone())
+        .unwrap_or_default();
+    let health_policy = cfg
+        .as_ref()
+        .map(|c| c.relay.health.clone())
+        .unwrap_or_default();
+    let auth_keys = match &cfg {
+        Some(c) => c.relay.auth_keys()?,
+        None => HashMap::new(),
+    };
+    let subscription_filter = match &cfg {
+        Some(c) => c.relay.subscription.filter()?,
+        None => Filter::new(),
+    };
+    let persist_relays = cfg.as_ref().is_some_and(|c| c.relay.persist_relays);
+    let (mut relay_pool, relay_event_rx) = RelayPool::new(health_check_interval, max_connections);
+    if persist_relays {
+        relay_pool = relay_pool.with_relay_persistence(rocksdb.clone());
+    }
+    if let Some(c) = &cfg {
+        relay_pool = relay_pool
+            .with_connect_timeout(Duration::from_millis(c.relay.connect_timeout_ms))
+            .with_max_event_bytes(c.deduplication.max_event_bytes)
+            .with_ingest_limits(c.ingest)
+            .with_rate_limits(
+                c.relay.max_events_per_sec,
+                c.relay.relay_max_events_per_sec.clone(),
+            );
+        if let Some(secs) = c.relay.initial_backfill_secs {
+            relay_pool = relay_pool.with_initial_backfill(Duration::from_secs(secs));
+        }
+    }
+    let nip11_ttl_secs = cfg.as_ref().map_or(3600, |c| c.relay.nip11_ttl_secs);
+    if nip11_ttl_secs > 0 {
+        relay_pool = relay_pool.with_relay_info(Duration::from_secs(nip11_ttl_secs));
+    }
+    let relay_pool = Arc::new(
+        relay_pool
+            .with_reconnect_policy(reconnect_policy)
+            .with_health_policy(health_policy)
+            .with_auth_keys(auth_keys)
+            .with_subscription_filter(subscription_filter)
+            .with_metrics(metrics.clone()),
+    );
     info!("Relay pool initialized");
 
     // Start health checks
@@ -103,10 +261,24 @@ async fn main() -> Result<()> {
     info!("Health checks started");
 
     // Connect to relays (example - load from config file or environment)
-    let relay_urls = match &cfg {
+    let bootstrap_urls = match &cfg {
         Some(c) => c.relay.bootstrap_relays.clone(),
         None => load_relay_urls().await?,
     };
+    // Equivalent spellings of one relay would otherwise race to connect it twice
+    let (mut relay_urls, duplicates) = dedup_relay_urls(bootstrap_urls);
+    for url in duplicates {
+        warn!("Ignoring duplicate bootstrap relay {}", url);
+    }
+    if persist_relays {
+        let persisted = rocksdb
+            .load_relays()
+            .await
+            .context("Failed to load persisted relays")?;
+        info!("Restoring {} persisted relays", persisted.len());
+        relay_urls.extend(persisted);
+        relay_urls = dedup_relay_urls(relay_urls).0;
+    }
     info!("Loading {} relay URLs", relay_urls.len());
 
     relay_pool
@@ -115,17 +287,48 @@ async fn main() -> Result<()> {
         .context("Failed to subscribe to relays")?;
     info!("Subscribed to all relays");
 
+    // Re-read the config file on SIGHUP and apply what can change without a restart
+    if let (Some(path), Some(running)) = (cli.config.clone(), cfg.clone()) {
+        tokio::spawn(watch_config_reload(
+            path,
+            running,
+            relay_pool.clone(),
+            reload_log_level,
+        ));
+    }
+
     // Create downstream event channel
-    let (downstream_tx, downstream_rx) = flume::unbounded();
+    let (downstream_tx, downstream_rx) = match cfg.as_ref().and_then(|c| c.output.queue_capacity) {
+        Some(capacity) => flume::bounded(capacity),
+        None => flume::unbounded(),
+    };
+    let overflow_policy = cfg
+        .as_ref()
+        .map(|c| c.output.overflow_policy)
+        .unwrap_or_default();
 
-    // Initialize event router
+    // Shutdown coordinator shared by the router and HTTP server
+    let shutdown = Shutdown::new();
+    let max_latency =
+        Duration::from_millis(cfg.as_ref().map(|c| c.output.max_latency_ms).unwrap_or(100) as u64);
+
+    // Initialize event router (owns the only downstream sender, so the channel closes with it)
     let event_router = EventRouter::new(
         dedupe_engine.clone(),
         cfg.as_ref().map(|c| c.output.batch_size).unwrap_or(100), // batch size
-        Duration::from_millis(cfg.as_ref().map(|c| c.output.max_latency_ms).unwrap_or(100) as u64), // max latency
-        downstream_tx.clone(),
+        max_latency,
+        downstream_tx,
     )
-    .with_metrics(metrics.clone());
+    .with_overflow_policy(overflow_policy, downstream_rx.clone())
+    .with_metrics(metrics.clone())
+    .with_shutdown(shutdown.clone());
+    let event_router = match cfg.as_ref().and_then(|c| c.output.reorder_window_ms) {
+        Some(window_ms) => {
+            info!("Reordering events by created_at within {}ms", window_ms);
+            event_router.with_reorder_window(Duration::from_millis(window_ms))
+        }
+        None => event_router,
+    };
 
     // Spawn event router task
     let router_handle = tokio::spawn(async move {
@@ -135,8 +338,17 @@ async fn main() -> Result<()> {
     });
 
     // Create REST API router
-    let rest_router =
-        rest_api::create_router(relay_pool.clone(), dedupe_engine.clone(), metrics.clone());
+    let rest_router = rest_api::create_router(
+        relay_pool.clone(),
+        dedupe_engine.clone(),
+        metrics.clone(),
+        rocksdb.clone(),
+        cfg.as_ref().and_then(|c| c.security.api_key.clone()),
+        cfg.as_ref()
+            .map(|c| c.monitoring.cors.allowed_origins.as_slice())
+            .unwrap_or_default(),
+        cfg.clone().map(Arc::new),
+    );
 
     // Handle downstream forwarding based on config
     let websocket_enabled = cfg
@@ -144,10 +356,56 @@ async fn main() -> Result<()> {
         .map(|c| c.output.websocket_enabled)
         .unwrap_or(true);
 
+    let heartbeat = match &cfg {
+        Some(c) => websocket::Heartbeat {
+            ping_interval: Duration::from_secs(c.output.ws_ping_interval_secs.max(1)),
+            idle_timeout: Duration::from_secs(c.output.ws_idle_timeout_secs.max(1)),
+        },
+        None => websocket::Heartbeat {
+            ping_interval: Duration::from_secs(30),
+            idle_timeout: Duration::from_secs(90),
+        },
+    };
+    // Relay status changes are pushed whether or not events are streamed over WebSocket
+    let rest_router = rest_router.merge(websocket::create_control_router(
+        relay_pool.clone(),
+        heartbeat,
+    ));
+
+    let mut forwarder_handle = None;
     let app = if websocket_enabled {
-        // Create WebSocket router (share the downstream event stream)
-        let downstream_rx_arc = Arc::new(downstream_rx);
-        let ws_router = websocket::create_websocket_router(downstream_rx_arc.clone());
+        // Create WebSocket router (fan out the downstream event stream to every client)
+        let limits = match &cfg {
+            Some(c) => websocket::ClientLimits {
+                buffer: c.output.ws_client_buffer,
+                slow_disconnect_after: c.output.slow_client_disconnect_after,
+                max_subscriptions: c.output.max_subscriptions_per_client,
+            },
+            None => websocket::ClientLimits {
+                buffer: 1024,
+                slow_disconnect_after: None,
+                max_subscriptions: 20,
+            },
+        };
+        let allowed_pubkeys = match cfg.as_ref().and_then(|c| c.output.auth.as_ref()) {
+            Some(auth) => {
+                let keys = auth.pubkeys()?;
+                info!(
+                    "WebSocket clients must authenticate (NIP-42), {} pubkeys allowed",
+                    keys.len()
+                );
+                Some(Arc::new(keys))
+            }
+            None => None,
+        };
+        let ws_router = websocket::create_websocket_router(
+            downstream_rx,
+            heartbeat,
+            limits,
+            allowed_pubkeys,
+            rocksdb.clone(),
+            metrics.clone(),
+        );
         axum::Router::new().merge(rest_router).merge(ws_router)
     } else {
         // Forward events via TCP or HTTP instead of WebSocket
@@ -159,22 +417,57 @@ async fn main() -> Result<()> {
             .as_ref()
             .map(|c| c.output.downstream_rest.clone())
             .unwrap_or_default();
+        let downstream_file = cfg.as_ref().and_then(|c| c.output.downstream_file.clone());
+        let downstream_kafka = cfg.as_ref().and_then(|c| c.output.downstream_kafka.clone());
 
-        if !downstream_tcp.is_empty() || !downstream_rest.is_empty() {
-            let forwarder = DownstreamForwarder::new(
+        if !downstream_tcp.is_empty()
+            || !downstream_rest.is_empty()
+            || downstream_file.is_some()
+            || downstream_kafka.is_some()
+        {
+            let mut forwarder = DownstreamForwarder::new(
                 downstream_tcp.clone(),
                 downstream_rest.clone(),
                 rocksdb.clone(),
-            );
+            )
+            .with_batching(
+                cfg.as_ref().map(|c| c.output.batch_size).unwrap_or(100),
+                max_latency,
+            )
+            .with_metrics(metrics.clone());
+            if let Some(max) = cfg.as_ref().and_then(|c| c.output.adaptive_batch_max) {
+                forwarder = forwarder.with_adaptive_batching(max);
+            }
+            if let Some(c) = &cfg {
+                forwarder = forwarder
+                    .with_sink_policy(
+                        c.output.sink_retry_policy.clone(),
+                        c.output.sink_failure_policy,
+                    )
+                    .with_circuit_breaker(c.output.sink_circuit_breaker.clone())
+                    .with_rest_pool(c.output.rest_pool.clone());
+            }
+            if let Some(file) = &downstream_file {
+                info!("File forwarding enabled ({})", file.path);
+                forwarder = forwarder.with_file(file.clone());
+            }
+            if let Some(kafka) = &downstream_kafka {
+                info!("Kafka forwarding enabled (topic: {})", kafka.topic);
+                forwarder = forwarder.with_kafka(kafka.clone());
+            }
             let downstream_rx_for_forwarder = downstream_rx;
-            tokio::spawn(async move {
+            forwarder_handle = Some(tokio::spawn(async move {
                 if let Err(e) = forwarder.forward_events(downstream_rx_for_forwarder).await {
                     error!("Downstream forwarder error: {}", e);
                 }
-            });
+            }));
+            let urls = |endpoints: &[SinkEndpoint]| -> Vec<String> {
+                endpoints.iter().map(|e| e.url().to_string()).collect()
+            };
             info!(
                 "Downstream forwarding enabled (TCP: {:?}, REST: {:?})",
-                downstream_tcp, downstream_rest
+                urls(&downstream_tcp),
+                urls(&downstream_rest)
             );
         } else {
             warn!(
@@ -190,23 +483,68 @@ async fn main() -> Result<()> {
         Some(c) => format!("0.0.0.0:{}", c.output.websocket_port),
         None => "0.0.0.0:8080".to_string(),
     };
-    info!("Starting HTTP server on {}", addr);
-    let server_addr_for_logs = addr.clone();
-    let server_handle = tokio::spawn(async move {
-        let listener = tokio::net::TcpListener::bind(addr)
-            .await
-            .context("Failed to bind to address")
-            .unwrap();
-        axum::serve(listener, app)
-            .await
-            .context("Failed to start server")
-            .unwrap();
-    });
+    let unix_socket = cfg.as_ref().and_then(|c| c.monitoring.unix_socket.clone());
+    let server_addr_for_logs = match &unix_socket {
+        Some(path) => format!("unix:{}", path),
+        None => addr.clone(),
+    };
+    info!("Starting HTTP server on {}", server_addr_for_logs);
+    let server_shutdown = shutdown.clone();
+    let tls = cfg.as_ref().and_then(|c| c.output.tls.clone());
+    let (http_scheme, ws_scheme) = if tls.is_some() {
+        ("https", "wss")
+    } else {
+        ("http", "ws")
+    };
+    let server_handle = match (unix_socket, tls) {
+        (Some(path), _) => serve_unix(path, app, server_shutdown)?,
+        (None, Some(tls)) => {
+            let listener = std::net::TcpListener::bind(&addr)
+                .with_context(|| format!("Failed to bind to address {}", addr))?;
+            serve_tls(listener, &tls, app, server_shutdown).await?
+        }
+        (None, None) => tokio::spawn(async move {
+            let listener = tokio::net::TcpListener::bind(addr)
+                .await
+                .context("Failed to bind to address")
+                .unwrap();
+            axum::serve(listener, app)
+                .with_graceful_shutdown(async move { server_shutdown.wait().await })
+                .await
+                .context("Failed to start server")
+                .unwrap();
+        }),
+    };
 
     info!("ISO Relayer started successfully");
-    info!("REST API: http://{}", server_addr_for_logs);
-    info!("WebSocket: ws://{}/ws", server_addr_for_logs);
-    info!("Metrics: http://{}/metrics", server_addr_for_logs);
+    info!("REST API: {}://{}", http_scheme, server_addr_for_logs);
+    info!("WebSocket: {}://{}/ws", ws_scheme, server_addr_for_logs);
+    info!(
+        "Relay status changes: {}://{}/ws/events/control",
+        ws_scheme, server_addr_for_logs
+    );
+    info!(
+        "Metrics: {}://{}/metrics",
+        http_scheme, server_addr_for_logs
+    );
+
+    // Periodically publish RocksDB internals, on the relay health check cadence
+    {
+        let metrics = metrics.clone();
+        let rocksdb = rocksdb.clone();
+        tokio::spawn(async move {
+            let mut interval_timer = tokio::time::interval(health_check_interval);
+            loop {
+                interval_timer.tick().await;
+                for (name, value) in rocksdb.int_properties(MONITORED_PROPERTIES).await {
+                    metrics
+                        .rocksdb_properties
+                        .with_label_values(&[name])
+                        .set(value as f64);
+                }
+            }
+        });
+    }
 
     // Periodically update memory usage gauge
     {
@@ -227,19 +565,313 @@ async fn main() -> Result<()> {
         });
     }
     // Wait for shutdown signal
-    signal::ctrl_c()
-        .await
-        .context("Failed to listen for shutdown signal")?;
+    shutdown::wait_for_signal().await?;
     info!("Shutdown signal received, gracefully shutting down...");
 
-    // Cancel tasks
-    router_handle.abort();
-    server_handle.abort();
+    // Stop accepting connections, let the router and outputs deliver pending events, then
+    // persist everything written so far
+    let mut tasks = vec![("Event router", router_handle)];
+    if let Some(handle) = forwarder_handle {
+        tasks.push(("Downstream forwarder", handle));
+    }
+    tasks.push(("HTTP server", server_handle));
+    let shutdown_timeout =
+        Duration::from_secs(cfg.as_ref().map_or(30, |c| c.output.shutdown_timeout_secs));
+    if let Err(e) = shutdown::drain(&shutdown, tasks, &rocksdb, shutdown_timeout).await {
+        error!("Failed to flush RocksDB on shutdown: {}", e);
+    }
+    if let Some((path, _)) = bloom_snapshot {
+        if let Err(e) = dedupe_engine.save_bloom(&path).await {
+            error!("Failed to save bloom filter on shutdown: {:#}", e);
+        }
+    }
 
     info!("Shutdown complete");
     Ok(())
 }
 
+/// Print what the service would start with the given (already validated) configuration
+fn print_config_summary(cfg: &AppConfig) {
+    println!("Configuration OK");
+    println!(
+        "  relays: {} bootstrap (max {} connections, health check every {}s)",
+        cfg.relay.bootstrap_relays.len(),
+        cfg.relay.max_connections,
+        cfg.relay.health_check_interval
+    );
+    println!(
+        "  deduplication: rocksdb at {}, hot set {}, bloom {} (fp rate {}), lru {}, key {:?}",
+        cfg.deduplication.rocksdb_path,
+        cfg.deduplication.hotset_size,
+        cfg.deduplication.bloom_capacity,
+        cfg.deduplication.bloom_fp_rate,
+        cfg.deduplication.lru_size,
+        cfg.deduplication.dedup_key
+    );
+    match (&cfg.monitoring.unix_socket, &cfg.output.tls) {
+        (Some(path), _) => println!("  http: unix socket {}", path),
+        (None, Some(tls)) => println!(
+            "  https: 0.0.0.0:{} (certificate {})",
+            cfg.output.websocket_port, tls.cert_path
+        ),
+        (None, None) => println!("  http: 0.0.0.0:{}", cfg.output.websocket_port),
+    }
+    if cfg.output.websocket_enabled {
+        println!("  output: WebSocket at /ws");
+    } else {
+        println!(
+            "  output: {} TCP sink(s), {} REST sink(s), file {}, Kafka {}",
+            cfg.output.downstream_tcp.len(),
+            cfg.output.downstream_rest.len(),
+            cfg.output
+                .downstream_file
+                .as_ref()
+                .map_or("disabled", |f| f.path.as_str()),
+            cfg.output
+                .downstream_kafka
+                .as_ref()
+                .map_or("disabled", |k| k.topic.as_str())
+        );
+    }
+    println!(
+        "  api key: {}",
+        if cfg.security.api_key.is_some() {
+            "required for mutating routes"
+        } else {
+            "not configured"
+        }
+    );
+}
+
+/// Replaces the active log filter with the given level for this crate
+type LogLevelReload = Box<dyn Fn(&str) -> Result<()> + Send + Sync>;
+
+/// Build the tracing subscriber for the given default level and output format, together with
+/// a hook to change the level later. `RUST_LOG` still takes precedence over the configured level.
+fn build_subscriber(
+    default_level: &str,
+    format: LogFormat,
+) -> (Box<dyn tracing::Subscriber + Send + Sync>, LogLevelReload) {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| format!("iso_relayer={}", default_level).into());
+    let level_filter = |level: &str| {
+        EnvFilter::try_new(format!("iso_relayer={}", level))
+            .with_context(|| format!("Invalid log level {}", level))
+    };
+    match format {
+        LogFormat::Text => {
+            let builder = tracing_subscriber::fmt()
+                .with_env_filter(filter)
+                .with_filter_reloading();
+            let handle = builder.reload_handle();
+            let reload: LogLevelReload = Box::new(move |level| {
+                handle
+                    .reload(level_filter(level)?)
+                    .context("Failed to reload log filter")
+            });
+            (Box::new(builder.finish()), reload)
+        }
+        LogFormat::Json => {
+            let builder = tracing_subscriber::fmt()
+                .json()
+                .flatten_event(true)
+                .with_env_filter(filter)
+                .with_filter_reloading();
+            let handle = builder.reload_handle();
+            let reload: LogLevelReload = Box::new(move |level| {
+                handle
+                    .reload(level_filter(level)?)
+                    .context("Failed to reload log filter")
+            });
+            (Box::new(builder.finish()), reload)
+        }
+    }
+}
+
+/// Reload the config file on every SIGHUP: connect added bootstrap relays, disconnect removed
+/// ones and apply a new log level. Other changes are logged and ignored until a restart.
+#[cfg(unix)]
+async fn watch_config_reload(
+    path: std::path::PathBuf,
+    mut running: AppConfig,
+    relay_pool: Arc<RelayPool>,
+    reload_log_level: LogLevelReload,
+) {
+    use tokio::signal::unix::{SignalKind, signal};
+    let mut sighup = match signal(SignalKind::hangup()) {
+        Ok(sighup) => sighup,
+        Err(e) => {
+            error!("Failed to listen for SIGHUP, config reload disabled: {}", e);
+            return;
+        }
+    };
+
+    while sighup.recv().await.is_some() {
+        info!("SIGHUP received, reloading {}", path.display());
+        reload_config(&path, &mut running, &relay_pool, &reload_log_level).await;
+    }
+}
+
+/// Re-read the config file and apply it to the running service. `running` takes on the new
+/// config, except for a log level that could not be applied, so a change that needs a restart
+/// is only warned about once.
+#[cfg(unix)]
+async fn reload_config(
+    path: &std::path::Path,
+    running: &mut AppConfig,
+    relay_pool: &RelayPool,
+    reload_log_level: &LogLevelReload,
+) {
+    let mut new = match AppConfig::load_from_path(path) {
+        Ok(new) => new,
+        Err(e) => {
+            error!("Config reload failed, keeping the running config: {:#}", e);
+            return;
+        }
+    };
+    let diff = running.diff(&new);
+    if diff.is_empty() {
+        info!("Config unchanged");
+        return;
+    }
+    if !diff.requires_restart.is_empty() {
+        warn!(
+            "Ignoring config changes that need a restart: {}",
+            diff.requires_restart.join(", ")
+        );
+    }
+
+    if let Some(level) = &diff.log_level {
+        if std::env::var_os("RUST_LOG").is_some() {
+            warn!("RUST_LOG is set, ignoring monitoring.log_level change");
+            new.monitoring.log_level = running.monitoring.log_level.clone();
+        } else if let Err(e) = reload_log_level(level) {
+            error!("Failed to apply log level {}: {:#}", level, e);
+            new.monitoring.log_level = running.monitoring.log_level.clone();
+        } else {
+            info!("Log level set to {}", level);
+        }
+    }
+    for url in &diff.removed_relays {
+        match relay_pool.disconnect_relay(url).await {
+            Ok(()) => info!("Removed bootstrap relay {}", url),
+            Err(e) => warn!("Failed to remove bootstrap relay {}: {}", url, e),
+        }
+    }
+    if !diff.added_relays.is_empty() {
+        info!(
+            "Connecting {} new bootstrap relays",
+            diff.added_relays.len()
+        );
+        let _ = relay_pool.subscribe_all(diff.added_relays.clone()).await;
+    }
+    *running = new;
+}
+
+/// Config reload relies on SIGHUP, which only exists on Unix
+#[cfg(not(unix))]
+async fn watch_config_reload(
+    _path: std::path::PathBuf,
+    _running: AppConfig,
+    _relay_pool: Arc<RelayPool>,
+    _reload_log_level: LogLevelReload,
+) {
+}
+
+/// Serve the router on a Unix domain socket, removing the socket file once the server stops
+#[cfg(unix)]
+fn serve_unix(
+    path: String,
+    app: axum::Router,
+    shutdown: Shutdown,
+) -> Result<tokio::task::JoinHandle<()>> {
+    let listener = bind_unix_socket(&path)?;
+
+    Ok(tokio::spawn(async move {
+        axum::serve(listener, app)
+            .with_graceful_shutdown(async move { shutdown.wait().await })
+            .await
+            .context("Failed to start server")
+            .unwrap();
+        let _ = std::fs::remove_file(&path);
+    }))
+}
+
+/// Bind a Unix domain socket at `path`. A socket file left behind by a previous run is
+/// removed first; one that still accepts connections belongs to a live server and is kept.
+#[cfg(unix)]
+fn bind_unix_socket(path: &str) -> Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::FileTypeExt;
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        anyhow::ensure!(
+            metadata.file_type().is_socket(),
+            "{} exists and is not a socket",
+            path
+        );
+        anyhow::ensure!(
+            std::os::unix::net::UnixStream::connect(path).is_err(),
+            "{} is in use by another server",
+            path
+        );
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale socket {}", path))?;
+    }
+    tokio::net::UnixListener::bind(path)
+        .with_context(|| format!("Failed to bind to Unix socket {}", path))
+}
+
+/// Serve the REST API, metrics and WebSocket endpoints over HTTPS/WSS on `listener`, presenting
+/// the configured certificate
+async fn serve_tls(
+    listener: std::net::TcpListener,
+    tls: &TlsConfig,
+    app: axum::Router,
+    shutdown: Shutdown,
+) -> Result<tokio::task::JoinHandle<()>> {
+    let rustls_config = RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to load TLS certificate {} / key {}",
+                tls.cert_path, tls.key_path
+            )
+        })?;
+    listener
+        .set_nonblocking(true)
+        .context("Failed to configure the TLS listener")?;
+    let handle = axum_server::Handle::new();
+    let shutdown_handle = handle.clone();
+    tokio::spawn(async move {
+        shutdown.wait().await;
+        shutdown_handle.graceful_shutdown(None);
+    });
+    Ok(tokio::spawn(async move {
+        axum_server::from_tcp_rustls(listener, rustls_config)
+            .handle(handle)
+            .serve(app.into_make_service())
+            .await
+            .context("Failed to start TLS server")
+            .unwrap();
+    }))
+}
+
+/// Select ring as the process-wide rustls crypto provider. rustls refuses to pick a default
+/// itself when dependencies enable more than one backend, which would fail TLS setup.
+fn install_crypto_provider() {
+    // Only fails when a provider is already installed
+    let _ = rustls::crypto::ring::default_provider().install_default();
+}
+
+/// Unix sockets are rejected by config validation on other platforms
+#[cfg(not(unix))]
+fn serve_unix(
+    _path: String,
+    _app: axum::Router,
+    _shutdown: Shutdown,
+) -> Result<tokio::task::JoinHandle<()>> {
+    anyhow::bail!("Unix sockets are only supported on Unix")
+}
+
 /// Load relay URLs from environment or config file
 /// In production, this should load from a config file or database
 async fn load_relay_urls() -> Result<Vec<String>> {
@@ -259,3 +891,238 @@ async fn load_relay_urls() -> Result<Vec<String>> {
         "wss://relay.snort.social".to_string(),
     ])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A self-signed certificate and its key written as PEM files into `dir`
+    fn self_signed(dir: &TempDir) -> (TlsConfig, Vec<u8>) {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let cert_path = dir.path().join("cert.pem");
+        let key_path = dir.path().join("key.pem");
+        std::fs::write(&cert_path, certified.cert.pem()).unwrap();
+        std::fs::write(&key_path, certified.key_pair.serialize_pem()).unwrap();
+        let tls = TlsConfig {
+            cert_path: cert_path.to_string_lossy().into_owned(),
+            key_path: key_path.to_string_lossy().into_owned(),
+        };
+        (tls, certified.cert.der().to_vec())
+    }
+
+    #[tokio::test]
+    async fn the_tls_server_presents_the_configured_certificate() {
+        install_crypto_provider();
+        let dir = TempDir::new().unwrap();
+        let (tls, cert_der) = self_signed(&dir);
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = axum::Router::new().route("/health", axum::routing::get(|| async { "ok" }));
+        serve_tls(listener, &tls, app, Shutdown::new())
+            .await
+            .unwrap();
+
+        // The certificate is self-signed, so it is compared instead of verified
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .tls_info(true)
+            .build()
+            .unwrap();
+        let response = client
+            .get(format!("https://{}/health", addr))
+            .send()
+            .await
+            .unwrap();
+        let tls_info = response
+            .extensions()
+            .get::<reqwest::tls::TlsInfo>()
+            .unwrap();
+        assert_eq!(tls_info.peer_certificate(), Some(cert_der.as_slice()));
+        assert_eq!(response.text().await.unwrap(), "ok");
+    }
+
+    #[tokio::test]
+    async fn the_tls_server_refuses_to_start_without_its_certificate() {
+        install_crypto_provider();
+        let dir = TempDir::new().unwrap();
+        let tls = TlsConfig {
+            cert_path: dir
+                .path()
+                .join("missing.pem")
+                .to_string_lossy()
+                .into_owned(),
+            key_path: dir
+                .path()
+                .join("missing.key")
+                .to_string_lossy()
+                .into_owned(),
+        };
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let error = serve_tls(listener, &tls, axum::Router::new(), Shutdown::new())
+            .await
+            .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("Failed to load TLS certificate")
+        );
+    }
+
+    #[cfg(unix)]
+    /// Write the template config with the given bootstrap relays and RocksDB path to `path`
+    fn write_config(path: &std::path::Path, relays: &[&str], rocksdb_path: &str) {
+        let mut table: toml::Table =
+            toml::from_str(include_str!("../config.template.toml")).unwrap();
+        table["relay"]["bootstrap_relays"] = relays.iter().map(|url| url.to_string()).collect();
+        table["deduplication"]["rocksdb_path"] = rocksdb_path.into();
+        std::fs::write(path, toml::to_string(&table).unwrap()).unwrap();
+    }
+
+    #[cfg(unix)]
+    fn keep_log_level() -> LogLevelReload {
+        Box::new(|_| Ok(()))
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn sighup_connects_a_relay_added_to_the_config_file() {
+        use tokio::signal::unix::{SignalKind, signal};
+        // Listening here replaces the default action, so an early SIGHUP cannot kill the test
+        let _sighup = signal(SignalKind::hangup()).unwrap();
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        let bootstrap = test_support::mock_relay().await;
+        let added = test_support::mock_relay().await;
+        write_config(&path, &[&bootstrap], "./data/rocksdb");
+        let running = AppConfig::load_from_path(&path).unwrap();
+        let (pool, _events) = RelayPool::new(Duration::from_secs(60), 10);
+        let pool = Arc::new(pool);
+        tokio::spawn(watch_config_reload(
+            path.clone(),
+            running,
+            pool.clone(),
+            keep_log_level(),
+        ));
+
+        write_config(&path, &[&bootstrap, &added], "./data/rocksdb");
+        // The watcher may not be listening yet; reloading the same file again is harmless
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while pool.active_connections() == 0 {
+                std::process::Command::new("kill")
+                    .args(["-HUP", &std::process::id().to_string()])
+                    .status()
+                    .unwrap();
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .expect("the added relay was not connected");
+
+        let statuses = pool.get_connection_statuses().await;
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(
+            statuses[0].0.trim_end_matches('/'),
+            added.trim_end_matches('/')
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn a_change_that_needs_a_restart_is_only_reported_once() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        let relay = test_support::mock_relay().await;
+        write_config(&path, &[&relay], "./data/rocksdb");
+        let mut running = AppConfig::load_from_path(&path).unwrap();
+        let (pool, _events) = RelayPool::new(Duration::from_secs(60), 10);
+
+        write_config(&path, &[&relay], "./data/elsewhere");
+        let reread = AppConfig::load_from_path(&path).unwrap();
+        assert_eq!(
+            running.diff(&reread).requires_restart,
+            ["deduplication.rocksdb_path"]
+        );
+        reload_config(&path, &mut running, &pool, &keep_log_level()).await;
+
+        // The next reload of the same file finds nothing new to warn about
+        assert!(running.diff(&reread).is_empty());
+        assert_eq!(pool.active_connections(), 0);
+    }
+
+    #[cfg(unix)]
+    fn socket_path(dir: &TempDir) -> String {
+        dir.path().join("api.sock").to_string_lossy().into_owned()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn a_stale_socket_file_is_replaced() {
+        let dir = TempDir::new().unwrap();
+        let path = socket_path(&dir);
+        // The listener goes away but leaves its socket file behind
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+        let _listener = bind_unix_socket(&path).unwrap();
+        assert!(std::os::unix::net::UnixStream::connect(&path).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn a_socket_in_use_by_another_server_is_kept() {
+        let dir = TempDir::new().unwrap();
+        let path = socket_path(&dir);
+        let _other = std::os::unix::net::UnixListener::bind(&path).unwrap();
+
+        let error = bind_unix_socket(&path).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("{} is in use by another server", path)
+        );
+        assert!(std::path::Path::new(&path).exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn a_file_that_is_not_a_socket_is_kept() {
+        let dir = TempDir::new().unwrap();
+        let path = socket_path(&dir);
+        std::fs::write(&path, "not a socket").unwrap();
+
+        let error = bind_unix_socket(&path).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("{} exists and is not a socket", path)
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a socket");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn the_api_answers_over_a_unix_socket_until_shutdown() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let dir = TempDir::new().unwrap();
+        let path = socket_path(&dir);
+        let app = axum::Router::new().route("/health", axum::routing::get(|| async { "ok" }));
+        let shutdown = Shutdown::new();
+        let server = serve_unix(path.clone(), app, shutdown.clone()).unwrap();
+
+        let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("\r\n\r\nok"), "{}", response);
+
+        shutdown.trigger();
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("server did not stop")
+            .unwrap();
+        assert!(!std::path::Path::new(&path).exists());
+    }
+}
@@ -0,0 +1,127 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::CircuitBreakerConfig;
+
+/// State of a sink's circuit breaker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Deliveries flow normally
+    Closed,
+    /// Deliveries fail fast until the cooldown elapses
+    Open,
+    /// Cooldown elapsed; the next delivery is a probe deciding whether to close again
+    HalfOpen,
+}
+
+impl BreakerState {
+    /// Value exported on the `sink_circuit_state` gauge
+    pub fn as_gauge(&self) -> f64 {
+        match self {
+            BreakerState::Closed => 0.0,
+            BreakerState::Open => 1.0,
+            BreakerState::HalfOpen => 2.0,
+        }
+    }
+
+    /// Inverse of [`BreakerState::as_gauge`]
+    pub fn from_gauge(value: f64) -> Self {
+        if value == 1.0 {
+            BreakerState::Open
+        } else if value == 2.0 {
+            BreakerState::HalfOpen
+        } else {
+            BreakerState::Closed
+        }
+    }
+
+    /// Lowercase name reported by `/status`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BreakerState::Closed => "closed",
+            BreakerState::Open => "open",
+            BreakerState::HalfOpen => "half_open",
+        }
+    }
+}
+
+struct BreakerInner {
+    state: BreakerState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+/// Opens after `failure_threshold` consecutive delivery failures so a dead sink is not
+/// hammered with retries, then lets a single probe through once `cooldown` has passed
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    inner: Mutex<BreakerInner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: &CircuitBreakerConfig) -> Self {
+        Self {
+            failure_threshold: config.failure_threshold.max(1),
+            cooldown: Duration::from_secs(config.cooldown_secs),
+            inner: Mutex::new(BreakerInner {
+                state: BreakerState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BreakerInner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Current state, moving from open to half-open once the cooldown has elapsed
+    pub fn state(&self) -> BreakerState {
+        let mut inner = self.lock();
+        if inner.state == BreakerState::Open
+            && inner
+                .opened_at
+                .is_some_and(|opened| opened.elapsed() >= self.cooldown)
+        {
+            inner.state = BreakerState::HalfOpen;
+        }
+        inner.state
+    }
+
+    /// Whether a delivery attempt may be made now
+    pub fn allow_request(&self) -> bool {
+        self.state() != BreakerState::Open
+    }
+
+    /// Time left before an open circuit lets a probe through
+    pub fn remaining_cooldown(&self) -> Duration {
+        let inner = self.lock();
+        match (inner.state, inner.opened_at) {
+            (BreakerState::Open, Some(opened)) => self.cooldown.saturating_sub(opened.elapsed()),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Record a successful delivery, closing the circuit
+    pub fn record_success(&self) -> BreakerState {
+        let mut inner = self.lock();
+        inner.state = BreakerState::Closed;
+        inner.consecutive_failures = 0;
+        inner.opened_at = None;
+        inner.state
+    }
+
+    /// Record a failed delivery, opening the circuit at the threshold or after a failed probe
+    pub fn record_failure(&self) -> BreakerState {
+        let mut inner = self.lock();
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        if inner.state == BreakerState::HalfOpen
+            || inner.consecutive_failures >= self.failure_threshold
+        {
+            inner.state = BreakerState::Open;
+            inner.opened_at = Some(Instant::now());
+        }
+        inner.state
+    }
+}
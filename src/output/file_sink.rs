@@ -0,0 +1,279 @@
+use anyhow::{Context, Result};
+use chrono::{NaiveDate, Utc};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use nostr_sdk::Event;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tracing::{error, info};
+
+use crate::api::metrics::Metrics;
+use crate::config::FileSinkConfig;
+use crate::output::tcp_sink::encode_ndjson;
+
+/// Downstream sink that appends newline-delimited JSON events to a local file,
+/// moving it aside to a timestamped segment once it grows too large or the day changes
+pub struct FileSink {
+    endpoint: String,
+    path: PathBuf,
+    file: File,
+    /// Bytes in the current segment
+    written: u64,
+    /// UTC day the current segment was opened on
+    opened_on: NaiveDate,
+    rotate_bytes: Option<u64>,
+    rotate_daily: bool,
+    gzip: bool,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl FileSink {
+    /// Open (or keep appending to) the configured file
+    pub async fn open(config: &FileSinkConfig) -> Result<Self> {
+        let path = PathBuf::from(&config.path);
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+        let file = open_append(&path).await?;
+        let written = file.metadata().await.map(|m| m.len()).unwrap_or(0);
+        Ok(Self {
+            endpoint: format!("file://{}", config.path),
+            path,
+            file,
+            written,
+            opened_on: Utc::now().date_naive(),
+            rotate_bytes: config.rotate_size_mb.map(|mb| mb * 1024 * 1024),
+            rotate_daily: config.rotate_daily,
+            gzip: config.gzip,
+            metrics: None,
+        })
+    }
+
+    /// Attach metrics collection
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        metrics.register_delivery_endpoint(&self.endpoint);
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Endpoint label for this sink (`file://<path>`)
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Append a batch and flush it to disk, rotating first if the segment is due
+    pub async fn send_batch(&mut self, events: &[Event]) -> Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let payload = encode_ndjson(events)?;
+
+        let result = self.write(&payload).await;
+        if let Some(m) = &self.metrics {
+            match &result {
+                Ok(()) => m.record_delivery_success(&self.endpoint),
+                Err(_) => m.record_delivery_failure(&self.endpoint),
+            }
+        }
+        result
+    }
+
+    async fn write(&mut self, payload: &[u8]) -> Result<()> {
+        if self.rotation_due(payload.len() as u64) {
+            self.rotate().await?;
+        }
+        self.file
+            .write_all(payload)
+            .await
+            .with_context(|| format!("Failed to write to {}", self.path.display()))?;
+        self.file
+            .flush()
+            .await
+            .with_context(|| format!("Failed to flush {}", self.path.display()))?;
+        self.written += payload.len() as u64;
+        Ok(())
+    }
+
+    /// Whether appending `incoming` bytes should start a new segment.
+    /// An empty segment is never rotated, so a single oversized batch still gets written.
+    fn rotation_due(&self, incoming: u64) -> bool {
+        if self.written == 0 {
+            return false;
+        }
+        let too_large = self
+            .rotate_bytes
+            .is_some_and(|limit| self.written + incoming > limit);
+        let new_day = self.rotate_daily && Utc::now().date_naive() != self.opened_on;
+        too_large || new_day
+    }
+
+    /// Move the current file aside and start a fresh one at the configured path
+    async fn rotate(&mut self) -> Result<()> {
+        self.file.sync_all().await.ok();
+        let segment = segment_path(&self.path);
+        tokio::fs::rename(&self.path, &segment)
+            .await
+            .with_context(|| format!("Failed to rotate {}", self.path.display()))?;
+        self.file = open_append(&self.path).await?;
+        self.written = 0;
+        self.opened_on = Utc::now().date_naive();
+        info!("Rotated {} to {}", self.path.display(), segment.display());
+
+        if self.gzip {
+            // Compress in the background so the event pipeline is not held up
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = gzip_segment(&segment) {
+                    error!("Failed to compress {}: {:#}", segment.display(), e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+async fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))
+}
+
+/// `<path>.<UTC timestamp>` for a rotated segment
+fn segment_path(path: &Path) -> PathBuf {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%S%3f");
+    let mut segment = path.as_os_str().to_owned();
+    segment.push(format!(".{}", stamp));
+    PathBuf::from(segment)
+}
+
+/// Replace a rotated segment with `<segment>.gz`
+fn gzip_segment(segment: &Path) -> Result<()> {
+    let mut gz_path = segment.as_os_str().to_owned();
+    gz_path.push(".gz");
+    let mut input = std::fs::File::open(segment).context("Failed to open segment")?;
+    let output = std::fs::File::create(&gz_path).context("Failed to create gzip file")?;
+    let mut encoder = GzEncoder::new(output, Compression::default());
+    std::io::copy(&mut input, &mut encoder).context("Failed to compress segment")?;
+    encoder.finish().context("Failed to finish gzip stream")?;
+    std::fs::remove_file(segment).context("Failed to remove uncompressed segment")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::signed_events;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use std::time::Duration;
+
+    async fn sink(dir: &Path, gzip: bool) -> FileSink {
+        let config = FileSinkConfig {
+            path: dir.join("events.ndjson").display().to_string(),
+            rotate_size_mb: None,
+            rotate_daily: false,
+            gzip,
+            sample_rate: None,
+        };
+        let mut sink = FileSink::open(&config).await.unwrap();
+        // Rotate after every batch; the config only speaks in megabytes
+        sink.rotate_bytes = Some(1);
+        sink
+    }
+
+    /// Names of the files in `dir`, sorted
+    fn files(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn ids(ndjson: &str) -> Vec<String> {
+        ndjson
+            .lines()
+            .map(|line| serde_json::from_str::<Event>(line).unwrap().id.to_hex())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn a_full_segment_is_moved_aside_before_the_next_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut sink = sink(dir.path(), false).await;
+        let events = signed_events(3);
+
+        for event in &events {
+            sink.send_batch(std::slice::from_ref(event)).await.unwrap();
+            // Segment names have millisecond resolution
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        let names = files(dir.path());
+        assert_eq!(names.len(), 3, "{:?}", names);
+        assert_eq!(
+            names[0].len(),
+            "events.ndjson.".len() + "20250101T000000000".len()
+        );
+        assert_eq!(names[2], "events.ndjson");
+        // Segments sort in the order they were written, the live file holds the newest batch
+        let written: Vec<String> = [&names[0], &names[1], &names[2]]
+            .iter()
+            .flat_map(|name| ids(&std::fs::read_to_string(dir.path().join(name)).unwrap()))
+            .collect();
+        let expected: Vec<String> = events.iter().map(|e| e.id.to_hex()).collect();
+        assert_eq!(written, expected);
+    }
+
+    #[tokio::test]
+    async fn an_oversized_batch_is_written_to_an_empty_segment() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut sink = sink(dir.path(), false).await;
+
+        sink.send_batch(&signed_events(2)).await.unwrap();
+
+        assert_eq!(files(dir.path()), vec!["events.ndjson".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn rotated_segments_are_gzipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut sink = sink(dir.path(), true).await;
+        let events = signed_events(2);
+
+        sink.send_batch(&events[..1]).await.unwrap();
+        sink.send_batch(&events[1..]).await.unwrap();
+
+        // Compression runs in the background
+        let mut names = files(dir.path());
+        for _ in 0..100 {
+            if names.iter().any(|name| name.ends_with(".gz")) && names.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            names = files(dir.path());
+        }
+        assert_eq!(
+            names.len(),
+            2,
+            "uncompressed segment left behind: {:?}",
+            names
+        );
+        assert!(names[0].ends_with(".gz"), "{:?}", names);
+
+        let mut segment = String::new();
+        GzDecoder::new(std::fs::File::open(dir.path().join(&names[0])).unwrap())
+            .read_to_string(&mut segment)
+            .unwrap();
+        assert_eq!(ids(&segment), vec![events[0].id.to_hex()]);
+        let live = std::fs::read_to_string(dir.path().join("events.ndjson")).unwrap();
+        assert_eq!(ids(&live), vec![events[1].id.to_hex()]);
+    }
+}
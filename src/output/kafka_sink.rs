@@ -0,0 +1,209 @@
+use anyhow::{Context, Result};
+use nostr_sdk::Event;
+use rdkafka::config::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::util::Timeout;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::api::metrics::Metrics;
+use crate::config::KafkaConfig;
+
+/// How long a send may wait for space in the producer queue
+const ENQUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Downstream sink that publishes each event as a JSON message to a Kafka topic.
+/// Batching and retries are left to the librdkafka producer.
+pub struct KafkaSink {
+    endpoint: String,
+    topic: String,
+    key_by_pubkey: bool,
+    producer: FutureProducer,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl KafkaSink {
+    /// Create a producer batching up to `batch_size` messages for at most `max_latency`
+    pub fn new(config: &KafkaConfig, batch_size: usize, max_latency: Duration) -> Result<Self> {
+        let producer: FutureProducer = ClientConfig::new()
+            .set("bootstrap.servers", config.brokers.join(","))
+            .set("batch.num.messages", batch_size.to_string())
+            .set("linger.ms", max_latency.as_millis().to_string())
+            .create()
+            .context("Failed to create Kafka producer")?;
+        Ok(Self {
+            endpoint: format!("kafka://{}", config.topic),
+            topic: config.topic.clone(),
+            key_by_pubkey: config.key_by_pubkey,
+            producer,
+            metrics: None,
+        })
+    }
+
+    /// Attach metrics collection
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        metrics.register_delivery_endpoint(&self.endpoint);
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Endpoint label for this sink (`kafka://<topic>`)
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Publish a batch and wait for every delivery report.
+    /// Fails if any message could not be delivered.
+    pub async fn send_batch(&self, events: &[Event]) -> Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let payloads = events
+            .iter()
+            .map(|event| {
+                let payload = serde_json::to_vec(event).context("Failed to serialize event")?;
+                let key = self.key_by_pubkey.then(|| event.pubkey.to_hex());
+                Ok((payload, key))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let deliveries = payloads.iter().map(|(payload, key)| {
+            let record = FutureRecord::to(&self.topic).payload(payload);
+            let record = match key {
+                Some(key) => record.key(key.as_str()),
+                None => record,
+            };
+            self.producer.send(record, Timeout::After(ENQUEUE_TIMEOUT))
+        });
+        let failed = futures::future::join_all(deliveries)
+            .await
+            .into_iter()
+            .filter_map(|result| result.err())
+            .inspect(|(e, _)| warn!("Kafka delivery to {} failed: {}", self.topic, e))
+            .count();
+
+        if failed == 0 {
+            if let Some(m) = &self.metrics {
+                m.record_delivery_success(&self.endpoint);
+            }
+            Ok(())
+        } else {
+            if let Some(m) = &self.metrics {
+                m.record_delivery_failure(&self.endpoint);
+            }
+            anyhow::bail!(
+                "{} of {} events not delivered to Kafka topic {}",
+                failed,
+                events.len(),
+                self.topic
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::signed_events;
+    use rdkafka::Message;
+    use rdkafka::consumer::{Consumer, StreamConsumer};
+    use rdkafka::mocking::MockCluster;
+
+    const TOPIC: &str = "nostr-events";
+
+    fn config(brokers: &str, key_by_pubkey: bool) -> KafkaConfig {
+        KafkaConfig {
+            brokers: vec![brokers.to_string()],
+            topic: TOPIC.to_string(),
+            key_by_pubkey,
+            sample_rate: None,
+        }
+    }
+
+    /// Publish `events` to an in-process mock cluster and read them back as (key, event),
+    /// sorted by event id
+    async fn round_trip(events: &[Event], key_by_pubkey: bool) -> Vec<(Option<String>, Event)> {
+        let cluster = MockCluster::new(1).unwrap();
+        cluster.create_topic(TOPIC, 1, 1).unwrap();
+        let brokers = cluster.bootstrap_servers();
+        let sink = KafkaSink::new(
+            &config(&brokers, key_by_pubkey),
+            10,
+            Duration::from_millis(5),
+        )
+        .unwrap();
+        sink.send_batch(events).await.unwrap();
+
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", &brokers)
+            .set("group.id", "kafka-sink-test")
+            .set("auto.offset.reset", "earliest")
+            .create()
+            .unwrap();
+        consumer.subscribe(&[TOPIC]).unwrap();
+        let mut messages = Vec::new();
+        while messages.len() < events.len() {
+            let message = tokio::time::timeout(Duration::from_secs(30), consumer.recv())
+                .await
+                .expect("no message was published")
+                .unwrap();
+            let key = message
+                .key()
+                .map(|key| String::from_utf8(key.to_vec()).unwrap());
+            let event: Event = serde_json::from_slice(message.payload().unwrap()).unwrap();
+            messages.push((key, event));
+        }
+        messages.sort_by_key(|(_, event)| event.id);
+        messages
+    }
+
+    #[tokio::test]
+    async fn publishes_each_event_as_a_json_message_keyed_by_pubkey() {
+        let mut events = signed_events(3);
+        events.sort_by_key(|event| event.id);
+
+        let messages = round_trip(&events, true).await;
+
+        let expected: Vec<_> = events
+            .iter()
+            .map(|event| (Some(event.pubkey.to_hex()), event.clone()))
+            .collect();
+        assert_eq!(messages, expected);
+    }
+
+    #[tokio::test]
+    async fn messages_have_no_key_unless_keyed_by_pubkey() {
+        let mut events = signed_events(2);
+        events.sort_by_key(|event| event.id);
+
+        let messages = round_trip(&events, false).await;
+
+        let expected: Vec<_> = events.iter().map(|event| (None, event.clone())).collect();
+        assert_eq!(messages, expected);
+    }
+
+    #[tokio::test]
+    async fn fails_the_batch_when_messages_are_not_delivered() {
+        // Nothing listens on the broker port, so every message times out undelivered
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", "127.0.0.1:1")
+            .set("message.timeout.ms", "100")
+            .create()
+            .unwrap();
+        let sink = KafkaSink {
+            endpoint: format!("kafka://{}", TOPIC),
+            topic: TOPIC.to_string(),
+            key_by_pubkey: false,
+            producer,
+            metrics: None,
+        };
+
+        let error = sink.send_batch(&signed_events(2)).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("2 of 2 events not delivered to Kafka topic {}", TOPIC)
+        );
+    }
+}
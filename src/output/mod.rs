@@ -0,0 +1,6 @@
+pub mod circuit_breaker;
+pub mod file_sink;
+#[cfg(feature = "kafka")]
+pub mod kafka_sink;
+pub mod rest_sink;
+pub mod tcp_sink;
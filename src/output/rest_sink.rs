@@ -0,0 +1,261 @@
+use anyhow::{Context, Result};
+use hyper_util::client::legacy::connect::HttpInfo;
+use nostr_sdk::Event;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::api::metrics::Metrics;
+use crate::config::{CircuitBreakerConfig, ReconnectPolicy, SinkFailurePolicy};
+use crate::output::circuit_breaker::{BreakerState, CircuitBreaker};
+
+/// Downstream sink that POSTs batches of events as a JSON array to an HTTP endpoint
+pub struct RestSink {
+    endpoint: String,
+    client: Arc<reqwest::Client>,
+    retry_policy: ReconnectPolicy,
+    failure_policy: SinkFailurePolicy,
+    breaker: CircuitBreaker,
+    /// Local sockets this sink's requests went out on, with when each was last used.
+    /// A request on a known socket reused a pooled keep-alive connection.
+    connections: Mutex<HashMap<SocketAddr, Instant>>,
+    /// Idle time after which the client's pool closes a connection
+    idle_timeout: Duration,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl RestSink {
+    /// Create a sink for an HTTP(S) endpoint sharing the given client
+    pub fn new(
+        endpoint: String,
+        client: Arc<reqwest::Client>,
+        retry_policy: ReconnectPolicy,
+        failure_policy: SinkFailurePolicy,
+    ) -> Self {
+        Self {
+            endpoint,
+            client,
+            retry_policy,
+            failure_policy,
+            breaker: CircuitBreaker::new(&CircuitBreakerConfig::default()),
+            connections: Mutex::new(HashMap::new()),
+            idle_timeout: Duration::from_secs(90),
+            metrics: None,
+        }
+    }
+
+    /// Match the shared client's pool idle timeout when counting active connections
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Replace the default circuit breaker settings
+    pub fn with_circuit_breaker(mut self, config: &CircuitBreakerConfig) -> Self {
+        self.breaker = CircuitBreaker::new(config);
+        self
+    }
+
+    /// Attach metrics collection
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        metrics.register_delivery_endpoint(&self.endpoint);
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Endpoint this sink posts to
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Deliver a batch of events, retrying with backoff on errors and non-2xx responses.
+    /// With `SinkFailurePolicy::Drop` the batch is abandoned after `max_retries` attempts or
+    /// once the circuit is open; with `Block` it waits out the cooldown and probes again.
+    pub async fn send_batch(&self, events: &[Event]) -> Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let mut attempt: u32 = 0;
+        loop {
+            if !self.breaker.allow_request() {
+                match self.failure_policy {
+                    SinkFailurePolicy::Drop => anyhow::bail!(
+                        "Circuit open for REST endpoint {}, dropping batch of {} events",
+                        self.endpoint,
+                        events.len()
+                    ),
+                    SinkFailurePolicy::Block => {
+                        tokio::time::sleep(self.breaker.remaining_cooldown()).await;
+                        continue;
+                    }
+                }
+            }
+
+            match self.post_batch(events).await {
+                Ok(()) => {
+                    let state = self.breaker.record_success();
+                    if let Some(m) = &self.metrics {
+                        m.record_delivery_success(&self.endpoint);
+                        m.set_sink_circuit_state(&self.endpoint, state);
+                    }
+                    return Ok(());
+                }
+                Err(e) => {
+                    let state = self.breaker.record_failure();
+                    if let Some(m) = &self.metrics {
+                        m.record_delivery_failure(&self.endpoint);
+                        m.set_sink_circuit_state(&self.endpoint, state);
+                    }
+                    if state == BreakerState::Open {
+                        warn!("Circuit opened for REST endpoint {}: {}", self.endpoint, e);
+                        continue;
+                    }
+                    if self.failure_policy == SinkFailurePolicy::Drop
+                        && attempt >= self.retry_policy.max_retries
+                    {
+                        return Err(e.context(format!(
+                            "Dropping batch of {} events for REST endpoint {}",
+                            events.len(),
+                            self.endpoint
+                        )));
+                    }
+                    let delay = self.retry_policy.backoff_delay(attempt);
+                    warn!(
+                        "REST endpoint {} failed ({}), retrying in {:?}",
+                        self.endpoint, e, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt = attempt.saturating_add(1);
+                }
+            }
+        }
+    }
+
+    /// POST a single batch
+    async fn post_batch(&self, events: &[Event]) -> Result<()> {
+        let response = self
+            .client
+            .post(&self.endpoint)
+            .json(events)
+            .send()
+            .await
+            .with_context(|| format!("Failed to send POST request to {}", self.endpoint))?;
+
+        if let Some(info) = response.extensions().get::<HttpInfo>() {
+            self.track_connection(info.local_addr());
+        }
+        if !response.status().is_success() {
+            anyhow::bail!("REST endpoint returned error status: {}", response.status());
+        }
+
+        Ok(())
+    }
+
+    /// Record whether a request reused a pooled connection, identified by its local socket.
+    /// Sockets unused for longer than the idle timeout have been closed by the pool and are
+    /// forgotten.
+    fn track_connection(&self, local_addr: SocketAddr) {
+        let now = Instant::now();
+        let mut connections = self.connections.lock().unwrap_or_else(|e| e.into_inner());
+        connections.retain(|_, last_used| now.duration_since(*last_used) < self.idle_timeout);
+        let reused = connections.insert(local_addr, now).is_some();
+        if let Some(m) = &self.metrics {
+            m.record_rest_connection(&self.endpoint, reused, connections.len());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{serve, signed_events};
+    use axum::{Json, Router, extract::State, http::StatusCode, routing::post};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Batches received by a local endpoint that fails its first `failures` requests
+    #[derive(Clone, Default)]
+    struct Endpoint {
+        batches: Arc<Mutex<Vec<Vec<Event>>>>,
+        requests: Arc<AtomicUsize>,
+        failures: usize,
+    }
+
+    impl Endpoint {
+        async fn start(failures: usize) -> (Self, String) {
+            let endpoint = Self {
+                failures,
+                ..Self::default()
+            };
+            let app = Router::new()
+                .route("/events", post(receive))
+                .with_state(endpoint.clone());
+            (endpoint, format!("{}/events", serve(app).await))
+        }
+
+        fn batches(&self) -> Vec<Vec<Event>> {
+            self.batches.lock().unwrap().clone()
+        }
+    }
+
+    async fn receive(
+        State(endpoint): State<Endpoint>,
+        Json(batch): Json<Vec<Event>>,
+    ) -> StatusCode {
+        if endpoint.requests.fetch_add(1, Ordering::SeqCst) < endpoint.failures {
+            return StatusCode::SERVICE_UNAVAILABLE;
+        }
+        endpoint.batches.lock().unwrap().push(batch);
+        StatusCode::OK
+    }
+
+    fn sink(url: String, max_retries: u32) -> RestSink {
+        let policy = ReconnectPolicy {
+            base_delay_ms: 1,
+            max_delay_ms: 1,
+            max_retries,
+        };
+        RestSink::new(
+            url,
+            Arc::new(reqwest::Client::new()),
+            policy,
+            SinkFailurePolicy::Drop,
+        )
+    }
+
+    #[tokio::test]
+    async fn posts_each_batch_as_a_json_array() {
+        let (endpoint, url) = Endpoint::start(0).await;
+        let sink = sink(url, 0);
+        let first = signed_events(3);
+        let second = signed_events(2);
+
+        sink.send_batch(&first).await.unwrap();
+        sink.send_batch(&second).await.unwrap();
+
+        assert_eq!(endpoint.batches(), [first, second]);
+    }
+
+    #[tokio::test]
+    async fn retries_until_the_endpoint_accepts_the_batch() {
+        let (endpoint, url) = Endpoint::start(2).await;
+        let events = signed_events(2);
+
+        sink(url, 3).send_batch(&events).await.unwrap();
+
+        assert_eq!(endpoint.requests.load(Ordering::SeqCst), 3);
+        assert_eq!(endpoint.batches(), [events]);
+    }
+
+    #[tokio::test]
+    async fn drops_the_batch_once_retries_run_out() {
+        let (endpoint, url) = Endpoint::start(usize::MAX).await;
+
+        assert!(sink(url, 1).send_batch(&signed_events(1)).await.is_err());
+
+        assert_eq!(endpoint.requests.load(Ordering::SeqCst), 2);
+        assert!(endpoint.batches().is_empty());
+    }
+}
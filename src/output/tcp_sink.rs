@@ -0,0 +1,204 @@
+use anyhow::{Context, Result};
+use nostr_sdk::Event;
+use serde_json;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tracing::{info, warn};
+
+use crate::api::metrics::Metrics;
+use crate::config::{CircuitBreakerConfig, ReconnectPolicy, SinkFailurePolicy};
+use crate::output::circuit_breaker::{BreakerState, CircuitBreaker};
+
+/// Downstream sink that keeps a persistent TCP connection and writes newline-delimited JSON
+pub struct TcpSink {
+    endpoint: String,
+    stream: Option<TcpStream>,
+    retry_policy: ReconnectPolicy,
+    failure_policy: SinkFailurePolicy,
+    breaker: CircuitBreaker,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl TcpSink {
+    /// Create a sink for a `host:port` endpoint; the connection is opened lazily
+    pub fn new(
+        endpoint: String,
+        retry_policy: ReconnectPolicy,
+        failure_policy: SinkFailurePolicy,
+    ) -> Self {
+        Self {
+            endpoint,
+            stream: None,
+            retry_policy,
+            failure_policy,
+            breaker: CircuitBreaker::new(&CircuitBreakerConfig::default()),
+            metrics: None,
+        }
+    }
+
+    /// Replace the default circuit breaker settings
+    pub fn with_circuit_breaker(mut self, config: &CircuitBreakerConfig) -> Self {
+        self.breaker = CircuitBreaker::new(config);
+        self
+    }
+
+    /// Attach metrics collection
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        metrics.register_delivery_endpoint(&self.endpoint);
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Endpoint this sink writes to
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Deliver a batch of events, reconnecting with backoff on failure.
+    /// With `SinkFailurePolicy::Drop` the batch is abandoned after `max_retries` attempts or
+    /// once the circuit is open; with `Block` it waits out the cooldown and probes again.
+    pub async fn send_batch(&mut self, events: &[Event]) -> Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let payload = encode_ndjson(events)?;
+
+        let mut attempt: u32 = 0;
+        loop {
+            if !self.breaker.allow_request() {
+                match self.failure_policy {
+                    SinkFailurePolicy::Drop => anyhow::bail!(
+                        "Circuit open for TCP endpoint {}, dropping batch of {} events",
+                        self.endpoint,
+                        events.len()
+                    ),
+                    SinkFailurePolicy::Block => {
+                        tokio::time::sleep(self.breaker.remaining_cooldown()).await;
+                        continue;
+                    }
+                }
+            }
+
+            match self.write_payload(&payload).await {
+                Ok(()) => {
+                    let state = self.breaker.record_success();
+                    if let Some(m) = &self.metrics {
+                        m.record_delivery_success(&self.endpoint);
+                        m.set_sink_circuit_state(&self.endpoint, state);
+                    }
+                    return Ok(());
+                }
+                Err(e) => {
+                    let state = self.breaker.record_failure();
+                    if let Some(m) = &self.metrics {
+                        m.record_delivery_failure(&self.endpoint);
+                        m.set_sink_circuit_state(&self.endpoint, state);
+                    }
+                    // Force a fresh connection on the next attempt
+                    self.stream = None;
+                    if state == BreakerState::Open {
+                        warn!("Circuit opened for TCP endpoint {}: {}", self.endpoint, e);
+                        continue;
+                    }
+                    if self.failure_policy == SinkFailurePolicy::Drop
+                        && attempt >= self.retry_policy.max_retries
+                    {
+                        return Err(e.context(format!(
+                            "Dropping batch of {} events for TCP endpoint {}",
+                            events.len(),
+                            self.endpoint
+                        )));
+                    }
+                    let delay = self.retry_policy.backoff_delay(attempt);
+                    warn!(
+                        "TCP endpoint {} failed ({}), retrying in {:?}",
+                        self.endpoint, e, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt = attempt.saturating_add(1);
+                }
+            }
+        }
+    }
+
+    /// Write a payload on the current connection, connecting first if needed
+    async fn write_payload(&mut self, payload: &[u8]) -> Result<()> {
+        if self.stream.is_none() {
+            let stream = TcpStream::connect(&self.endpoint)
+                .await
+                .with_context(|| format!("Failed to connect to TCP endpoint: {}", self.endpoint))?;
+            info!("Connected to TCP endpoint {}", self.endpoint);
+            self.stream = Some(stream);
+        }
+
+        if let Some(stream) = self.stream.as_mut() {
+            stream.write_all(payload).await?;
+            stream.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+/// Serialize events as newline-delimited JSON
+pub(crate) fn encode_ndjson(events: &[Event]) -> Result<Vec<u8>> {
+    let mut payload = Vec::new();
+    for event in events {
+        serde_json::to_writer(&mut payload, event).context("Failed to serialize event to JSON")?;
+        payload.push(b'\n');
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::signed_events;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn delivers_batches_to_a_tcp_listener_as_ndjson() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        let mut sink = TcpSink::new(
+            endpoint,
+            ReconnectPolicy::default(),
+            SinkFailurePolicy::Drop,
+        );
+
+        let first = signed_events(3);
+        let second = signed_events(2);
+        sink.send_batch(&first).await.unwrap();
+        sink.send_batch(&second).await.unwrap();
+
+        // Both batches go out on the one connection, one event per line
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut lines = BufReader::new(socket).lines();
+        for expected in first.iter().chain(&second) {
+            let line = lines.next_line().await.unwrap().expect("a line per event");
+            let received: Event = serde_json::from_str(&line).unwrap();
+            assert_eq!(received.id, expected.id);
+            assert!(received.verify().is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn drops_the_batch_when_the_endpoint_is_unreachable() {
+        // Bind and close a listener to get a port nothing listens on
+        let endpoint = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let policy = ReconnectPolicy {
+            base_delay_ms: 1,
+            max_delay_ms: 1,
+            max_retries: 1,
+        };
+        let mut sink = TcpSink::new(endpoint, policy, SinkFailurePolicy::Drop);
+
+        assert!(sink.send_batch(&signed_events(1)).await.is_err());
+    }
+}
@@ -1,47 +1,138 @@
-use bloom::{ASMS, BloomFilter as BloomFilterLib};
+use anyhow::{Context, Result, ensure};
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+/// Header written before the bit array of a persisted filter
+const SNAPSHOT_MAGIC: &[u8; 8] = b"ISOBLOOM";
+const SNAPSHOT_VERSION: u32 = 1;
+const SNAPSHOT_HEADER_LEN: usize = 8 + 4 + 8 + 4;
+
 /// In-memory Bloom filter for fast duplicate detection
 /// Capacity: 10 million events with ~1% false positive rate
+///
+/// Event ids and content hashes are SHA-256 digests, so bit positions are derived from the id
+/// bytes directly. They are the same in every process, which lets the filter be saved to disk
+/// and reloaded after a restart.
 pub struct BloomFilter {
-    filter: Arc<RwLock<BloomFilterLib>>,
+    bits: Arc<RwLock<Vec<u64>>>,
+    num_bits: u64,
+    num_hashes: u32,
 }
 
 impl BloomFilter {
     /// Create a new Bloom filter with custom capacity and false positive rate
     pub fn with_capacity(capacity: usize, false_positive_rate: f64) -> Self {
-        let filter = BloomFilterLib::with_rate(false_positive_rate as f32, capacity as u32);
+        let capacity = capacity.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-capacity * false_positive_rate.ln() / (ln2 * ln2))
+            .ceil()
+            .max(64.0) as u64;
+        let num_hashes = ((num_bits as f64 / capacity) * ln2).round().max(1.0) as u32;
         Self {
-            filter: Arc::new(RwLock::new(filter)),
+            bits: Arc::new(RwLock::new(vec![0; num_bits.div_ceil(64) as usize])),
+            num_bits,
+            num_hashes,
         }
     }
 
     /// Create a new Bloom filter with capacity for 10 million items
     pub fn new() -> Self {
         // Create bloom filter with 10M capacity and 1% false positive rate
-        let filter = BloomFilterLib::with_rate(0.01, 10_000_000);
-        Self {
-            filter: Arc::new(RwLock::new(filter)),
-        }
+        Self::with_capacity(10_000_000, 0.01)
+    }
+
+    /// Bit positions for an id, by double hashing over two 64-bit halves of the digest
+    fn positions(&self, event_id: &[u8; 32]) -> impl Iterator<Item = u64> {
+        let h1 = u64::from_le_bytes(event_id[0..8].try_into().unwrap_or_default());
+        let h2 = u64::from_le_bytes(event_id[8..16].try_into().unwrap_or_default()) | 1;
+        let num_bits = self.num_bits;
+        (0..self.num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
     }
 
     /// Check if an event ID might exist (fast check, may have false positives)
     pub async fn contains(&self, event_id: &[u8; 32]) -> bool {
-        let filter = self.filter.read().await;
-        filter.contains(event_id)
+        let bits = self.bits.read().await;
+        self.positions(event_id)
+            .all(|bit| bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
     }
 
     /// Insert an event ID into the bloom filter
     pub async fn insert(&self, event_id: &[u8; 32]) {
-        let mut filter = self.filter.write().await;
-        filter.insert(event_id);
+        let mut bits = self.bits.write().await;
+        for bit in self.positions(event_id) {
+            bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
     }
 
     /// Clear the bloom filter (useful for testing or reset)
     pub async fn clear(&self) {
-        let mut filter = self.filter.write().await;
-        *filter = BloomFilterLib::with_rate(0.01, 10_000_000);
+        let mut bits = self.bits.write().await;
+        bits.fill(0);
+    }
+
+    /// Write the filter to `path`, replacing any previous snapshot atomically
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let words = self.bits.read().await.clone();
+        let mut buf = Vec::with_capacity(SNAPSHOT_HEADER_LEN + words.len() * 8);
+        buf.extend_from_slice(SNAPSHOT_MAGIC);
+        buf.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        buf.extend_from_slice(&self.num_bits.to_le_bytes());
+        buf.extend_from_slice(&self.num_hashes.to_le_bytes());
+        for word in words {
+            buf.extend_from_slice(&word.to_le_bytes());
+        }
+
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let tmp = path.with_extension("tmp");
+            std::fs::write(&tmp, &buf)
+                .with_context(|| format!("Failed to write bloom filter to {}", tmp.display()))?;
+            std::fs::rename(&tmp, &path)
+                .with_context(|| format!("Failed to move bloom filter to {}", path.display()))
+        })
+        .await?
+    }
+
+    /// Load a snapshot written by [`BloomFilter::save`], returning `false` when none exists.
+    /// Fails if the snapshot was written with a different capacity or false-positive rate.
+    pub async fn load(&self, path: &Path) -> Result<bool> {
+        let buf = match tokio::fs::read(path).await {
+            Ok(buf) => buf,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read bloom filter {}", path.display()));
+            }
+        };
+        ensure!(
+            buf.len() >= SNAPSHOT_HEADER_LEN && &buf[0..8] == SNAPSHOT_MAGIC,
+            "{} is not a bloom filter snapshot",
+            path.display()
+        );
+        let version = u32::from_le_bytes(buf[8..12].try_into()?);
+        let num_bits = u64::from_le_bytes(buf[12..20].try_into()?);
+        let num_hashes = u32::from_le_bytes(buf[20..24].try_into()?);
+        ensure!(
+            version == SNAPSHOT_VERSION,
+            "unsupported bloom filter snapshot version {}",
+            version
+        );
+        ensure!(
+            num_bits == self.num_bits && num_hashes == self.num_hashes,
+            "bloom filter snapshot was sized for a different bloom_capacity or bloom_fp_rate"
+        );
+
+        let body = &buf[SNAPSHOT_HEADER_LEN..];
+        let mut bits = self.bits.write().await;
+        ensure!(
+            body.len() == bits.len() * 8,
+            "bloom filter snapshot is truncated"
+        );
+        for (word, chunk) in bits.iter_mut().zip(body.chunks_exact(8)) {
+            *word = u64::from_le_bytes(chunk.try_into()?);
+        }
+        Ok(true)
     }
 }
 
@@ -50,3 +141,54 @@ impl Default for BloomFilter {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::signed_events;
+
+    #[tokio::test]
+    async fn a_saved_filter_reloads_with_the_same_members() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dedup.bloom");
+        let events = signed_events(2);
+        let saved = BloomFilter::with_capacity(1000, 0.001);
+        saved.insert(events[0].id.as_bytes()).await;
+        saved.save(&path).await.unwrap();
+
+        let loaded = BloomFilter::with_capacity(1000, 0.001);
+        assert!(loaded.load(&path).await.unwrap());
+        assert!(loaded.contains(events[0].id.as_bytes()).await);
+        assert!(!loaded.contains(events[1].id.as_bytes()).await);
+    }
+
+    #[tokio::test]
+    async fn loading_without_a_snapshot_leaves_the_filter_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let filter = BloomFilter::with_capacity(1000, 0.001);
+
+        assert!(
+            !filter
+                .load(&dir.path().join("missing.bloom"))
+                .await
+                .unwrap()
+        );
+        assert!(!filter.contains(signed_events(1)[0].id.as_bytes()).await);
+    }
+
+    #[tokio::test]
+    async fn a_snapshot_of_a_differently_sized_filter_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dedup.bloom");
+        BloomFilter::with_capacity(1000, 0.001)
+            .save(&path)
+            .await
+            .unwrap();
+
+        let error = BloomFilter::with_capacity(2000, 0.001)
+            .load(&path)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("different bloom_capacity"));
+    }
+}
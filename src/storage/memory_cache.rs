@@ -34,10 +34,19 @@ impl MemoryCache {
         cache.contains(event_id)
     }
 
-    /// Insert an event ID into the cache
-    pub async fn put(&self, event_id: String) {
+    /// Insert an event ID into the cache, returning whether the least recently used entry was
+    /// evicted to make room
+    pub async fn put(&self, event_id: String) -> bool {
         let mut cache = self.cache.write().await;
+        let evicts = !cache.contains(&event_id) && cache.len() == cache.cap().get();
         cache.put(event_id, ());
+        evicts
+    }
+
+    /// Remove an event ID from the cache
+    pub async fn remove(&self, event_id: &str) {
+        let mut cache = self.cache.write().await;
+        cache.pop(event_id);
     }
 
     /// Get the current size of the cache
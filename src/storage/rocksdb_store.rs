@@ -1,44 +1,309 @@
 use anyhow::{Context, Result};
-use nostr_sdk::Event;
-use rocksdb::{DB, IteratorMode, Options};
+use nostr_sdk::{Event, EventId, Tags};
+use rocksdb::{
+    BlockBasedOptions, Cache, DB, DBCompressionType, Direction, ErrorKind, IteratorMode, Options,
+    checkpoint::Checkpoint,
+};
 use serde_json;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
 use tokio::sync::RwLock;
 
+use crate::config::{RetentionPolicy, RocksDbCompression, RocksDbTuning};
+
+/// Maximum number of events removed per `WriteBatch` during pruning
+const PRUNE_BATCH_SIZE: usize = 1000;
+
+/// Events read per page by [`RocksDBStore::export_jsonl`]; the lock is released between pages
+const EXPORT_PAGE_SIZE: usize = 1000;
+
+/// Events written per `WriteBatch` by [`RocksDBStore::import_jsonl`]
+const IMPORT_BATCH_SIZE: usize = 1000;
+
+/// Single-letter tags whose values are indexed for [`RocksDBStore::get_events_by_tag`]
+pub const INDEXED_TAGS: &[char] = &['p', 'e'];
+
+/// Integer RocksDB properties exported as metrics
+pub const MONITORED_PROPERTIES: &[&str] = &[
+    "rocksdb.num-files-at-level0",
+    "rocksdb.estimate-pending-compaction-bytes",
+    "rocksdb.num-running-compactions",
+    "rocksdb.num-running-flushes",
+    "rocksdb.block-cache-usage",
+    "rocksdb.cur-size-all-mem-tables",
+    "rocksdb.total-sst-files-size",
+    "rocksdb.estimate-num-keys",
+];
+
+/// Why RocksDB refused to open the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OpenFailure {
+    /// The files on disk are damaged; retrying will not help
+    Corruption,
+    /// The options or path were rejected; retrying will not help
+    Rejected,
+    /// An I/O error or busy database that may clear up, e.g. a `LOCK` file still held by a
+    /// previous process or a volume that is not mounted yet
+    Unavailable,
+}
+
+impl OpenFailure {
+    fn classify(e: &rocksdb::Error) -> Self {
+        match e.kind() {
+            ErrorKind::Corruption => Self::Corruption,
+            ErrorKind::InvalidArgument | ErrorKind::NotSupported => Self::Rejected,
+            _ => Self::Unavailable,
+        }
+    }
+
+    fn is_retryable(self) -> bool {
+        self == Self::Unavailable
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Corruption => "database files are corrupted",
+            Self::Rejected => "database options were rejected",
+            Self::Unavailable => "database is unavailable (locked by another process or I/O error)",
+        }
+    }
+}
+
+/// Run `open` until it succeeds, retrying up to `retries` times with a doubling delay.
+/// Corruption and rejected options are reported immediately since they cannot resolve
+/// themselves. Blocks between attempts, so async callers open the store via `spawn_blocking`.
+fn open_with_retry<T>(
+    retries: u32,
+    delay: Duration,
+    mut open: impl FnMut() -> std::result::Result<T, rocksdb::Error>,
+) -> Result<T> {
+    let mut attempt = 0;
+    loop {
+        let e = match open() {
+            Ok(db) => return Ok(db),
+            Err(e) => e,
+        };
+        let failure = OpenFailure::classify(&e);
+        if !failure.is_retryable() || attempt >= retries {
+            return Err(anyhow::Error::new(e).context(failure.describe()));
+        }
+        let wait = delay.saturating_mul(1 << attempt.min(16));
+        tracing::warn!(
+            "RocksDB open failed ({}: {}), retrying in {:?} ({}/{})",
+            failure.describe(),
+            e,
+            wait,
+            attempt + 1,
+            retries
+        );
+        std::thread::sleep(wait);
+        attempt += 1;
+    }
+}
+
+/// Position in `(created_at, id)` order, written as `<created_at>:<event id>`, from which
+/// [`RocksDBStore::get_events_page`] resumes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventCursor {
+    pub created_at: u64,
+    pub event_id: String,
+}
+
+/// Outcome of [`RocksDBStore::import_jsonl`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImportSummary {
+    /// Events written to the store
+    pub imported: u64,
+    /// Non-empty lines that did not parse as an event
+    pub skipped: u64,
+}
+
+impl std::fmt::Display for EventCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.created_at, self.event_id)
+    }
+}
+
+impl std::str::FromStr for EventCursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (created_at, event_id) = s
+            .split_once(':')
+            .with_context(|| format!("Invalid cursor {}: expected <created_at>:<event id>", s))?;
+        let created_at = created_at
+            .parse()
+            .with_context(|| format!("Invalid cursor {}: bad created_at", s))?;
+        anyhow::ensure!(
+            !event_id.is_empty() && event_id.bytes().all(|b| b.is_ascii_hexdigit()),
+            "Invalid cursor {}: bad event id",
+            s
+        );
+        Ok(Self {
+            created_at,
+            event_id: event_id.to_string(),
+        })
+    }
+}
+
 /// Persistent storage using RocksDB for event deduplication and archival
 pub struct RocksDBStore {
-    db: Arc<RwLock<DB>>,
+    /// Reference-counted so a long compaction can run on a blocking thread without the lock
+    db: RwLock<Arc<DB>>,
+    /// Re-check id and signature of events read back from disk
+    verify_on_read: bool,
+    /// Opened as a read-only secondary instance that follows a primary's files
+    secondary: bool,
 }
 
 impl RocksDBStore {
     /// Open or create a RocksDB database at the specified path
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::with_tuning(path, &RocksDbTuning::default())
+    }
+
+    /// Open or create a RocksDB database with the given tuning options
+    pub fn with_tuning<P: AsRef<Path>>(path: P, tuning: &RocksDbTuning) -> Result<Self> {
         let mut opts = Options::default();
         opts.create_if_missing(true);
         opts.create_missing_column_families(true);
 
         // Optimize for write-heavy workload
-        opts.set_write_buffer_size(64 * 1024 * 1024); // 64MB
-        opts.set_max_write_buffer_number(3);
+        opts.set_write_buffer_size(tuning.write_buffer_mb * 1024 * 1024);
+        opts.set_max_write_buffer_number(tuning.max_write_buffers);
         opts.set_min_write_buffer_number_to_merge(1);
 
         // Enable compression
-        opts.set_compression_type(rocksdb::DBCompressionType::Lz4);
+        opts.set_compression_type(Self::compression_type(tuning.compression));
+
+        if let Some(cache_mb) = tuning.block_cache_mb {
+            let cache = Cache::new_lru_cache(cache_mb * 1024 * 1024);
+            let mut block_opts = BlockBasedOptions::default();
+            block_opts.set_block_cache(&cache);
+            opts.set_block_based_table_factory(&block_opts);
+        }
+
+        let path = path.as_ref();
+        let db = open_with_retry(
+            tuning.open_retries,
+            Duration::from_millis(tuning.open_retry_delay_ms),
+            || DB::open(&opts, path),
+        )
+        .with_context(|| format!("Failed to open RocksDB database at {}", path.display()))?;
 
-        let db = DB::open(&opts, path).context("Failed to open RocksDB database")?;
+        Ok(Self {
+            db: RwLock::new(Arc::new(db)),
+            verify_on_read: tuning.verify_on_read,
+            secondary: false,
+        })
+    }
+
+    /// Open a read-only secondary instance of the database at `primary_path`, e.g. for an
+    /// analytics sidecar. RocksDB keeps the secondary's own logs under `secondary_path`; it
+    /// sees the primary's writes as of opening and after each [`Self::catch_up_with_primary`].
+    /// Write methods fail on a secondary.
+    pub fn open_secondary<P: AsRef<Path>, S: AsRef<Path>>(
+        primary_path: P,
+        secondary_path: S,
+    ) -> Result<Self> {
+        let mut opts = Options::default();
+        // Secondaries must be able to open every file the primary may reference
+        opts.set_max_open_files(-1);
+        let db = DB::open_as_secondary(&opts, primary_path.as_ref(), secondary_path.as_ref())
+            .context("Failed to open RocksDB secondary instance")?;
 
         Ok(Self {
-            db: Arc::new(RwLock::new(db)),
+            db: RwLock::new(Arc::new(db)),
+            verify_on_read: false,
+            secondary: true,
         })
     }
 
+    /// Whether this store is a read-only secondary instance
+    pub fn is_secondary(&self) -> bool {
+        self.secondary
+    }
+
+    /// Replay the primary's new WAL and manifest entries so recent writes become visible
+    pub async fn catch_up_with_primary(&self) -> Result<()> {
+        anyhow::ensure!(
+            self.secondary,
+            "RocksDB is not open as a secondary instance"
+        );
+        let db = self.db.write().await;
+        db.try_catch_up_with_primary()
+            .context("Failed to catch up with the RocksDB primary")?;
+        Ok(())
+    }
+
+    /// Refuse writes on a read-only secondary instance
+    fn ensure_writable(&self) -> Result<()> {
+        anyhow::ensure!(!self.secondary, "RocksDB is open as a read-only secondary");
+        Ok(())
+    }
+
+    fn compression_type(compression: RocksDbCompression) -> DBCompressionType {
+        match compression {
+            RocksDbCompression::None => DBCompressionType::None,
+            RocksDbCompression::Snappy => DBCompressionType::Snappy,
+            RocksDbCompression::Zlib => DBCompressionType::Zlib,
+            RocksDbCompression::Bz2 => DBCompressionType::Bz2,
+            RocksDbCompression::Lz4 => DBCompressionType::Lz4,
+            RocksDbCompression::Lz4hc => DBCompressionType::Lz4hc,
+            RocksDbCompression::Zstd => DBCompressionType::Zstd,
+        }
+    }
+
+    /// Drop an event read from disk whose id or signature no longer verifies
+    fn verified(&self, event: Event) -> Option<Event> {
+        if !self.verify_on_read {
+            return Some(event);
+        }
+        match event.verify() {
+            Ok(()) => Some(event),
+            Err(e) => {
+                tracing::warn!("Stored event {} failed verification: {}", event.id, e);
+                None
+            }
+        }
+    }
+
     #[inline]
     fn key_event(event_id: &str) -> Vec<u8> {
+        Self::key_event_bytes(event_id.as_bytes())
+    }
+
+    #[inline]
+    fn key_event_bytes(event_id: &[u8]) -> Vec<u8> {
         // Event payload storage
         let mut key = Vec::with_capacity(4 + event_id.len());
         key.extend_from_slice(b"evt:");
-        key.extend_from_slice(event_id.as_bytes());
+        key.extend_from_slice(event_id);
+        key
+    }
+
+    #[inline]
+    fn key_content_hash(hash: &str) -> Vec<u8> {
+        // Marker for content-hash deduplication
+        let mut key = Vec::with_capacity(6 + hash.len());
+        key.extend_from_slice(b"chash:");
+        key.extend_from_slice(hash.as_bytes());
+        key
+    }
+
+    #[inline]
+    fn key_last_seen(dedup_id: &str) -> Vec<u8> {
+        Self::key_last_seen_bytes(dedup_id.as_bytes())
+    }
+
+    #[inline]
+    fn key_last_seen_bytes(dedup_id: &[u8]) -> Vec<u8> {
+        // When a dedup id was last accepted, for windowed deduplication
+        let mut key = Vec::with_capacity(5 + dedup_id.len());
+        key.extend_from_slice(b"seen:");
+        key.extend_from_slice(dedup_id);
         key
     }
 
@@ -65,23 +330,223 @@ impl RocksDBStore {
         key
     }
 
+    #[inline]
+    fn key_relay(relay_url: &str) -> Vec<u8> {
+        // Relays added at runtime, restored on startup
+        let mut key = Vec::with_capacity(6 + relay_url.len());
+        key.extend_from_slice(b"relay:");
+        key.extend_from_slice(relay_url.as_bytes());
+        key
+    }
+
+    #[inline]
+    fn key_created_at_index(created_at: u64, event_id: &str) -> Vec<u8> {
+        // Secondary index ordered by event creation time
+        // Format: "ts:{016x}:{event_id}", zero-padded hex so keys sort by timestamp
+        let mut key = Vec::with_capacity(3 + 16 + 1 + event_id.len());
+        key.extend_from_slice(b"ts:");
+        key.extend_from_slice(format!("{:016x}", created_at).as_bytes());
+        key.push(b':');
+        key.extend_from_slice(event_id.as_bytes());
+        key
+    }
+
+    #[inline]
+    fn key_tag_prefix(tag: char, value: &str) -> Vec<u8> {
+        // Secondary index from a tag value to the events referencing it
+        // Format: "tag:{tag}:{value}:{016x created_at}:{event_id}", so matches sort by time
+        format!("tag:{}:{}:", tag, value).into_bytes()
+    }
+
+    #[inline]
+    fn key_tag_index(tag: char, value: &str, created_at: u64, event_id: &str) -> Vec<u8> {
+        let mut key = Self::key_tag_prefix(tag, value);
+        key.extend_from_slice(format!("{:016x}:", created_at).as_bytes());
+        key.extend_from_slice(event_id.as_bytes());
+        key
+    }
+
+    /// Tag index keys for every indexed tag on an event.
+    /// Values containing `:` are skipped so one value's prefix never matches another's keys.
+    fn tag_index_keys(event: &Event, event_id: &str) -> Vec<Vec<u8>> {
+        let created_at = event.created_at.as_secs();
+        event
+            .tags
+            .iter()
+            .filter_map(|tag| match tag.as_slice() {
+                [name, value, ..] => {
+                    let mut chars = name.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) if INDEXED_TAGS.contains(&c) && !value.contains(':') => {
+                            Some(Self::key_tag_index(c, value, created_at, event_id))
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Check if an event ID exists in the database
-    pub async fn exists(&self, event_id: &str) -> bool {
+    pub async fn exists(&self, event_id: &str) -> Result<bool> {
         let db = self.db.read().await;
-        match db.get(Self::key_event(event_id)) {
-            Ok(Some(_)) => true,
-            _ => false,
+        match db.get_pinned(Self::key_event(event_id)) {
+            Ok(Some(_)) => Ok(true),
+            Ok(None) => Ok(false),
+            Err(e) => Err(anyhow::anyhow!("Database error: {}", e)),
+        }
+    }
+
+    /// Check many event ids in one `multi_get`, returning a flag per id in input order
+    pub async fn exists_many(&self, event_ids: &[&str]) -> Result<Vec<bool>> {
+        let db = self.db.read().await;
+        db.multi_get(event_ids.iter().map(|id| Self::key_event(id)))
+            .into_iter()
+            .map(|result| match result {
+                Ok(value) => Ok(value.is_some()),
+                Err(e) => Err(anyhow::anyhow!("Database error: {}", e)),
+            })
+            .collect()
+    }
+
+    /// Check if a content hash has been recorded
+    pub async fn content_hash_exists(&self, hash: &str) -> Result<bool> {
+        let db = self.db.read().await;
+        match db.get_pinned(Self::key_content_hash(hash)) {
+            Ok(Some(_)) => Ok(true),
+            Ok(None) => Ok(false),
+            Err(e) => Err(anyhow::anyhow!("Database error: {}", e)),
+        }
+    }
+
+    /// Record a content hash for content-based deduplication
+    pub async fn store_content_hash(&self, hash: &str) -> Result<()> {
+        self.ensure_writable()?;
+        let db = self.db.write().await;
+        db.put(Self::key_content_hash(hash), b"")
+            .context("Failed to store content hash in RocksDB")?;
+        Ok(())
+    }
+
+    /// Remove a content hash marker so matching events are accepted again
+    pub async fn delete_content_hash(&self, hash: &str) -> Result<()> {
+        self.ensure_writable()?;
+        let db = self.db.write().await;
+        db.delete(Self::key_content_hash(hash))
+            .context("Failed to delete content hash from RocksDB")?;
+        Ok(())
+    }
+
+    /// Unix time in seconds at which a dedup id was last accepted as new, if recorded
+    pub async fn last_seen(&self, dedup_id: &str) -> Result<Option<u64>> {
+        let db = self.db.read().await;
+        let value = db
+            .get_pinned(Self::key_last_seen(dedup_id))
+            .context("Failed to read last-seen time from RocksDB")?;
+        Ok(value.and_then(|v| v.as_ref().try_into().ok().map(u64::from_be_bytes)))
+    }
+
+    /// Record when a dedup id was accepted as new
+    pub async fn store_last_seen(&self, dedup_id: &str, seen_at: u64) -> Result<()> {
+        self.ensure_writable()?;
+        let db = self.db.write().await;
+        db.put(Self::key_last_seen(dedup_id), seen_at.to_be_bytes())
+            .context("Failed to store last-seen time in RocksDB")?;
+        Ok(())
+    }
+
+    /// Drop the last-seen time of a dedup id so its window starts over
+    pub async fn delete_last_seen(&self, dedup_id: &str) -> Result<()> {
+        self.ensure_writable()?;
+        let db = self.db.write().await;
+        db.delete(Self::key_last_seen(dedup_id))
+            .context("Failed to delete last-seen time from RocksDB")?;
+        Ok(())
+    }
+
+    /// Verify the database answers reads by looking up a probe key; works on a secondary
+    pub async fn check_readable(&self) -> Result<()> {
+        let db = self.db.read().await;
+        db.get(b"health:probe")
+            .context("RocksDB rejected probe read")?;
+        Ok(())
+    }
+
+    /// Verify the database accepts writes by writing and removing a probe key
+    pub async fn check_writable(&self) -> Result<()> {
+        self.ensure_writable()?;
+        let db = self.db.write().await;
+        db.put(b"health:probe", b"")
+            .context("RocksDB rejected probe write")?;
+        db.delete(b"health:probe")
+            .context("RocksDB rejected probe delete")?;
+        Ok(())
+    }
+
+    /// Remember a relay so it is reconnected after a restart
+    pub async fn store_relay(&self, relay_url: &str) -> Result<()> {
+        self.ensure_writable()?;
+        let db = self.db.write().await;
+        db.put(Self::key_relay(relay_url), b"")
+            .context("Failed to store relay in RocksDB")?;
+        Ok(())
+    }
+
+    /// Forget a persisted relay
+    pub async fn remove_relay(&self, relay_url: &str) -> Result<()> {
+        self.ensure_writable()?;
+        let db = self.db.write().await;
+        db.delete(Self::key_relay(relay_url))
+            .context("Failed to remove relay from RocksDB")?;
+        Ok(())
+    }
+
+    /// Load every persisted relay URL
+    pub async fn load_relays(&self) -> Result<Vec<String>> {
+        let db = self.db.read().await;
+        let mut relays = Vec::new();
+        for item in db.iterator(IteratorMode::From(b"relay:", Direction::Forward)) {
+            let (key, _) = item.context("Failed to iterate persisted relays")?;
+            let Some(url) = key.strip_prefix(b"relay:") else {
+                break;
+            };
+            if let Ok(url) = std::str::from_utf8(url) {
+                relays.push(url.to_string());
+            }
         }
+        Ok(relays)
     }
 
     /// Store an event in the database
     pub async fn store_event(&self, event: &Event) -> Result<()> {
-        let event_id = event.id.to_string();
-        let serialized = serde_json::to_vec(event).context("Failed to serialize event")?;
+        self.store_events(std::slice::from_ref(event)).await
+    }
+
+    /// Store multiple events atomically in a single write batch
+    pub async fn store_events(&self, events: &[Event]) -> Result<()> {
+        self.ensure_writable()?;
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let mut batch = rocksdb::WriteBatch::default();
+        for event in events {
+            let event_id = event.id.to_string();
+            let serialized = serde_json::to_vec(event).context("Failed to serialize event")?;
+            batch.put(Self::key_event(&event_id), serialized);
+            batch.put(
+                Self::key_created_at_index(event.created_at.as_secs(), &event_id),
+                &[],
+            );
+            for key in Self::tag_index_keys(event, &event_id) {
+                batch.put(key, &[]);
+            }
+        }
 
         let db = self.db.write().await;
-        db.put(Self::key_event(&event_id), serialized)
-            .context("Failed to store event in RocksDB")?;
+        db.write(batch)
+            .context("Failed to store events in RocksDB")?;
 
         Ok(())
     }
@@ -93,34 +558,477 @@ impl RocksDBStore {
             Ok(Some(data)) => {
                 let event: Event =
                     serde_json::from_slice(&data).context("Failed to deserialize event")?;
-                Ok(Some(event))
+                Ok(self.verified(event))
             }
             Ok(None) => Ok(None),
             Err(e) => Err(anyhow::anyhow!("Database error: {}", e)),
         }
     }
 
-    /// Delete an event by ID
+    /// Retrieve up to `limit` events with `since <= created_at <= until`, oldest first
+    pub async fn get_events_in_range(
+        &self,
+        since: u64,
+        until: u64,
+        limit: usize,
+    ) -> Result<Vec<Event>> {
+        let (events, _) = self.get_events_page(since, until, None, limit).await?;
+        Ok(events)
+    }
+
+    /// Retrieve up to `limit` events with `since <= created_at <= until` that come after
+    /// `after` in `(created_at, id)` order, oldest first. Seeks straight to the start position
+    /// in the `created_at` index. Returns the cursor to pass back for the next page, or `None`
+    /// once the range is exhausted.
+    pub async fn get_events_page(
+        &self,
+        since: u64,
+        until: u64,
+        after: Option<&EventCursor>,
+        limit: usize,
+    ) -> Result<(Vec<Event>, Option<EventCursor>)> {
+        let mut events = Vec::new();
+        if since > until || limit == 0 {
+            return Ok((events, None));
+        }
+
+        let db = self.db.read().await;
+        let range_start = Self::key_created_at_index(since, "");
+        let after_key = after.map(|c| Self::key_created_at_index(c.created_at, &c.event_id));
+        let start = match &after_key {
+            Some(key) if *key > range_start => key.clone(),
+            _ => range_start,
+        };
+        let mut last: Option<EventCursor> = None;
+        let iter = db.iterator(IteratorMode::From(&start, Direction::Forward));
+        for item in iter {
+            let (key, _) = item.context("Failed to iterate created_at index")?;
+            // key format: ts:{016x}:{event_id}
+            if !key.starts_with(b"ts:") || key.len() < 20 {
+                break;
+            }
+            if after_key.as_deref() == Some(&key[..]) {
+                continue;
+            }
+            let created_at = std::str::from_utf8(&key[3..19])
+                .ok()
+                .and_then(|hex| u64::from_str_radix(hex, 16).ok());
+            let created_at = match created_at {
+                Some(created_at) if created_at <= until => created_at,
+                _ => return Ok((events, None)),
+            };
+            if events.len() >= limit {
+                // More events remain in range; resume after the last one returned
+                return Ok((events, last));
+            }
+
+            let event_id = &key[20..];
+            last = Some(EventCursor {
+                created_at,
+                event_id: String::from_utf8_lossy(event_id).into_owned(),
+            });
+            if let Some(data) = db
+                .get(Self::key_event_bytes(event_id))
+                .context("Failed to read indexed event")?
+            {
+                let event: Event =
+                    serde_json::from_slice(&data).context("Failed to deserialize event")?;
+                if let Some(event) = self.verified(event) {
+                    events.push(event);
+                }
+            }
+        }
+
+        Ok((events, None))
+    }
+
+    /// Retrieve up to `limit` events carrying a `tag` (one of [`INDEXED_TAGS`]) with the given
+    /// value, oldest first
+    pub async fn get_events_by_tag(
+        &self,
+        tag: char,
+        value: &str,
+        limit: usize,
+    ) -> Result<Vec<Event>> {
+        anyhow::ensure!(INDEXED_TAGS.contains(&tag), "Tag '{}' is not indexed", tag);
+        let mut events = Vec::new();
+        if limit == 0 || value.contains(':') {
+            return Ok(events);
+        }
+
+        let db = self.db.read().await;
+        let prefix = Self::key_tag_prefix(tag, value);
+        for item in db.iterator(IteratorMode::From(&prefix, Direction::Forward)) {
+            let (key, _) = item.context("Failed to iterate tag index")?;
+            // Remainder after the prefix: {016x created_at}:{event_id}
+            let Some(rest) = key.strip_prefix(prefix.as_slice()) else {
+                break;
+            };
+            if rest.len() <= 17 {
+                continue;
+            }
+            if let Some(data) = db
+                .get(Self::key_event_bytes(&rest[17..]))
+                .context("Failed to read indexed event")?
+            {
+                let event: Event =
+                    serde_json::from_slice(&data).context("Failed to deserialize event")?;
+                let Some(event) = self.verified(event) else {
+                    continue;
+                };
+                events.push(event);
+                if events.len() >= limit {
+                    break;
+                }
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Delete an event by ID. Its dedup markers are keyed by the dedup id, which depends on the
+    /// dedup mode, so `DeduplicationEngine::forget` removes those.
     pub async fn delete_event(&self, event_id: &str) -> Result<()> {
+        self.ensure_writable()?;
         let db = self.db.write().await;
-        db.delete(Self::key_event(event_id))
+        let mut batch = rocksdb::WriteBatch::default();
+        // Drop the created_at and tag index entries alongside the payload
+        if let Some(data) = db
+            .get(Self::key_event(event_id))
+            .context("Failed to read event from RocksDB")?
+        {
+            if let Ok(event) = serde_json::from_slice::<Event>(&data) {
+                batch.delete(Self::key_created_at_index(
+                    event.created_at.as_secs(),
+                    event_id,
+                ));
+                for key in Self::tag_index_keys(&event, event_id) {
+                    batch.delete(key);
+                }
+            }
+        }
+        batch.delete(Self::key_event(event_id));
+        batch.delete(Self::key_forward_status(event_id));
+        db.write(batch)
             .context("Failed to delete event from RocksDB")?;
         Ok(())
     }
 
+    /// Delete events whose retention under `policy` has elapsed at `now` (unix seconds),
+    /// returning how many were removed. The stored event's `kind` selects its retention, so the
+    /// created_at index is scanned up to the shortest retention and events of longer-lived kinds
+    /// are skipped. Delivery records older than the shortest retention are no longer recent and
+    /// are dropped alongside. Deletes are applied in bounded batches so writers are not stalled.
+    pub async fn prune_expired(&self, now: u64, policy: &RetentionPolicy) -> Result<usize> {
+        self.ensure_writable()?;
+        let Some(shortest) = policy.shortest() else {
+            return Ok(0);
+        };
+        let cutoff = now.saturating_sub(shortest);
+        let end = Self::key_created_at_index(cutoff, "");
+        let success_end = Self::key_success_index(cutoff.saturating_mul(1000) as i64, "");
+        let mut start = b"ts:".to_vec();
+        let mut pruned = 0;
+        loop {
+            // Scan the next page of the created_at index under the read lock, collecting the
+            // expired index keys and the tag index and content hash entries of their events
+            let mut expired: Vec<Box<[u8]>> = Vec::new();
+            let mut event_keys: Vec<Vec<u8>> = Vec::new();
+            let mut resume_at = None;
+            let mut scanned = 0;
+            let stale_successes;
+            {
+                let db = self.db.read().await;
+                for item in db.iterator(IteratorMode::From(&start, Direction::Forward)) {
+                    let (key, _) = item.context("Failed to iterate created_at index")?;
+                    if !key.starts_with(b"ts:") || key[..] >= end[..] {
+                        break;
+                    }
+                    scanned += 1;
+                    // key format: ts:{016x}:{event_id}
+                    let event = match key.get(20..) {
+                        Some(event_id) => db
+                            .get(Self::key_event_bytes(event_id))
+                            .context("Failed to read expired event")?
+                            .and_then(|data| serde_json::from_slice::<Event>(&data).ok()),
+                        None => None,
+                    };
+                    let mut next = key.to_vec();
+                    next.push(0);
+                    resume_at = Some(next);
+                    match event {
+                        Some(event) => {
+                            let is_expired =
+                                policy
+                                    .retention_for(event.kind.as_u16())
+                                    .is_some_and(|secs| {
+                                        event.created_at.as_secs() < now.saturating_sub(secs)
+                                    });
+                            if is_expired {
+                                if let Ok(event_id) = std::str::from_utf8(&key[20..]) {
+                                    event_keys.extend(Self::tag_index_keys(&event, event_id));
+                                }
+                                // Markers written under the content hash in that dedup mode
+                                let hash = content_hash(&event).to_hex();
+                                event_keys.push(Self::key_content_hash(&hash));
+                                event_keys.push(Self::key_last_seen(&hash));
+                                expired.push(key);
+                            }
+                        }
+                        // Index entries whose event is gone or unreadable are stale
+                        None => expired.push(key),
+                    }
+                    if scanned >= PRUNE_BATCH_SIZE {
+                        break;
+                    }
+                }
+                stale_successes = Self::keys_before(&db, b"succ:", &success_end)?;
+            }
+
+            if !expired.is_empty() || !stale_successes.is_empty() {
+                let mut batch = rocksdb::WriteBatch::default();
+                for key in &expired {
+                    if key.len() > 20 {
+                        let event_id = &key[20..];
+                        batch.delete(Self::key_event_bytes(event_id));
+                        let mut fwd_key = b"fwd:".to_vec();
+                        fwd_key.extend_from_slice(event_id);
+                        batch.delete(fwd_key);
+                        batch.delete(Self::key_last_seen_bytes(event_id));
+                    }
+                    batch.delete(key);
+                }
+                for key in event_keys {
+                    batch.delete(key);
+                }
+                for key in &stale_successes {
+                    batch.delete(key);
+                }
+
+                let db = self.db.write().await;
+                db.write(batch).context("Failed to prune expired events")?;
+                pruned += expired.len();
+            }
+
+            let more_successes = stale_successes.len() >= PRUNE_BATCH_SIZE;
+            match resume_at {
+                Some(next) if scanned >= PRUNE_BATCH_SIZE => start = next,
+                // Keep clearing delivery records once the created_at index is done
+                _ if more_successes => start = end.clone(),
+                _ => break,
+            }
+        }
+        Ok(pruned)
+    }
+
+    /// Up to `PRUNE_BATCH_SIZE` keys with `prefix` that sort before `end`
+    fn keys_before(db: &DB, prefix: &[u8], end: &[u8]) -> Result<Vec<Box<[u8]>>> {
+        let mut keys = Vec::new();
+        for item in db.iterator(IteratorMode::From(prefix, Direction::Forward)) {
+            let (key, _) = item.context("Failed to iterate RocksDB keys")?;
+            if !key.starts_with(prefix) || key[..] >= end[..] || keys.len() >= PRUNE_BATCH_SIZE {
+                break;
+            }
+            keys.push(key);
+        }
+        Ok(keys)
+    }
+
+    /// Flush the write-ahead log and memtables to disk
+    pub async fn flush(&self) -> Result<()> {
+        self.ensure_writable()?;
+        let db = self.db.write().await;
+        db.flush_wal(true).context("Failed to flush RocksDB WAL")?;
+        db.flush().context("Failed to flush RocksDB memtables")?;
+        Ok(())
+    }
+
+    /// Create a consistent, point-in-time copy of the database at `dest`.
+    /// Uses RocksDB checkpoints (hard-linked SST files), so it is fast and only holds the read lock.
+    pub async fn create_checkpoint(&self, dest: &Path) -> Result<()> {
+        let db = self.db.read().await;
+        let checkpoint = Checkpoint::new(&db).context("Failed to initialize RocksDB checkpoint")?;
+        checkpoint
+            .create_checkpoint(dest)
+            .with_context(|| format!("Failed to create checkpoint at {}", dest.display()))?;
+        Ok(())
+    }
+
+    /// Write every stored event to a new file at `path` as newline-delimited JSON, returning how
+    /// many were written. Events are read in pages, so memory stays bounded and writers are only
+    /// held up for one page at a time. Fails if `path` already exists.
+    pub async fn export_jsonl(&self, path: &Path) -> Result<u64> {
+        let file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .await
+            .with_context(|| format!("Failed to create export file {}", path.display()))?;
+        let mut writer = tokio::io::BufWriter::new(file);
+        let mut start = b"evt:".to_vec();
+        let mut exported = 0;
+        loop {
+            let mut page: Vec<Box<[u8]>> = Vec::with_capacity(EXPORT_PAGE_SIZE);
+            let mut resume_at = None;
+            {
+                let db = self.db.read().await;
+                for item in db.iterator(IteratorMode::From(&start, Direction::Forward)) {
+                    let (key, value) = item.context("Failed to iterate stored events")?;
+                    if !key.starts_with(b"evt:") {
+                        break;
+                    }
+                    page.push(value);
+                    if page.len() >= EXPORT_PAGE_SIZE {
+                        let mut next = key.to_vec();
+                        next.push(0);
+                        resume_at = Some(next);
+                        break;
+                    }
+                }
+            }
+
+            for event in &page {
+                writer
+                    .write_all(event)
+                    .await
+                    .with_context(|| format!("Failed to write to {}", path.display()))?;
+                writer.write_all(b"\n").await?;
+            }
+            exported += page.len() as u64;
+            match resume_at {
+                Some(next) => start = next,
+                None => break,
+            }
+        }
+        writer
+            .flush()
+            .await
+            .with_context(|| format!("Failed to write to {}", path.display()))?;
+        Ok(exported)
+    }
+
+    /// Store the events of a newline-delimited JSON file such as one written by
+    /// [`Self::export_jsonl`], in write batches of `IMPORT_BATCH_SIZE`. Lines that are not valid
+    /// events, or whose id or signature does not verify, are skipped and counted; blank lines are
+    /// ignored. `on_batch` sees each batch once it is stored, so callers can index the events
+    /// elsewhere.
+    pub async fn import_jsonl(
+        &self,
+        path: &Path,
+        mut on_batch: impl AsyncFnMut(&[Event]),
+    ) -> Result<ImportSummary> {
+        self.ensure_writable()?;
+        let file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("Failed to open import file {}", path.display()))?;
+        let mut lines = tokio::io::BufReader::new(file).lines();
+        let mut summary = ImportSummary::default();
+        let mut batch = Vec::with_capacity(IMPORT_BATCH_SIZE);
+        let mut line_number = 0u64;
+        loop {
+            let line = lines
+                .next_line()
+                .await
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let end_of_file = line.is_none();
+            if let Some(line) = line {
+                line_number += 1;
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                // Forged ids would otherwise be marked as seen and block the real events
+                let parsed = serde_json::from_str::<Event>(line)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|event| {
+                        event.verify().context("invalid id or signature")?;
+                        Ok(event)
+                    });
+                match parsed {
+                    Ok(event) => batch.push(event),
+                    Err(e) => {
+                        tracing::debug!(
+                            "Skipping line {} of {}: {:#}",
+                            line_number,
+                            path.display(),
+                            e
+                        );
+                        summary.skipped += 1;
+                    }
+                }
+            }
+
+            if batch.len() >= IMPORT_BATCH_SIZE || (end_of_file && !batch.is_empty()) {
+                self.store_events(&batch).await?;
+                on_batch(&batch).await;
+                summary.imported += batch.len() as u64;
+                batch.clear();
+            }
+            if end_of_file {
+                break;
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Compact the whole key range, dropping tombstones left by deletes and pruning.
+    /// Does nothing on a secondary instance, which cannot write new files.
+    ///
+    /// Runs on a blocking thread and does not hold the lock, so reads and writes carry on
+    /// while a compaction that can take minutes is in progress.
+    pub async fn compact_range(&self) {
+        if self.secondary {
+            return;
+        }
+        let db = self.db.read().await.clone();
+        let compaction =
+            tokio::task::spawn_blocking(move || db.compact_range::<&[u8], &[u8]>(None, None));
+        if let Err(e) = compaction.await {
+            tracing::error!("RocksDB compaction task failed: {}", e);
+        }
+    }
+
+    /// Read integer-valued RocksDB properties, skipping any that are unavailable
+    pub async fn int_properties(&self, names: &[&'static str]) -> Vec<(&'static str, u64)> {
+        let db = self.db.read().await;
+        names
+            .iter()
+            .filter_map(|name| match db.property_int_value(name) {
+                Ok(Some(value)) => Some((*name, value)),
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Get approximate number of events in the database
     pub async fn approximate_count(&self) -> u64 {
         let db = self.db.read().await;
-        // This is an approximation, actual count may vary
-        db.iterator(rocksdb::IteratorMode::Start).count() as u64
+        // O(1) estimate maintained by RocksDB; actual count may vary
+        match db.property_int_value("rocksdb.estimate-num-keys") {
+            Ok(Some(count)) => count,
+            Ok(None) => {
+                tracing::warn!("RocksDB property rocksdb.estimate-num-keys is unavailable");
+                0
+            }
+            Err(e) => {
+                tracing::warn!("Failed to read rocksdb.estimate-num-keys: {}", e);
+                0
+            }
+        }
     }
 
-    /// Mark an event as successfully forwarded to downstream(s)
-    pub async fn mark_forward_success(&self, event_id: &str) -> Result<()> {
+    /// Mark a batch of events as successfully forwarded to downstream(s) in one write
+    pub async fn mark_forward_success_many(&self, event_ids: &[String]) -> Result<()> {
+        if event_ids.is_empty() {
+            return Ok(());
+        }
+        self.ensure_writable()?;
         let now_ms = chrono::Utc::now().timestamp_millis();
         let mut batch = rocksdb::WriteBatch::default();
-        batch.put(Self::key_forward_status(event_id), b"1");
-        batch.put(Self::key_success_index(now_ms, event_id), &[]);
+        for event_id in event_ids {
+            batch.put(Self::key_forward_status(event_id), b"1");
+            batch.put(Self::key_success_index(now_ms, event_id), &[]);
+        }
         let db = self.db.write().await;
         db.write(batch).context("Failed to mark forward success")?;
         Ok(())
@@ -157,7 +1065,14 @@ impl RocksDBStore {
                                 // event id starts after second colon
                                 let event_id_bytes = &k[second_idx + 1..];
                                 if let Ok(event_id) = std::str::from_utf8(event_id_bytes) {
-                                    result.push(event_id.to_string());
+                                    // Deleted events lose their forward status and are skipped
+                                    let forwarded = matches!(
+                                        db.get_pinned(Self::key_forward_status(event_id)),
+                                        Ok(Some(_))
+                                    );
+                                    if forwarded {
+                                        result.push(event_id.to_string());
+                                    }
                                 }
                             }
                         }
@@ -169,3 +1084,491 @@ impl RocksDBStore {
         result
     }
 }
+
+/// Hash of an event's signed fields with its tags in a fixed order, so copies that only differ
+/// in tag order (and so in id and signature) hash the same. Keys the `chash:` markers.
+pub fn content_hash(event: &Event) -> EventId {
+    EventId::new(
+        &event.pubkey,
+        &event.created_at,
+        &event.kind,
+        &canonical_tags(&event.tags),
+        &event.content,
+    )
+}
+
+/// Tags in a fixed order; only used for the content hash, stored events keep their signed order
+fn canonical_tags(tags: &Tags) -> Tags {
+    let mut sorted = tags.clone().to_vec();
+    sorted.sort_by(|a, b| a.as_slice().cmp(b.as_slice()));
+    Tags::from_list(sorted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{signed_event, signed_events, temp_store};
+    use nostr_sdk::{EventBuilder, Keys, Tag, Timestamp};
+    use std::collections::HashMap;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn retain_for(secs: u64) -> RetentionPolicy {
+        RetentionPolicy {
+            default_secs: Some(secs),
+            per_kind: HashMap::new(),
+        }
+    }
+
+    async fn has_key(store: &RocksDBStore, key: &[u8]) -> bool {
+        store.db.read().await.get(key).unwrap().is_some()
+    }
+
+    /// One note per timestamp, in `(created_at, id)` order like the `created_at` index
+    fn events_at(timestamps: &[u64]) -> Vec<Event> {
+        let mut events: Vec<Event> = timestamps
+            .iter()
+            .map(|at| signed_event("note", Timestamp::from(*at)))
+            .collect();
+        events.sort_by_key(|event| (event.created_at, event.id));
+        events
+    }
+
+    fn ids(events: &[Event]) -> Vec<EventId> {
+        events.iter().map(|event| event.id).collect()
+    }
+
+    #[tokio::test]
+    async fn exists_only_for_stored_events() {
+        let (_dir, store) = temp_store();
+        let event = signed_events(1).remove(0);
+        store.store_event(&event).await.unwrap();
+
+        assert!(store.exists(&event.id.to_hex()).await.unwrap());
+        let unknown = EventId::from_byte_array(rand::random());
+        assert!(!store.exists(&unknown.to_hex()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn exists_many_answers_in_input_order() {
+        let (_dir, store) = temp_store();
+        let events = signed_events(3);
+        store.store_events(&events[..2]).await.unwrap();
+        let unknown = EventId::from_byte_array(rand::random()).to_hex();
+        let stored = [events[0].id.to_hex(), events[1].id.to_hex()];
+        let missing = events[2].id.to_hex();
+
+        let queried = [&stored[1], &unknown, &stored[0], &missing, &stored[1]].map(String::as_str);
+        let found = store.exists_many(&queried).await.unwrap();
+        assert_eq!(found, [true, false, true, false, true]);
+        assert!(store.exists_many(&[]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn range_queries_include_both_bounds_and_respect_the_limit() {
+        let (_dir, store) = temp_store();
+        let events = events_at(&[100, 200, 200, 300, 400]);
+        store.store_events(&events).await.unwrap();
+
+        let in_range = store.get_events_in_range(200, 300, 10).await.unwrap();
+        assert_eq!(ids(&in_range), ids(&events[1..4]));
+        let limited = store.get_events_in_range(0, u64::MAX, 2).await.unwrap();
+        assert_eq!(ids(&limited), ids(&events[..2]));
+        let single = store.get_events_in_range(400, 400, 10).await.unwrap();
+        assert_eq!(ids(&single), ids(&events[4..]));
+
+        assert!(
+            store
+                .get_events_in_range(301, 399, 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            store
+                .get_events_in_range(300, 200, 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            store
+                .get_events_in_range(0, u64::MAX, 0)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn pages_resume_after_the_cursor_across_created_at_ties() {
+        let (_dir, store) = temp_store();
+        let events = events_at(&[100, 100, 100, 100, 100, 200, 200, 300]);
+        store.store_events(&events).await.unwrap();
+
+        let mut pages = Vec::new();
+        let mut cursor: Option<EventCursor> = None;
+        loop {
+            let (page, next) = store
+                .get_events_page(100, 200, cursor.as_ref(), 3)
+                .await
+                .unwrap();
+            pages.push(ids(&page));
+            match next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        // Seven events in range: the cursor lands inside the run of ties at 100 and the last
+        // page ends with the range rather than handing out a cursor to an empty page
+        assert_eq!(
+            pages,
+            [ids(&events[..3]), ids(&events[3..6]), ids(&events[6..7])]
+        );
+
+        let (page, next) = store.get_events_page(0, 200, None, 7).await.unwrap();
+        assert_eq!(ids(&page), ids(&events[..7]));
+        assert!(next.is_none());
+
+        // A cursor before `since` starts at `since`
+        let early = EventCursor {
+            created_at: 50,
+            event_id: events[0].id.to_hex(),
+        };
+        let (page, _) = store
+            .get_events_page(200, 300, Some(&early), 10)
+            .await
+            .unwrap();
+        assert_eq!(ids(&page), ids(&events[5..]));
+    }
+
+    #[tokio::test]
+    async fn approximate_count_tracks_flushed_events() {
+        let (_dir, store) = temp_store();
+        assert_eq!(store.approximate_count().await, 0);
+        store.store_events(&signed_events(1000)).await.unwrap();
+        store.flush().await.unwrap();
+
+        // The estimate covers every key, so each event's created_at index entry counts as well
+        let count = store.approximate_count().await;
+        assert!(
+            (1000..=3000).contains(&count),
+            "estimate {} out of range",
+            count
+        );
+    }
+
+    #[tokio::test]
+    async fn a_large_batch_is_stored_and_indexed_in_full() {
+        let (_dir, store) = temp_store();
+        let events = signed_events(10_000);
+        store.store_events(&events).await.unwrap();
+
+        for event in &events {
+            let found = store.get_event(&event.id.to_hex()).await.unwrap();
+            assert_eq!(found.as_ref(), Some(event));
+        }
+        let indexed = store
+            .get_events_in_range(0, u64::MAX, 20_000)
+            .await
+            .unwrap();
+        assert_eq!(indexed.len(), events.len());
+    }
+
+    #[tokio::test]
+    async fn compaction_drops_deleted_events_and_keeps_the_rest() {
+        let (_dir, store) = temp_store();
+        let events = signed_events(1000);
+        store.store_events(&events).await.unwrap();
+        store.flush().await.unwrap();
+        for event in &events[..900] {
+            store.delete_event(&event.id.to_hex()).await.unwrap();
+        }
+        store.flush().await.unwrap();
+
+        store.compact_range().await;
+
+        // Once the tombstones are compacted away the estimate only covers the survivors
+        let count = store.approximate_count().await;
+        assert!(
+            (100..=300).contains(&count),
+            "estimate {} after compaction",
+            count
+        );
+        for event in &events[900..] {
+            assert!(store.exists(&event.id.to_hex()).await.unwrap());
+        }
+        assert!(!store.exists(&events[0].id.to_hex()).await.unwrap());
+    }
+
+    /// Overwrite the stored payload of `event` with different content under the same id
+    async fn tamper(store: &RocksDBStore, event: &Event) {
+        let mut tampered = serde_json::to_value(event).unwrap();
+        tampered["content"] = "tampered".into();
+        let db = store.db.write().await;
+        db.put(
+            RocksDBStore::key_event(&event.id.to_hex()),
+            tampered.to_string(),
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn verify_on_read_drops_tampered_events() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let tuning = RocksDbTuning {
+            verify_on_read: true,
+            ..RocksDbTuning::default()
+        };
+        let store = RocksDBStore::with_tuning(dir.path().join("db"), &tuning).unwrap();
+        let events = signed_events(2);
+        store.store_events(&events).await.unwrap();
+        tamper(&store, &events[0]).await;
+
+        assert_eq!(store.get_event(&events[0].id.to_hex()).await.unwrap(), None);
+        let intact = store.get_event(&events[1].id.to_hex()).await.unwrap();
+        assert_eq!(intact.as_ref(), Some(&events[1]));
+        let listed = store.get_events_in_range(0, u64::MAX, 10).await.unwrap();
+        assert_eq!(ids(&listed), [events[1].id]);
+    }
+
+    #[tokio::test]
+    async fn tampered_events_are_returned_without_verify_on_read() {
+        let (_dir, store) = temp_store();
+        let event = signed_events(1).remove(0);
+        store.store_event(&event).await.unwrap();
+        tamper(&store, &event).await;
+
+        let found = store.get_event(&event.id.to_hex()).await.unwrap().unwrap();
+        assert_eq!(found.content, "tampered");
+    }
+
+    #[tokio::test]
+    async fn a_secondary_follows_the_primary_after_catching_up() {
+        let (dir, primary) = temp_store();
+        let events = signed_events(2);
+        primary.store_event(&events[0]).await.unwrap();
+
+        let secondary =
+            RocksDBStore::open_secondary(dir.path().join("db"), dir.path().join("secondary"))
+                .unwrap();
+        assert!(secondary.is_secondary());
+        assert!(secondary.exists(&events[0].id.to_hex()).await.unwrap());
+
+        primary.store_event(&events[1]).await.unwrap();
+        assert!(!secondary.exists(&events[1].id.to_hex()).await.unwrap());
+        secondary.catch_up_with_primary().await.unwrap();
+        assert!(secondary.exists(&events[1].id.to_hex()).await.unwrap());
+
+        assert!(secondary.store_event(&signed_events(1)[0]).await.is_err());
+        assert!(secondary.check_writable().await.is_err());
+        assert!(primary.catch_up_with_primary().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn tag_lookups_return_referencing_events_oldest_first() {
+        let (_dir, store) = temp_store();
+        let mentioned = Keys::generate().public_key();
+        let other = Keys::generate().public_key();
+        let root = signed_events(1).remove(0);
+        let tagged = |content: &str, created_at: u64, tags: Vec<Tag>| {
+            EventBuilder::text_note(content)
+                .tags(tags)
+                .custom_created_at(Timestamp::from(created_at))
+                .sign_with_keys(&Keys::generate())
+                .unwrap()
+        };
+        let newer = tagged("newer", 200, vec![Tag::public_key(mentioned)]);
+        let reply = tagged(
+            "reply",
+            100,
+            vec![Tag::public_key(mentioned), Tag::event(root.id)],
+        );
+        let unrelated = tagged("unrelated", 150, vec![Tag::public_key(other)]);
+        store
+            .store_events(&[newer.clone(), reply.clone(), unrelated])
+            .await
+            .unwrap();
+
+        let by_p = store
+            .get_events_by_tag('p', &mentioned.to_hex(), 10)
+            .await
+            .unwrap();
+        assert_eq!(ids(&by_p), [reply.id, newer.id]);
+        let limited = store
+            .get_events_by_tag('p', &mentioned.to_hex(), 1)
+            .await
+            .unwrap();
+        assert_eq!(ids(&limited), [reply.id]);
+        let by_e = store
+            .get_events_by_tag('e', &root.id.to_hex(), 10)
+            .await
+            .unwrap();
+        assert_eq!(ids(&by_e), [reply.id]);
+        assert!(
+            store
+                .get_events_by_tag('e', &newer.id.to_hex(), 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(store.get_events_by_tag('t', "nostr", 10).await.is_err());
+
+        // Deleting an event drops its index entries
+        store.delete_event(&reply.id.to_hex()).await.unwrap();
+        let by_p = store
+            .get_events_by_tag('p', &mentioned.to_hex(), 10)
+            .await
+            .unwrap();
+        assert_eq!(ids(&by_p), [newer.id]);
+        let reply_index =
+            RocksDBStore::key_tag_index('e', &root.id.to_hex(), 100, &reply.id.to_hex());
+        assert!(!has_key(&store, &reply_index).await);
+    }
+
+    #[tokio::test]
+    async fn checkpoint_opens_as_a_database_holding_every_event() {
+        let (dir, store) = temp_store();
+        let events = signed_events(5);
+        store.store_events(&events).await.unwrap();
+
+        let checkpoint = dir.path().join("checkpoint");
+        store.create_checkpoint(&checkpoint).await.unwrap();
+
+        let restored = RocksDBStore::new(&checkpoint).unwrap();
+        for event in &events {
+            let found = restored.get_event(&event.id.to_hex()).await.unwrap();
+            assert_eq!(found.map(|e| e.id), Some(event.id));
+        }
+    }
+
+    #[tokio::test]
+    async fn export_then_import_restores_every_event() {
+        let (dir, source) = temp_store();
+        let events = signed_events(2500);
+        source.store_events(&events).await.unwrap();
+        let file = dir.path().join("events.jsonl");
+        assert_eq!(source.export_jsonl(&file).await.unwrap(), 2500);
+
+        let target = RocksDBStore::new(dir.path().join("target")).unwrap();
+        let mut batches = Vec::new();
+        let summary = target
+            .import_jsonl(&file, async |batch: &[Event]| batches.push(batch.len()))
+            .await
+            .unwrap();
+
+        assert_eq!(summary.imported, 2500);
+        assert_eq!(summary.skipped, 0);
+        assert_eq!(batches, [IMPORT_BATCH_SIZE, IMPORT_BATCH_SIZE, 500]);
+        for event in &events {
+            let found = target.get_event(&event.id.to_hex()).await.unwrap();
+            assert_eq!(found.map(|e| e.id), Some(event.id));
+        }
+    }
+
+    #[tokio::test]
+    async fn import_skips_malformed_and_forged_events() {
+        let (dir, store) = temp_store();
+        let genuine = signed_events(1).remove(0);
+        let mut forged = serde_json::to_value(&genuine).unwrap();
+        forged["content"] = "tampered".into();
+        let file = dir.path().join("events.jsonl");
+        let genuine_line = serde_json::to_string(&genuine).unwrap();
+        let contents = format!("{}\n\nnot json\n{}\n", genuine_line, forged);
+        std::fs::write(&file, contents).unwrap();
+
+        let summary = store
+            .import_jsonl(&file, async |_: &[Event]| {})
+            .await
+            .unwrap();
+
+        assert_eq!(summary.imported, 1);
+        assert_eq!(summary.skipped, 2);
+        assert!(
+            store
+                .get_event(&genuine.id.to_hex())
+                .await
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn prune_drops_delivery_records_older_than_the_retention() {
+        let (_dir, store) = temp_store();
+        let old = signed_event("old", Timestamp::from(NOW - 7200));
+        let fresh = signed_event("fresh", Timestamp::from(NOW - 60));
+        store
+            .store_events(&[old.clone(), fresh.clone()])
+            .await
+            .unwrap();
+        let old_delivery =
+            RocksDBStore::key_success_index(((NOW - 7200) * 1000) as i64, &old.id.to_hex());
+        let fresh_delivery =
+            RocksDBStore::key_success_index(((NOW - 60) * 1000) as i64, &fresh.id.to_hex());
+        {
+            let db = store.db.write().await;
+            db.put(&old_delivery, []).unwrap();
+            db.put(&fresh_delivery, []).unwrap();
+        }
+
+        assert_eq!(
+            store.prune_expired(NOW, &retain_for(3600)).await.unwrap(),
+            1
+        );
+
+        assert!(store.get_event(&old.id.to_hex()).await.unwrap().is_none());
+        assert!(store.get_event(&fresh.id.to_hex()).await.unwrap().is_some());
+        assert!(!has_key(&store, &old_delivery).await);
+        assert!(has_key(&store, &fresh_delivery).await);
+    }
+
+    #[tokio::test]
+    async fn prune_drops_the_content_hash_of_expired_events() {
+        let (_dir, store) = temp_store();
+        let old = signed_event("old", Timestamp::from(NOW - 7200));
+        store.store_event(&old).await.unwrap();
+        let hash = content_hash(&old).to_hex();
+        store.store_content_hash(&hash).await.unwrap();
+
+        assert_eq!(
+            store.prune_expired(NOW, &retain_for(3600)).await.unwrap(),
+            1
+        );
+
+        assert!(!store.content_hash_exists(&hash).await.unwrap());
+    }
+
+    #[test]
+    fn open_retries_until_the_lock_is_released() {
+        let (dir, holder) = temp_store();
+        let path = dir.path().join("db");
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            drop(holder);
+        });
+        let tuning = RocksDbTuning {
+            open_retries: 8,
+            open_retry_delay_ms: 20,
+            ..RocksDbTuning::default()
+        };
+
+        RocksDBStore::with_tuning(&path, &tuning).unwrap();
+        release.join().unwrap();
+    }
+
+    #[test]
+    fn open_reports_a_held_lock_once_retries_run_out() {
+        let (dir, _holder) = temp_store();
+        let tuning = RocksDbTuning {
+            open_retries: 1,
+            open_retry_delay_ms: 1,
+            ..RocksDbTuning::default()
+        };
+
+        let Err(e) = RocksDBStore::with_tuning(dir.path().join("db"), &tuning) else {
+            panic!("opened a database whose lock is held");
+        };
+        assert!(format!("{:#}", e).contains(OpenFailure::Unavailable.describe()));
+    }
+}
@@ -0,0 +1,206 @@
+//! Fixtures shared by the unit tests
+
+use axum::Router;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::routing::any;
+use futures_util::{SinkExt, StreamExt};
+use nostr_sdk::{Event, EventBuilder, Keys, Timestamp};
+use serde_json::Value;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio_tungstenite::tungstenite::{self, protocol::CloseFrame};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::api::metrics::Metrics;
+use crate::storage::rocksdb_store::RocksDBStore;
+
+/// A text note signed by a fresh key
+pub fn signed_event(content: &str, created_at: Timestamp) -> Event {
+    EventBuilder::text_note(content)
+        .custom_created_at(created_at)
+        .sign_with_keys(&Keys::generate())
+        .expect("signing a test event")
+}
+
+/// `count` distinct text notes created now
+pub fn signed_events(count: usize) -> Vec<Event> {
+    (0..count)
+        .map(|i| signed_event(&format!("test note {}", i), Timestamp::now()))
+        .collect()
+}
+
+/// A store in a fresh temporary directory, deleted once the returned `TempDir` is dropped
+pub fn temp_store() -> (TempDir, Arc<RocksDBStore>) {
+    let dir = TempDir::new().unwrap();
+    let store = Arc::new(RocksDBStore::new(dir.path().join("db")).unwrap());
+    (dir, store)
+}
+
+/// Start a relay that answers every REQ with EOSE and never sends events; returns its URL
+pub async fn mock_relay() -> String {
+    relay_sending(Vec::new()).await
+}
+
+/// Start a relay that answers every REQ with an `EVENT` frame for each payload, sent as given
+/// whether or not it is a valid event, followed by EOSE; returns its URL
+pub async fn relay_sending(payloads: Vec<Value>) -> String {
+    let payloads = Arc::new(payloads);
+    let app = Router::new().route(
+        "/",
+        any(move |ws: WebSocketUpgrade| {
+            let payloads = payloads.clone();
+            async move { ws.on_upgrade(move |socket| serve_mock_relay(socket, payloads)) }
+        }),
+    );
+    serve(app).await.replacen("http://", "ws://", 1)
+}
+
+async fn serve_mock_relay(mut socket: WebSocket, payloads: Arc<Vec<Value>>) {
+    while let Some(Ok(message)) = socket.recv().await {
+        let Message::Text(text) = message else {
+            continue;
+        };
+        let Some(eose) = eose_reply(text.as_str()) else {
+            continue;
+        };
+        let subscription_id = &serde_json::from_str::<Value>(text.as_str()).unwrap()[1];
+        let frames = payloads
+            .iter()
+            .map(|payload| serde_json::json!(["EVENT", subscription_id, payload]).to_string())
+            .chain(std::iter::once(eose));
+        for frame in frames {
+            if socket.send(Message::Text(frame.into())).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// The mock relay's answer to a client frame: `EOSE` for a `REQ`, nothing otherwise
+fn eose_reply(text: &str) -> Option<String> {
+    let frame = serde_json::from_str::<Vec<Value>>(text).ok()?;
+    match (frame.first().and_then(|value| value.as_str()), frame.get(1)) {
+        (Some("REQ"), Some(subscription_id)) => {
+            Some(serde_json::json!(["EOSE", subscription_id]).to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Start a relay like [`mock_relay`] that goes away together with all its connections once
+/// the returned task is aborted; returns its URL and the task
+pub async fn closable_relay() -> (String, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let relay = tokio::spawn(async move {
+        // Aborting the relay drops the set, which aborts every connection
+        let mut connections = JoinSet::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            connections.spawn(async move {
+                let Ok(mut socket) = tokio_tungstenite::accept_async(stream).await else {
+                    return;
+                };
+                while let Some(Ok(message)) = socket.next().await {
+                    let tungstenite::Message::Text(text) = message else {
+                        continue;
+                    };
+                    let Some(eose) = eose_reply(text.as_str()) else {
+                        continue;
+                    };
+                    if socket
+                        .send(tungstenite::Message::Text(eose.into()))
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    (url, relay)
+}
+
+/// Metrics shared by every test; the Prometheus registry refuses to register them twice
+pub fn metrics() -> Arc<Metrics> {
+    static METRICS: OnceLock<Arc<Metrics>> = OnceLock::new();
+    METRICS.get_or_init(|| Arc::new(Metrics::default())).clone()
+}
+
+/// Serve `router` on a local port; returns its base URL
+pub async fn serve(router: Router) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, router).await });
+    url
+}
+
+/// URL of a relay that refuses connections
+pub async fn unreachable_relay() -> String {
+    // Nothing listens on the port once the listener is dropped
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    format!("ws://{}", listener.local_addr().unwrap())
+}
+
+/// How long a WebSocket test waits for a frame before failing
+const WS_RECV_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// WebSocket client for tests, speaking JSON text frames
+pub struct WsClient(WebSocketStream<MaybeTlsStream<TcpStream>>);
+
+impl WsClient {
+    /// Connect to `url` (`http://` is rewritten to `ws://`)
+    pub async fn connect(url: &str) -> Self {
+        let url = url.replacen("http://", "ws://", 1);
+        let (stream, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        Self(stream)
+    }
+
+    pub async fn send(&mut self, frame: Value) {
+        let frame = tungstenite::Message::Text(frame.to_string().into());
+        self.0.send(frame).await.unwrap();
+    }
+
+    /// The next text frame; panics if the connection closes or nothing arrives in time
+    pub async fn recv(&mut self) -> Value {
+        self.try_recv(WS_RECV_TIMEOUT)
+            .await
+            .expect("no WebSocket text frame received")
+    }
+
+    /// The next text frame, or `None` if none arrives within `wait` or the connection closes.
+    /// Pings are answered by the client library and skipped.
+    pub async fn try_recv(&mut self, wait: Duration) -> Option<Value> {
+        let deadline = tokio::time::Instant::now() + wait;
+        loop {
+            let message = tokio::time::timeout_at(deadline, self.0.next())
+                .await
+                .ok()??;
+            match message.ok()? {
+                tungstenite::Message::Text(text) => {
+                    return Some(serde_json::from_str(text.as_str()).unwrap());
+                }
+                tungstenite::Message::Close(_) => return None,
+                _ => {}
+            }
+        }
+    }
+
+    /// Read until the server closes the connection, returning its close frame
+    pub async fn closed(&mut self) -> Option<CloseFrame> {
+        let deadline = tokio::time::Instant::now() + WS_RECV_TIMEOUT;
+        loop {
+            let message = tokio::time::timeout_at(deadline, self.0.next())
+                .await
+                .expect("WebSocket connection was not closed");
+            match message {
+                Some(Ok(tungstenite::Message::Close(frame))) => return frame,
+                Some(Ok(_)) => {}
+                Some(Err(_)) | None => return None,
+            }
+        }
+    }
+}